@@ -0,0 +1,196 @@
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+/// Derives `PlainData` and `Pixel` for a `#[repr(C)]` or `#[repr(transparent)]` struct whose
+/// fields are all `PlainData`. Padding between or after the fields fails to compile.
+///
+/// The impls refer to the library as `::generic_image`. When it is renamed or only reachable
+/// through a re-export, name it with `#[generic_image(crate = path::to::generic_image)]`.
+#[proc_macro_derive(Pixel, attributes(generic_image))]
+pub fn derive_pixel(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err(message) => format!("::core::compile_error!({message:?});")
+            .parse()
+            .unwrap(),
+    }
+}
+
+struct Field {
+    /// The field name, or its position for tuple structs.
+    name: String,
+    ty: String,
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let mut tokens = input.into_iter();
+    let mut repr_ok = false;
+    let mut krate = String::from("::generic_image");
+    let name = loop {
+        match tokens.next() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.next() {
+                    repr_ok |= is_plain_repr(&group);
+                    if let Some(path) = crate_path(&group)? {
+                        krate = path;
+                    }
+                }
+            }
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => match tokens.next() {
+                Some(TokenTree::Ident(name)) => break name.to_string(),
+                _ => return Err("expected a struct name".into()),
+            },
+            Some(TokenTree::Ident(ident)) if matches!(&*ident.to_string(), "enum" | "union") => {
+                return Err("`Pixel` can only be derived for structs".into())
+            }
+            Some(_) => {}
+            None => return Err("expected a struct".into()),
+        }
+    };
+    if !repr_ok {
+        return Err(format!(
+            "`{name}` needs `#[repr(C)]` or `#[repr(transparent)]` to derive `Pixel`"
+        ));
+    }
+    let fields = match tokens.next() {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("`Pixel` can not be derived for generic structs".into())
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+            parse_fields(group.stream(), true)
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            parse_fields(group.stream(), false)
+        }
+        _ => Vec::new(),
+    };
+
+    let krate = format!("{krate}::plain");
+    let mut channels = String::new();
+    let mut checks = String::new();
+    let mut sizes = String::from("0");
+    for Field { name: field, ty } in &fields {
+        channels += &format!(
+            "{krate}::ChannelInfo {{ name: {field:?}, offset: ::core::mem::offset_of!({name}, {field}), \
+             size: ::core::mem::size_of::<{ty}>(), ty: {ty:?} }},"
+        );
+        checks += &format!("plain::<{ty}>();");
+        sizes += &format!(" + ::core::mem::size_of::<{ty}>()");
+    }
+    Ok(format!(
+        "unsafe impl {krate}::PlainData for {name} {{}}
+        impl {krate}::Pixel for {name} {{
+            const CHANNELS: &'static [{krate}::ChannelInfo] = &[{channels}];
+        }}
+        const _: () = {{
+            const fn plain<T: {krate}::PlainData>() {{}}
+            {checks}
+            ::core::assert!(
+                ::core::mem::size_of::<{name}>() == {sizes},
+                \"`{name}` has padding bytes, which `Pixel` does not allow\",
+            );
+        }};"
+    )
+    .parse()
+    .unwrap())
+}
+
+fn is_plain_repr(attribute: &Group) -> bool {
+    let mut tokens = attribute.stream().into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(args))) if ident.to_string() == "repr" => {
+            args.stream().into_iter().any(|token| {
+                matches!(&token, TokenTree::Ident(ident) if matches!(&*ident.to_string(), "C" | "transparent"))
+            })
+        }
+        _ => false,
+    }
+}
+
+/// The path given by `#[generic_image(crate = path)]`, if `attribute` is one.
+fn crate_path(attribute: &Group) -> Result<Option<String>, String> {
+    let mut tokens = attribute.stream().into_iter();
+    let args = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(args)))
+            if ident.to_string() == "generic_image" =>
+        {
+            args.stream().into_iter().collect::<Vec<_>>()
+        }
+        _ => return Ok(None),
+    };
+    match &args[..] {
+        [TokenTree::Ident(key), TokenTree::Punct(eq), path @ ..]
+            if key.to_string() == "crate" && eq.as_char() == '=' && !path.is_empty() =>
+        {
+            Ok(Some(
+                path.iter().cloned().collect::<TokenStream>().to_string(),
+            ))
+        }
+        _ => Err("expected `#[generic_image(crate = path)]`".into()),
+    }
+}
+
+/// Splits the fields of a struct body, skipping attributes and visibility.
+fn parse_fields(body: TokenStream, named: bool) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut current: Vec<TokenTree> = Vec::new();
+    let mut depth = 0;
+    let mut tokens = body.into_iter();
+    loop {
+        let token = tokens.next();
+        let end = match &token {
+            None => true,
+            Some(TokenTree::Punct(punct)) => match punct.as_char() {
+                '<' => {
+                    depth += 1;
+                    false
+                }
+                '>' if depth > 0 => {
+                    depth -= 1;
+                    false
+                }
+                ',' => depth == 0,
+                _ => false,
+            },
+            _ => false,
+        };
+        if end {
+            if !current.is_empty() {
+                let index = fields.len();
+                fields.push(field(std::mem::take(&mut current), named, index));
+            }
+            if token.is_none() {
+                return fields;
+            }
+        } else {
+            current.extend(token);
+        }
+    }
+}
+
+fn field(tokens: Vec<TokenTree>, named: bool, index: usize) -> Field {
+    let mut rest = &tokens[..];
+    loop {
+        match rest {
+            [TokenTree::Punct(punct), TokenTree::Group(_), tail @ ..] if punct.as_char() == '#' => {
+                rest = tail
+            }
+            [TokenTree::Ident(ident), TokenTree::Group(group), tail @ ..]
+                if ident.to_string() == "pub" && group.delimiter() == Delimiter::Parenthesis =>
+            {
+                rest = tail
+            }
+            [TokenTree::Ident(ident), tail @ ..] if ident.to_string() == "pub" => rest = tail,
+            _ => break,
+        }
+    }
+    let (name, ty) = if named {
+        // `name: Type`
+        (rest[0].to_string(), &rest[2..])
+    } else {
+        (index.to_string(), rest)
+    };
+    Field {
+        name,
+        ty: ty.iter().cloned().collect::<TokenStream>().to_string(),
+    }
+}
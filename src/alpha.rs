@@ -0,0 +1,155 @@
+use crate::{
+    channel::Channel,
+    error::SizeMismatch,
+    pixel::{LumaA, Rgba},
+    Image,
+};
+
+/// Pixels whose color channels are already multiplied by their alpha.
+pub trait Premultiplied: Copy {
+    /// Source-over compositing of `self` on top of `below`.
+    fn over(self, below: Self) -> Self;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PremultipliedRgba<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+    pub a: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PremultipliedLumaA<T> {
+    pub l: T,
+    pub a: T,
+}
+
+impl<T: Channel> Rgba<T> {
+    pub fn premultiply(self) -> PremultipliedRgba<T> {
+        PremultipliedRgba {
+            r: self.r.mul_alpha(self.a),
+            g: self.g.mul_alpha(self.a),
+            b: self.b.mul_alpha(self.a),
+            a: self.a,
+        }
+    }
+}
+
+impl<T: Channel> PremultipliedRgba<T> {
+    pub fn unpremultiply(self) -> Rgba<T> {
+        Rgba {
+            r: self.r.div_alpha(self.a),
+            g: self.g.div_alpha(self.a),
+            b: self.b.div_alpha(self.a),
+            a: self.a,
+        }
+    }
+}
+
+impl<T: Channel> LumaA<T> {
+    pub fn premultiply(self) -> PremultipliedLumaA<T> {
+        PremultipliedLumaA {
+            l: self.l.mul_alpha(self.a),
+            a: self.a,
+        }
+    }
+}
+
+impl<T: Channel> PremultipliedLumaA<T> {
+    pub fn unpremultiply(self) -> LumaA<T> {
+        LumaA {
+            l: self.l.div_alpha(self.a),
+            a: self.a,
+        }
+    }
+}
+
+impl<T: Channel> Premultiplied for PremultipliedRgba<T> {
+    fn over(self, below: Self) -> Self {
+        let remaining = self.a.invert();
+        Self {
+            r: self.r.saturating_add(below.r.mul_alpha(remaining)),
+            g: self.g.saturating_add(below.g.mul_alpha(remaining)),
+            b: self.b.saturating_add(below.b.mul_alpha(remaining)),
+            a: self.a.saturating_add(below.a.mul_alpha(remaining)),
+        }
+    }
+}
+
+impl<T: Channel> Premultiplied for PremultipliedLumaA<T> {
+    fn over(self, below: Self) -> Self {
+        let remaining = self.a.invert();
+        Self {
+            l: self.l.saturating_add(below.l.mul_alpha(remaining)),
+            a: self.a.saturating_add(below.a.mul_alpha(remaining)),
+        }
+    }
+}
+
+impl<Source, T> Image<Source, Rgba<T>>
+where
+    Source: AsRef<[Rgba<T>]>,
+    T: Channel,
+{
+    pub fn premultiply(&self) -> Image<Box<[PremultipliedRgba<T>]>, PremultipliedRgba<T>> {
+        self.map(|pixel| pixel.premultiply())
+    }
+}
+
+impl<Source, T> Image<Source, PremultipliedRgba<T>>
+where
+    Source: AsRef<[PremultipliedRgba<T>]>,
+    T: Channel,
+{
+    pub fn unpremultiply(&self) -> Image<Box<[Rgba<T>]>, Rgba<T>> {
+        self.map(|pixel| pixel.unpremultiply())
+    }
+}
+
+impl<Source, T> Image<Source, LumaA<T>>
+where
+    Source: AsRef<[LumaA<T>]>,
+    T: Channel,
+{
+    pub fn premultiply(&self) -> Image<Box<[PremultipliedLumaA<T>]>, PremultipliedLumaA<T>> {
+        self.map(|pixel| pixel.premultiply())
+    }
+}
+
+impl<Source, T> Image<Source, PremultipliedLumaA<T>>
+where
+    Source: AsRef<[PremultipliedLumaA<T>]>,
+    T: Channel,
+{
+    pub fn unpremultiply(&self) -> Image<Box<[LumaA<T>]>, LumaA<T>> {
+        self.map(|pixel| pixel.unpremultiply())
+    }
+}
+
+impl<Source, Pixel> Image<Source, Pixel>
+where
+    Source: AsRef<[Pixel]> + AsMut<[Pixel]>,
+    Pixel: Premultiplied,
+{
+    /// Composites `top` over this image in place.
+    pub fn composite_over<Top: AsRef<[Pixel]>>(
+        &mut self,
+        top: &Image<Top, Pixel>,
+    ) -> Result<(), SizeMismatch> {
+        if self.width() != top.width() || self.height() != top.height() {
+            return Err(SizeMismatch {
+                left: [self.width(), self.height()],
+                right: [top.width(), top.height()],
+            });
+        }
+        for (row, top_row) in self.iter_rows_mut().zip(top.iter_rows()) {
+            for (below, above) in row.iter_mut().zip(top_row) {
+                *below = above.over(*below);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{
+    error::{PositionOutOfRange, SourceTooSmall, WhichAxes},
+    Image, ImageIndex,
+};
+use core::ops::Range;
+
+/// An image of `BITS`-bit values (1, 2 or 4) packed most significant bit first.
+///
+/// Every row starts on a byte boundary `stride` bytes after the previous one. The first pixel of
+/// each row sits `offset` bits into it, which lets regions start in the middle of a byte.
+pub struct BitImage<Source, const BITS: usize>
+where
+    Source: AsRef<[u8]>,
+{
+    width: usize,
+    height: usize,
+    stride: usize,
+    offset: usize,
+    source: Source,
+}
+
+pub type BitImage1<Source> = BitImage<Source, 1>;
+pub type BitImage2<Source> = BitImage<Source, 2>;
+pub type BitImage4<Source> = BitImage<Source, 4>;
+
+const fn replicate<const BITS: usize>(value: u8) -> u8 {
+    let mut byte = 0;
+    let mut i = 0;
+    while i < 8 / BITS {
+        byte = (byte << BITS) | value;
+        i += 1;
+    }
+    byte
+}
+
+impl<Source, const BITS: usize> BitImage<Source, BITS>
+where
+    Source: AsRef<[u8]>,
+{
+    const VALID: () = assert!(BITS == 1 || BITS == 2 || BITS == 4);
+    pub const MAX: u8 = ((1u16 << BITS) - 1) as u8;
+    const PER_BYTE: usize = 8 / BITS;
+
+    pub const fn row_bytes(width: usize) -> usize {
+        (width * BITS).div_ceil(8)
+    }
+    /// # Safety
+    /// `stride` is in bytes and must be at least [`Self::row_bytes`] of `width`, and `source` must
+    /// hold at least `stride * height` bytes.
+    pub const unsafe fn from_source_with_stride_unchecked(
+        width: usize,
+        height: usize,
+        stride: usize,
+        source: Source,
+    ) -> Self {
+        let () = Self::VALID;
+        Self {
+            width,
+            height,
+            stride,
+            offset: 0,
+            source,
+        }
+    }
+    pub fn from_source(
+        width: usize,
+        height: usize,
+        source: Source,
+    ) -> Result<Self, SourceTooSmall<Source, u8>> {
+        Self::from_source_with_stride(width, height, Self::row_bytes(width), source)
+    }
+    /// `stride` is in bytes.
+    pub fn from_source_with_stride(
+        width: usize,
+        height: usize,
+        stride: usize,
+        source: Source,
+    ) -> Result<Self, SourceTooSmall<Source, u8>> {
+        if stride < Self::row_bytes(width) || source.as_ref().len() < stride * height {
+            Err(SourceTooSmall::new(source, width, height, stride))
+        } else {
+            Ok(unsafe { Self::from_source_with_stride_unchecked(width, height, stride, source) })
+        }
+    }
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+    pub const fn stride(&self) -> usize {
+        self.stride
+    }
+    /// The bit position of the first pixel within every row.
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// # Safety
+    /// Only part of the source is the image: each row starts `stride` bytes after the previous
+    /// one, its first pixel `offset` bits (always below 8) into that byte. The bits around it are
+    /// padding or, for a region, pixels of the image it was taken from.
+    pub const unsafe fn source(&self) -> &Source {
+        &self.source
+    }
+    /// # Safety
+    /// See [`Self::source`].
+    pub unsafe fn into_source(self) -> Source {
+        self.source
+    }
+    /// An empty image with the same geometry, in pixels, for resolving an [`ImageIndex`].
+    fn geometry(&self) -> Image<[(); 0], ()> {
+        unsafe {
+            Image::from_source_with_stride_unchecked(
+                self.width,
+                self.height,
+                self.stride * Self::PER_BYTE,
+                [],
+            )
+        }
+    }
+    fn position(&self, index: impl ImageIndex) -> Result<[usize; 2], PositionOutOfRange> {
+        let geometry = self.geometry();
+        // Indices past the end of a row or the image resolve to a position outside it.
+        let [x, y] = index.pos(&geometry).unwrap_or_else(|err| {
+            let stride = geometry.stride();
+            [err.value % stride, err.value / stride]
+        });
+        geometry.pos_to_index(x, y)?;
+        Ok([x, y])
+    }
+    /// The byte index and shift of the pixel at `[x, y]`.
+    const fn locate(&self, x: usize, y: usize) -> (usize, usize) {
+        let bit = self.offset + x * BITS;
+        (y * self.stride + bit / 8, 8 - BITS - bit % 8)
+    }
+    pub fn get(&self, index: impl ImageIndex) -> Option<u8> {
+        let [x, y] = self.position(index).ok()?;
+        let (byte, shift) = self.locate(x, y);
+        Some((self.source.as_ref()[byte] >> shift) & Self::MAX)
+    }
+    pub fn row(&self, y: usize) -> Option<BitRow<'_, BITS>> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.stride;
+        let end = start + (self.offset + self.width * BITS).div_ceil(8);
+        Some(BitRow {
+            bytes: &self.source.as_ref()[start..end],
+            bit: self.offset,
+            end: self.offset + self.width * BITS,
+        })
+    }
+    pub fn iter_rows(&self) -> impl Iterator<Item = BitRow<'_, BITS>> {
+        (0..self.height).map(|y| self.row(y).unwrap())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = ([usize; 2], u8)> + '_ {
+        self.iter_rows()
+            .enumerate()
+            .flat_map(|(y, row)| row.enumerate().map(move |(x, value)| ([x, y], value)))
+    }
+    fn region_bounds(
+        &self,
+        range: Range<impl ImageIndex>,
+    ) -> Result<([usize; 4], Range<usize>), crate::Error<Source, u8>> {
+        let geometry = self.geometry();
+        let [x0, y0] = range.start.pos(&geometry)?;
+        let [x1, y1] = range.end.pos(&geometry)?;
+        let x_out_of_range = x1 > self.width || x0 > x1;
+        let y_out_of_range = y1 > self.height || y0 > y1;
+        if x_out_of_range || y_out_of_range {
+            return Err(PositionOutOfRange {
+                pos: [x1, y1],
+                which_axes: match (x_out_of_range, y_out_of_range) {
+                    (true, true) => WhichAxes::Both,
+                    (true, false) => WhichAxes::X,
+                    _ => WhichAxes::Y,
+                },
+            }
+            .into());
+        }
+        let (width, height) = (x1 - x0, y1 - y0);
+        let bit = self.offset + x0 * BITS;
+        let start = y0 * self.stride + bit / 8;
+        let end = if height == 0 {
+            start
+        } else {
+            start + (height - 1) * self.stride + (bit % 8 + width * BITS).div_ceil(8)
+        };
+        Ok(([width, height, bit % 8, self.stride], start..end))
+    }
+    /// Like [`Image::region`], with `range.end` exclusive on both axes.
+    pub fn region(
+        &self,
+        range: Range<impl ImageIndex>,
+    ) -> Result<BitImage<&[u8], BITS>, crate::Error<Source, u8>> {
+        let ([width, height, offset, stride], bytes) = self.region_bounds(range)?;
+        Ok(BitImage {
+            width,
+            height,
+            stride,
+            offset,
+            source: &self.source.as_ref()[bytes],
+        })
+    }
+    pub fn to_image(&self) -> Image<Box<[u8]>, u8> {
+        let mut buf = Vec::with_capacity(self.width * self.height);
+        for row in self.iter_rows() {
+            if row.bit % 8 == 0 {
+                let full = self.width / Self::PER_BYTE;
+                for byte in &row.bytes[..full] {
+                    for i in (0..Self::PER_BYTE).rev() {
+                        buf.push((byte >> (i * BITS)) & Self::MAX);
+                    }
+                }
+                buf.extend(row.skip(full * Self::PER_BYTE));
+            } else {
+                buf.extend(row);
+            }
+        }
+        unsafe { Image::from_source_unchecked(self.width, self.height, buf.into_boxed_slice()) }
+    }
+}
+
+impl<Source, const BITS: usize> BitImage<Source, BITS>
+where
+    Source: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// # Safety
+    /// See [`Self::source`]. The source must stay long enough to hold every row, and bits
+    /// outside the image should be left alone, as they may belong to another image.
+    pub unsafe fn source_mut(&mut self) -> &mut Source {
+        &mut self.source
+    }
+    /// Only the low `BITS` bits of `value` are used.
+    pub fn set(&mut self, index: impl ImageIndex, value: u8) -> Result<(), PositionOutOfRange> {
+        let [x, y] = self.position(index)?;
+        let (byte, shift) = self.locate(x, y);
+        let byte = &mut self.source.as_mut()[byte];
+        *byte = (*byte & !(Self::MAX << shift)) | ((value & Self::MAX) << shift);
+        Ok(())
+    }
+    pub fn fill(&mut self, value: u8) {
+        let pattern = replicate::<BITS>(value & Self::MAX);
+        let (width, stride, offset) = (self.width, self.stride, self.offset);
+        if width == 0 {
+            return;
+        }
+        let end = offset + width * BITS;
+        for y in 0..self.height {
+            let row = &mut self.source.as_mut()[y * stride..][..end.div_ceil(8)];
+            let head = offset / 8;
+            let tail = end / 8;
+            if head == tail {
+                let mask = (0xFF >> (offset % 8)) & !(0xFF >> (end % 8));
+                row[head] = (row[head] & !mask) | (pattern & mask);
+                continue;
+            }
+            let mut full = head..tail;
+            if !offset.is_multiple_of(8) {
+                let mask = 0xFF >> (offset % 8);
+                row[head] = (row[head] & !mask) | (pattern & mask);
+                full.start += 1;
+            }
+            row[full].fill(pattern);
+            if !end.is_multiple_of(8) {
+                let mask = !(0xFF >> (end % 8));
+                row[tail] = (row[tail] & !mask) | (pattern & mask);
+            }
+        }
+    }
+    pub fn region_mut(
+        &mut self,
+        range: Range<impl ImageIndex>,
+    ) -> Result<BitImage<&mut [u8], BITS>, crate::Error<Source, u8>> {
+        let ([width, height, offset, stride], bytes) = self.region_bounds(range)?;
+        Ok(BitImage {
+            width,
+            height,
+            stride,
+            offset,
+            source: &mut self.source.as_mut()[bytes],
+        })
+    }
+}
+
+impl<const BITS: usize> BitImage<Box<[u8]>, BITS> {
+    pub fn filled(width: usize, height: usize, value: u8) -> Self {
+        let stride = Self::row_bytes(width);
+        let source = vec![replicate::<BITS>(value & Self::MAX); stride * height].into_boxed_slice();
+        unsafe { Self::from_source_with_stride_unchecked(width, height, stride, source) }
+    }
+    /// Packs the low `BITS` bits of every pixel.
+    pub fn from_image<Source: AsRef<[u8]>>(image: &Image<Source, u8>) -> Self {
+        let stride = Self::row_bytes(image.width());
+        let mut source = Vec::with_capacity(stride * image.height());
+        for row in image.iter_rows() {
+            for chunk in row.chunks(Self::PER_BYTE) {
+                let byte = chunk
+                    .iter()
+                    .fold(0, |byte, value| (byte << BITS) | (value & Self::MAX));
+                source.push(byte << ((Self::PER_BYTE - chunk.len()) * BITS));
+            }
+        }
+        unsafe {
+            Self::from_source_with_stride_unchecked(
+                image.width(),
+                image.height(),
+                stride,
+                source.into_boxed_slice(),
+            )
+        }
+    }
+}
+
+/// The values of one row of a [`BitImage`].
+#[derive(Clone)]
+pub struct BitRow<'a, const BITS: usize> {
+    bytes: &'a [u8],
+    bit: usize,
+    end: usize,
+}
+
+impl<'a, const BITS: usize> Iterator for BitRow<'a, BITS> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.bit == self.end {
+            None
+        } else {
+            let value = (self.bytes[self.bit / 8] >> (8 - BITS - self.bit % 8)) & ((1 << BITS) - 1);
+            self.bit += BITS;
+            Some(value)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.bit) / BITS;
+        (len, Some(len))
+    }
+    fn nth(&mut self, n: usize) -> Option<u8> {
+        self.bit = (self.bit + n * BITS).min(self.end);
+        self.next()
+    }
+}
+
+impl<'a, const BITS: usize> ExactSizeIterator for BitRow<'a, BITS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 11;
+    const HEIGHT: usize = 3;
+    const PADDING: u8 = 0b1010_0101;
+
+    fn value<const BITS: usize>(x: usize, y: usize) -> u8 {
+        ((x * 3 + y * 5) % (1 << BITS)) as u8
+    }
+
+    /// Rows of `WIDTH` pixels, which never fill their last byte, followed by a padding byte.
+    fn blank<const BITS: usize>() -> BitImage<Vec<u8>, BITS> {
+        let stride = BitImage::<Vec<u8>, BITS>::row_bytes(WIDTH) + 1;
+        let source = vec![PADDING; stride * HEIGHT];
+        BitImage::from_source_with_stride(WIDTH, HEIGHT, stride, source)
+            .ok()
+            .unwrap()
+    }
+
+    fn sample<const BITS: usize>() -> BitImage<Vec<u8>, BITS> {
+        let mut image = blank();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                image.set([x, y], value::<BITS>(x, y)).unwrap();
+            }
+        }
+        image
+    }
+
+    /// The source bits that are not part of the image.
+    fn padding<const BITS: usize>(image: &BitImage<Vec<u8>, BITS>) -> Vec<u8> {
+        let source = unsafe { image.source() };
+        let end = WIDTH * BITS;
+        source
+            .chunks(image.stride())
+            .flat_map(|row| {
+                let mut row = row[end / 8..].to_vec();
+                row[0] &= 0xff >> (end % 8);
+                row
+            })
+            .collect()
+    }
+
+    fn get_and_set<const BITS: usize>() {
+        let mut image = sample::<BITS>();
+        for y in 0..HEIGHT {
+            assert!(image
+                .row(y)
+                .unwrap()
+                .eq((0..WIDTH).map(|x| value::<BITS>(x, y))));
+            for x in 0..WIDTH {
+                assert_eq!(image.get([x, y]), Some(value::<BITS>(x, y)));
+            }
+        }
+        let untouched = padding(&blank::<BITS>());
+        assert_eq!(padding(&image), untouched);
+        let pixels = image.to_image();
+        assert!(pixels.iter().all(|([x, y], &v)| v == value::<BITS>(x, y)));
+        let packed = BitImage::<_, BITS>::from_image(&pixels);
+        assert!(packed.iter().eq(image.iter()));
+
+        // Only the low bits are stored.
+        image
+            .set([1, 1], 0xf0 | BitImage::<Vec<u8>, BITS>::MAX)
+            .unwrap();
+        assert_eq!(image.get([1, 1]), Some(BitImage::<Vec<u8>, BITS>::MAX));
+
+        assert_eq!(image.get([WIDTH, 0]), None);
+        assert_eq!(image.get([0, HEIGHT]), None);
+        assert!(matches!(
+            image.set([WIDTH, 0], 1),
+            Err(PositionOutOfRange {
+                which_axes: WhichAxes::X,
+                ..
+            })
+        ));
+        assert!(matches!(
+            image.set([0, HEIGHT], 1),
+            Err(PositionOutOfRange {
+                which_axes: WhichAxes::Y,
+                ..
+            })
+        ));
+        assert!(matches!(
+            image.set([WIDTH, HEIGHT], 1),
+            Err(PositionOutOfRange {
+                which_axes: WhichAxes::Both,
+                ..
+            })
+        ));
+        // The padding of a row, addressed by index.
+        assert!(image.set(WIDTH, 1).is_err());
+        assert_eq!(padding(&image), untouched);
+    }
+
+    fn regions<const BITS: usize>() {
+        let mut image = sample::<BITS>();
+        let region = image.region([3, 1]..[10, 2]).ok().unwrap();
+        assert_eq!([region.width(), region.height()], [7, 1]);
+        assert_eq!(region.offset(), 3 * BITS % 8);
+        assert!(region
+            .iter()
+            .all(|([x, y], v)| v == value::<BITS>(x + 3, y + 1)));
+
+        let mut region = image.region_mut([3, 1]..[10, 2]).ok().unwrap();
+        region.set([0, 0], 0).unwrap();
+        region.set([6, 0], 0).unwrap();
+        assert!(region.set([7, 0], 0).is_err());
+        assert_eq!(image.get([3, 1]), Some(0));
+        assert_eq!(image.get([9, 1]), Some(0));
+        assert_eq!(image.get([2, 1]), Some(value::<BITS>(2, 1)));
+        assert_eq!(image.get([10, 1]), Some(value::<BITS>(10, 1)));
+
+        // Filling a region leaves its neighbours alone.
+        let max = BitImage::<Vec<u8>, BITS>::MAX;
+        for [x0, x1] in [[1, 2], [3, 10], [0, 11], [5, 5]] {
+            let mut image = sample::<BITS>();
+            image.region_mut([x0, 0]..[x1, 3]).ok().unwrap().fill(max);
+            for ([x, y], v) in image.iter() {
+                let expected = if (x0..x1).contains(&x) {
+                    max
+                } else {
+                    value::<BITS>(x, y)
+                };
+                assert_eq!(v, expected, "{x0}..{x1} at {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn one_bit() {
+        get_and_set::<1>();
+        regions::<1>();
+    }
+
+    #[test]
+    fn two_bits() {
+        get_and_set::<2>();
+        regions::<2>();
+    }
+
+    #[test]
+    fn four_bits() {
+        get_and_set::<4>();
+        regions::<4>();
+    }
+}
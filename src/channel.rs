@@ -0,0 +1,75 @@
+pub trait Channel: Copy + PartialEq {
+    const ZERO: Self;
+    /// The value of a fully opaque alpha, or of a channel at full intensity.
+    const MAX: Self;
+    /// `self * alpha / MAX`, rounded to nearest.
+    fn mul_alpha(self, alpha: Self) -> Self;
+    /// `self * MAX / alpha`, rounded to nearest and saturated. Zero alpha gives zero.
+    fn div_alpha(self, alpha: Self) -> Self;
+    fn saturating_add(self, other: Self) -> Self;
+    fn invert(self) -> Self;
+}
+
+impl Channel for u8 {
+    const ZERO: Self = 0;
+    const MAX: Self = u8::MAX;
+    fn mul_alpha(self, alpha: Self) -> Self {
+        let t = self as u32 * alpha as u32 + 128;
+        (((t >> 8) + t) >> 8) as u8
+    }
+    fn div_alpha(self, alpha: Self) -> Self {
+        if alpha == 0 {
+            0
+        } else {
+            ((self as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+        }
+    }
+    fn saturating_add(self, other: Self) -> Self {
+        u8::saturating_add(self, other)
+    }
+    fn invert(self) -> Self {
+        u8::MAX - self
+    }
+}
+
+impl Channel for u16 {
+    const ZERO: Self = 0;
+    const MAX: Self = u16::MAX;
+    fn mul_alpha(self, alpha: Self) -> Self {
+        ((self as u32 * alpha as u32 + 32767) / 65535) as u16
+    }
+    fn div_alpha(self, alpha: Self) -> Self {
+        if alpha == 0 {
+            0
+        } else {
+            ((self as u64 * 65535 + alpha as u64 / 2) / alpha as u64).min(65535) as u16
+        }
+    }
+    fn saturating_add(self, other: Self) -> Self {
+        u16::saturating_add(self, other)
+    }
+    fn invert(self) -> Self {
+        u16::MAX - self
+    }
+}
+
+impl Channel for f32 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = 1.0;
+    fn mul_alpha(self, alpha: Self) -> Self {
+        self * alpha
+    }
+    fn div_alpha(self, alpha: Self) -> Self {
+        if alpha == 0.0 {
+            0.0
+        } else {
+            self / alpha
+        }
+    }
+    fn saturating_add(self, other: Self) -> Self {
+        self + other
+    }
+    fn invert(self) -> Self {
+        1.0 - self
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    dynamic::{ChannelLayout, DynamicImage, DynamicPixel},
+    pixel::{Luma, LumaA, Rgb, Rgba},
+    Image,
+};
+
+pub mod bmp;
+pub(crate) mod crc;
+pub mod farbfeld;
+pub mod gif;
+pub mod netpbm;
+pub mod png;
+#[cfg(feature = "png")]
+mod png_crate;
+pub mod qoi;
+pub mod tga;
+pub(crate) mod zlib;
+
+fn collect<P, T, const N: usize>(
+    width: usize,
+    height: usize,
+    samples: &[u16],
+    f: impl Fn(u16) -> T,
+) -> Image<Box<[P]>, P>
+where
+    P: From<[T; N]>,
+{
+    let buf = samples
+        .chunks_exact(N)
+        .map(|chunk| P::from(core::array::from_fn(|i| f(chunk[i]))))
+        .collect();
+    unsafe { Image::from_source_unchecked(width, height, buf) }
+}
+
+/// Builds an image from interleaved samples, which are 16 bit when `wide` is set and 8 bit
+/// otherwise.
+pub(crate) fn from_samples(
+    width: usize,
+    height: usize,
+    layout: ChannelLayout,
+    wide: bool,
+    samples: &[u16],
+) -> DynamicImage {
+    let narrow = |s: u16| s as u8;
+    let same = |s: u16| s;
+    match (layout, wide) {
+        (ChannelLayout::Luma, false) => {
+            collect::<Luma<u8>, _, 1>(width, height, samples, narrow).into()
+        }
+        (ChannelLayout::LumaA, false) => {
+            collect::<LumaA<u8>, _, 2>(width, height, samples, narrow).into()
+        }
+        (ChannelLayout::Rgb, false) => {
+            collect::<Rgb<u8>, _, 3>(width, height, samples, narrow).into()
+        }
+        (ChannelLayout::Rgba, false) => {
+            collect::<Rgba<u8>, _, 4>(width, height, samples, narrow).into()
+        }
+        (ChannelLayout::Luma, true) => {
+            collect::<Luma<u16>, _, 1>(width, height, samples, same).into()
+        }
+        (ChannelLayout::LumaA, true) => {
+            collect::<LumaA<u16>, _, 2>(width, height, samples, same).into()
+        }
+        (ChannelLayout::Rgb, true) => {
+            collect::<Rgb<u16>, _, 3>(width, height, samples, same).into()
+        }
+        (ChannelLayout::Rgba, true) => {
+            collect::<Rgba<u16>, _, 4>(width, height, samples, same).into()
+        }
+    }
+}
+
+/// The channels of `pixel` in `layout`, scaled to `0..=maxval`. Only the first
+/// `layout.channels()` entries are used.
+pub(crate) fn to_samples<P: DynamicPixel>(
+    pixel: P,
+    layout: ChannelLayout,
+    maxval: u16,
+) -> [u16; 4] {
+    let rgba = pixel.to_rgba();
+    let scale = |unit: f32| (unit.clamp(0.0, 1.0) * maxval as f32).round() as u16;
+    match layout {
+        ChannelLayout::Luma => [scale(Luma::<f32>::from_rgba(rgba).l), 0, 0, 0],
+        ChannelLayout::LumaA => [scale(Luma::<f32>::from_rgba(rgba).l), scale(rgba.a), 0, 0],
+        ChannelLayout::Rgb => [scale(rgba.r), scale(rgba.g), scale(rgba.b), 0],
+        ChannelLayout::Rgba => [rgba.r, rgba.g, rgba.b, rgba.a].map(scale),
+    }
+}
+
+/// The inverse of [`to_samples`], reading `layout.channels()` samples from `samples`.
+pub(crate) fn from_samples_pixel<P: DynamicPixel>(
+    samples: &[u16],
+    layout: ChannelLayout,
+    maxval: u16,
+) -> P {
+    let unit = |sample: u16| sample as f32 / maxval as f32;
+    let rgba = match (layout, samples) {
+        (ChannelLayout::Luma, &[l, ..]) => [unit(l), unit(l), unit(l), 1.0],
+        (ChannelLayout::LumaA, &[l, a, ..]) => [unit(l), unit(l), unit(l), unit(a)],
+        (ChannelLayout::Rgb, &[r, g, b, ..]) => [unit(r), unit(g), unit(b), 1.0],
+        (ChannelLayout::Rgba, &[r, g, b, a, ..]) => [unit(r), unit(g), unit(b), unit(a)],
+        _ => panic!("too few samples for {layout:?}"),
+    };
+    P::from_rgba(rgba.into())
+}
+
+/// Converts a decoded image into `target`, which may be a region of a larger image.
+pub(crate) fn copy_into<Source, P>(
+    decoded: &DynamicImage,
+    target: &mut Image<Source, P>,
+) -> Result<(), crate::error::SizeMismatch>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    if [decoded.width(), decoded.height()] != [target.width(), target.height()] {
+        return Err(crate::error::SizeMismatch {
+            left: [decoded.width(), decoded.height()],
+            right: [target.width(), target.height()],
+        });
+    }
+    let converted = decoded.to_image::<P>();
+    for (src, dst) in converted.iter_rows().zip(target.iter_rows_mut()) {
+        dst.copy_from_slice(src);
+    }
+    Ok(())
+}
@@ -0,0 +1,572 @@
+use crate::{
+    codec::{copy_into, from_samples, to_samples},
+    dynamic::{ChannelLayout, DynamicImage, DynamicPixel, ImageVisitor},
+    error::CodecError,
+    pixel::Rgb,
+    Image,
+};
+use std::io::{Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BmpCompression {
+    Rgb,
+    Rle8,
+    Rle4,
+    /// Channels are picked out of each pixel with masks.
+    Bitfields,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BmpHeader {
+    /// Size of the info header, from 12 for `BITMAPCOREHEADER` to 124 for `BITMAPV5HEADER`.
+    pub header_size: u32,
+    pub width: usize,
+    pub height: usize,
+    /// Rows are stored top to bottom instead of the usual bottom to top.
+    pub top_down: bool,
+    pub bits_per_pixel: u16,
+    pub compression: BmpCompression,
+    /// Red, green, blue and alpha masks. Only used for 16 and 32 bit images.
+    pub masks: [u32; 4],
+    pub palette: Vec<Rgb<u8>>,
+}
+
+fn bytes<const N: usize>(data: &[u8], pos: usize) -> Result<[u8; N], CodecError> {
+    data.get(pos..pos + N)
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or(CodecError::Truncated)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, CodecError> {
+    bytes(data, pos).map(u16::from_le_bytes)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, CodecError> {
+    bytes(data, pos).map(u32::from_le_bytes)
+}
+
+fn header(data: &[u8]) -> Result<(BmpHeader, usize), CodecError> {
+    if bytes(data, 0)? != *b"BM" {
+        return Err(CodecError::Invalid("not a BMP file"));
+    }
+    let offset = u32_at(data, 10)? as usize;
+    let header_size = u32_at(data, 14)?;
+    let (width, height, bits_per_pixel, code) = match header_size {
+        12 => (
+            u16_at(data, 18)? as i32,
+            u16_at(data, 20)? as i32,
+            u16_at(data, 24)?,
+            0,
+        ),
+        40 | 52 | 56 | 108 | 124 => (
+            u32_at(data, 18)? as i32,
+            u32_at(data, 22)? as i32,
+            u16_at(data, 28)?,
+            u32_at(data, 30)?,
+        ),
+        _ => return Err(CodecError::Unsupported("BMP header size")),
+    };
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(CodecError::Invalid("image size out of range"));
+    }
+    let compression = match (code, bits_per_pixel) {
+        (0, 1 | 4 | 8 | 16 | 24 | 32) => BmpCompression::Rgb,
+        (1, 8) => BmpCompression::Rle8,
+        (2, 4) => BmpCompression::Rle4,
+        (3 | 6, 16 | 32) => BmpCompression::Bitfields,
+        (4 | 5, _) => return Err(CodecError::Unsupported("embedded JPEG or PNG")),
+        _ => return Err(CodecError::Invalid("invalid compression for the bit depth")),
+    };
+
+    // Masks follow a plain info header, and are part of the larger ones.
+    let mut after_header = 14 + header_size as usize;
+    let mut masks = match bits_per_pixel {
+        16 => [0x7c00, 0x03e0, 0x001f, 0],
+        _ => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+    };
+    let bitfields = compression == BmpCompression::Bitfields;
+    if bitfields {
+        let count = if code == 6 { 4 } else { 3 };
+        for (i, mask) in masks.iter_mut().take(count).enumerate() {
+            *mask = u32_at(data, 54 + 4 * i)?;
+        }
+        after_header = after_header.max(54 + 4 * count);
+    }
+    if header_size >= 56 && (bitfields || bits_per_pixel == 32) {
+        masks[3] = u32_at(data, 66)?;
+    }
+
+    let mut palette = Vec::new();
+    if bits_per_pixel <= 8 {
+        let used = if header_size == 12 {
+            0
+        } else {
+            u32_at(data, 46)?
+        };
+        let count = match used {
+            0 => 1 << bits_per_pixel,
+            used => used.min(256) as usize,
+        };
+        let entry = if header_size == 12 { 3 } else { 4 };
+        for i in 0..count {
+            let [b, g, r] = bytes(data, after_header + entry * i)?;
+            palette.push(Rgb { r, g, b });
+        }
+    }
+    let header = BmpHeader {
+        header_size,
+        width: width as usize,
+        height: height.unsigned_abs() as usize,
+        top_down: height < 0,
+        bits_per_pixel,
+        compression,
+        masks,
+        palette,
+    };
+    Ok((header, offset))
+}
+
+/// Pulls a masked channel out of `pixel`, scaled to 8 bits.
+fn channel(pixel: u32, mask: u32) -> u16 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = (mask >> mask.trailing_zeros()) as u64;
+    let value = ((pixel & mask) >> mask.trailing_zeros()) as u64;
+    ((value * 255 + max / 2) / max) as u16
+}
+
+/// Expands run length encoded palette indices, in file row order. Skipped pixels stay 0.
+fn decode_rle(data: &[u8], header: &BmpHeader) -> Result<Vec<u8>, CodecError> {
+    let (width, height) = (header.width, header.height);
+    let four = header.compression == BmpCompression::Rle4;
+    let mut indices = vec![0; width * height];
+    let mut put = |x: usize, y: usize, index: u8| {
+        if x < width && y < height {
+            indices[y * width + x] = index;
+        }
+    };
+    let (mut pos, mut x, mut y) = (0, 0, 0);
+    loop {
+        let [count, value] = bytes(data, pos)?;
+        pos += 2;
+        match (count, value) {
+            (0, 0) => {
+                x = 0;
+                y += 1;
+            }
+            (0, 1) => break,
+            (0, 2) => {
+                let [dx, dy] = bytes(data, pos)?;
+                pos += 2;
+                x += dx as usize;
+                y += dy as usize;
+            }
+            (0, count) => {
+                let len = if four {
+                    (count as usize).div_ceil(2)
+                } else {
+                    count as usize
+                };
+                let run = data.get(pos..pos + len).ok_or(CodecError::Truncated)?;
+                for i in 0..count as usize {
+                    let index = if four {
+                        run[i / 2] >> (4 - 4 * (i % 2)) & 0x0f
+                    } else {
+                        run[i]
+                    };
+                    put(x, y, index);
+                    x += 1;
+                }
+                // Absolute runs are padded to 16 bits.
+                pos += len + len % 2;
+            }
+            (count, value) => {
+                for i in 0..count as usize {
+                    let index = if four {
+                        value >> (4 - 4 * (i % 2)) & 0x0f
+                    } else {
+                        value
+                    };
+                    put(x, y, index);
+                    x += 1;
+                }
+            }
+        }
+    }
+    Ok(indices)
+}
+
+pub fn decode_with_header(mut reader: impl Read) -> Result<(BmpHeader, DynamicImage), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (header, offset) = header(&data)?;
+    let pixels = data.get(offset..).ok_or(CodecError::Truncated)?;
+    let (width, height) = (header.width, header.height);
+    let bits = header.bits_per_pixel as usize;
+    let stride = (width.checked_mul(bits).ok_or(CodecError::Truncated)?).div_ceil(32) * 4;
+    width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or(CodecError::Invalid("image is too large"))?;
+    let rle = matches!(
+        header.compression,
+        BmpCompression::Rle4 | BmpCompression::Rle8
+    );
+    let len = stride.checked_mul(height);
+    if !rle && len.filter(|&len| len <= pixels.len()).is_none() {
+        return Err(CodecError::Truncated);
+    }
+    // A run of two bytes draws at most 255 pixels, so anything larger can not be filled in.
+    if rle && width * height > pixels.len() / 2 * 255 {
+        return Err(CodecError::Truncated);
+    }
+    let alpha = header.masks[3] != 0;
+    let layout = if alpha {
+        ChannelLayout::Rgba
+    } else {
+        ChannelLayout::Rgb
+    };
+    let channels = layout.channels();
+    let mut samples = vec![0u16; width * height * channels];
+    let indices = if rle {
+        decode_rle(pixels, &header)?
+    } else {
+        Vec::new()
+    };
+    let palette = |index: u8| {
+        let color = header
+            .palette
+            .get(index as usize)
+            .ok_or(CodecError::Invalid("palette index out of range"))?;
+        Ok::<_, CodecError>([color.r, color.g, color.b, 255].map(u16::from))
+    };
+
+    for row in 0..height {
+        let y = if header.top_down {
+            row
+        } else {
+            height - 1 - row
+        };
+        let line = if rle {
+            &[][..]
+        } else {
+            &pixels[row * stride..][..stride]
+        };
+        for x in 0..width {
+            let pixel = if rle {
+                palette(indices[row * width + x])?
+            } else {
+                match bits {
+                    1 | 2 | 4 | 8 => {
+                        let bit = x * bits;
+                        let index = line[bit / 8] >> (8 - bits - bit % 8) & ((1 << bits) - 1) as u8;
+                        palette(index)?
+                    }
+                    24 => {
+                        let [b, g, r] = [line[3 * x], line[3 * x + 1], line[3 * x + 2]];
+                        [r, g, b, 255].map(u16::from)
+                    }
+                    _ => {
+                        let value = if bits == 16 {
+                            u16::from_le_bytes([line[2 * x], line[2 * x + 1]]) as u32
+                        } else {
+                            u32::from_le_bytes(line[4 * x..4 * x + 4].try_into().unwrap())
+                        };
+                        header.masks.map(|mask| channel(value, mask))
+                    }
+                }
+            };
+            let start = (y * width + x) * channels;
+            samples[start..start + channels].copy_from_slice(&pixel[..channels]);
+        }
+    }
+    // Plenty of writers leave the alpha channel zeroed, which means opaque.
+    if alpha && samples.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        samples.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 255);
+    }
+    let image = from_samples(width, height, layout, false, &samples);
+    Ok((header, image))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target`, converting the pixels. `target` may be a region of a larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    Ok(copy_into(&decode(reader)?, target)?)
+}
+
+/// Writes a bottom-up BMP, 32 bit with a `BITMAPV4HEADER` alpha mask if the pixels have alpha
+/// and 24 bit otherwise.
+pub fn encode<Source, P>(mut writer: impl Write, image: &Image<Source, P>) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 {
+        return Err(CodecError::Invalid("BMP images can not be empty"));
+    }
+    let alpha = P::FORMAT.layout.has_alpha();
+    let (layout, bits, header_size) = if alpha {
+        (ChannelLayout::Rgba, 32, 108)
+    } else {
+        (ChannelLayout::Rgb, 24, 40)
+    };
+    let stride = (width * bits / 8).div_ceil(4) * 4;
+    let offset = 14 + header_size;
+    let size = stride
+        .checked_mul(height)
+        .and_then(|len| len.checked_add(offset))
+        .filter(|&size| size <= u32::MAX as usize && width <= i32::MAX as usize)
+        .ok_or(CodecError::Invalid("image is too large for BMP"))?;
+
+    let mut out = Vec::with_capacity(size);
+    out.extend(b"BM");
+    out.extend((size as u32).to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend((offset as u32).to_le_bytes());
+    out.extend((header_size as u32).to_le_bytes());
+    out.extend((width as i32).to_le_bytes());
+    out.extend((height as i32).to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend((bits as u16).to_le_bytes());
+    // BI_BITFIELDS carries the alpha mask.
+    out.extend(if alpha { 3u32 } else { 0 }.to_le_bytes());
+    out.extend(((stride * height) as u32).to_le_bytes());
+    // 72 DPI.
+    out.extend(2835u32.to_le_bytes());
+    out.extend(2835u32.to_le_bytes());
+    out.extend([0; 8]);
+    if alpha {
+        for mask in [0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+            out.extend(mask.to_le_bytes());
+        }
+        out.extend(b"BGRs");
+        // Endpoints and gamma, unused with sRGB.
+        out.extend([0; 48]);
+    }
+    for row in image.iter_rows().rev() {
+        let start = out.len();
+        for &pixel in row {
+            let [r, g, b, a] = to_samples(pixel, layout, 255).map(|sample| sample as u8);
+            out.extend([b, g, r]);
+            if alpha {
+                out.push(a);
+            }
+        }
+        out.resize(start + stride, 0);
+    }
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+struct Encode<W> {
+    writer: W,
+}
+
+impl<W: Write> ImageVisitor for Encode<W> {
+    type Output = Result<(), CodecError>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        encode(self.writer, image)
+    }
+}
+
+pub fn encode_dynamic(writer: impl Write, image: &DynamicImage) -> Result<(), CodecError> {
+    image.visit(Encode { writer })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::Rgba;
+
+    /// A file with a `BITMAPINFOHEADER`, followed by `table`, which holds the masks or the
+    /// palette, and the pixel data.
+    fn file(
+        width: i32,
+        height: i32,
+        bits: u16,
+        compression: u32,
+        table: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let offset = 14 + 40 + table.len() as u32;
+        let colors = if bits <= 8 { table.len() as u32 / 4 } else { 0 };
+        let mut out = b"BM".to_vec();
+        out.extend((offset + pixels.len() as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(offset.to_le_bytes());
+        out.extend(40u32.to_le_bytes());
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(bits.to_le_bytes());
+        out.extend(compression.to_le_bytes());
+        out.extend([0; 12]);
+        out.extend(colors.to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(table);
+        out.extend(pixels);
+        out
+    }
+
+    /// Palette entries whose red channel is ten times the index.
+    fn palette(len: u8) -> Vec<u8> {
+        (0..len).flat_map(|i| [0, 0, i * 10, 0]).collect()
+    }
+
+    fn reds(data: &[u8]) -> Vec<u8> {
+        let image = decode(data).unwrap();
+        let image = image.as_image::<Rgb<u8>>().unwrap();
+        image.iter().map(|(_, pixel)| pixel.r).collect()
+    }
+
+    #[test]
+    fn oversized_rle_is_truncated() {
+        let data = file(0x7fff_ffff, 0x7fff_ffff, 8, 1, &palette(1), &[0, 0, 0, 1]);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn rle8() {
+        // Bottom row: a run of 2 and an absolute run of 3. Top row: a delta of 2 to the right,
+        // then the end, leaving skipped pixels at index 0.
+        let pixels = [2, 1, 0, 3, 2, 3, 4, 0, 0, 0, 0, 2, 2, 0, 1, 5, 0, 1];
+        let data = file(5, 2, 8, 1, &palette(6), &pixels);
+        assert_eq!(reds(&data), [0, 0, 50, 0, 0, 10, 10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn rle4() {
+        // A run alternating two indices, then an absolute run of 3 padded to 16 bits.
+        let pixels = [5, 0x12, 0, 0, 0, 3, 0x34, 0x50, 0, 1];
+        let data = file(5, 2, 4, 2, &palette(6), &pixels);
+        assert_eq!(reds(&data), [30, 40, 50, 0, 0, 10, 20, 10, 20, 10]);
+    }
+
+    #[test]
+    fn top_down_and_bottom_up() {
+        // 8 bit rows of 3 pixels, padded to 4 bytes.
+        let pixels = [1, 2, 3, 0xee, 4, 5, 0, 0xee];
+        let bottom_up = file(3, 2, 8, 0, &palette(6), &pixels);
+        assert_eq!(reds(&bottom_up), [40, 50, 0, 10, 20, 30]);
+        let (header, _) = decode_with_header(bottom_up.as_slice()).unwrap();
+        assert!(!header.top_down);
+        let top_down = file(3, -2, 8, 0, &palette(6), &pixels);
+        assert_eq!(reds(&top_down), [10, 20, 30, 40, 50, 0]);
+        let (header, _) = decode_with_header(top_down.as_slice()).unwrap();
+        assert!(header.top_down);
+    }
+
+    #[test]
+    fn sub_byte_indices() {
+        // 1 bit rows of 10 pixels, each padded to 4 bytes.
+        let pixels = [0b1010_0000, 0b0100_0000, 0, 0, 0xff, 0xc0, 0, 0];
+        let data = file(10, -2, 1, 0, &palette(2), &pixels);
+        let expected: Vec<u8> = [1, 0, 1, 0, 0, 0, 0, 0, 0, 1]
+            .into_iter()
+            .chain([1; 10])
+            .map(|i| i * 10)
+            .collect();
+        assert_eq!(reds(&data), expected);
+    }
+
+    #[test]
+    fn bitfields_16() {
+        let rgb565: Vec<u8> = [0xf800u32, 0x07e0, 0x001f]
+            .iter()
+            .flat_map(|mask| mask.to_le_bytes())
+            .collect();
+        // Red, green, blue and white, which fill the row without padding.
+        let pixels = [0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0xff, 0xff];
+        let data = file(4, -1, 16, 3, &rgb565, &pixels);
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<Rgb<u8>>().unwrap();
+        let colors: Vec<[u8; 3]> = image.iter().map(|(_, &p)| p.into()).collect();
+        assert_eq!(colors, [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255; 3]]);
+        // Without masks, 16 bit pixels are 5 bits per channel.
+        let data = file(1, 1, 16, 0, &[], &[0x00, 0x7c, 0, 0]);
+        assert_eq!(reds(&data), [255]);
+    }
+
+    #[test]
+    fn alpha_32() {
+        let mut image = Image::filled(3, 2, Rgba::from([0u8, 0, 0, 0]));
+        for ([x, y], pixel) in image.iter_mut() {
+            *pixel = Rgba::from([x as u8 * 100, y as u8 * 100, 7, x as u8 * 60 + y as u8]);
+        }
+        let mut data = Vec::new();
+        encode(&mut data, &image).unwrap();
+        let (header, decoded) = decode_with_header(data.as_slice()).unwrap();
+        assert_eq!(
+            [header.bits_per_pixel, header.header_size as u16],
+            [32, 108]
+        );
+        assert_eq!(header.masks[3], 0xff00_0000);
+        assert_eq!(decoded.as_image::<Rgba<u8>>(), Some(&image));
+
+        // Zeroed alpha everywhere means the writer did not use it.
+        let masks: Vec<u8> = [0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000]
+            .iter()
+            .flat_map(|mask| mask.to_le_bytes())
+            .collect();
+        let data = file(1, 1, 32, 6, &masks, &[1, 2, 3, 0]);
+        let decoded = decode(data.as_slice()).unwrap();
+        assert_eq!(
+            decoded.as_image::<Rgba<u8>>().unwrap()[[0, 0]],
+            Rgba::from([3, 2, 1, 255])
+        );
+    }
+
+    #[test]
+    fn padded_rows() {
+        for width in 1..=5 {
+            let mut image = Image::filled(width, 3, Rgb { r: 0u8, g: 0, b: 0 });
+            for ([x, y], pixel) in image.iter_mut() {
+                *pixel = Rgb::from([x as u8, y as u8, 0xee]);
+            }
+            let mut data = Vec::new();
+            encode(&mut data, &image).unwrap();
+            let stride = (width * 3).div_ceil(4) * 4;
+            assert_eq!(data.len(), 54 + stride * 3);
+            // Bottom up, so the first row written is the last one.
+            assert_eq!(&data[54..57], [0xee, 2, 0]);
+            assert!(data[54 + width * 3..54 + stride].iter().all(|&b| b == 0));
+            assert_eq!(
+                decode(data.as_slice()).unwrap().as_image::<Rgb<u8>>(),
+                Some(&image)
+            );
+        }
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let empty = Image::filled(0, 0, Rgb { r: 0u8, g: 0, b: 0 });
+        assert!(matches!(
+            encode(Vec::new(), &empty),
+            Err(CodecError::Invalid(_))
+        ));
+        let empty = Image::filled(3, 0, Rgb { r: 0u8, g: 0, b: 0 });
+        assert!(matches!(
+            encode(Vec::new(), &empty),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    dynamic::DynamicImage,
+    endian::{Endian, ScalarLayout},
+    error::{CodecError, SizeMismatch},
+    pixel::Rgba,
+    Image, PlainData,
+};
+use core::mem::size_of;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 8] = b"farbfeld";
+
+/// Pixels laid out as farbfeld stores them: red, green, blue and alpha as 16 bit scalars.
+///
+/// # Safety
+/// The pixel must be exactly four `u16` channels in RGBA order.
+pub unsafe trait FarbfeldPixel: PlainData + ScalarLayout {}
+
+unsafe impl FarbfeldPixel for Rgba<u16> {}
+unsafe impl FarbfeldPixel for [u16; 4] {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FarbfeldHeader {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FarbfeldHeader {
+    /// The size of the pixel data following the header, if it fits in memory.
+    fn data_len(&self) -> Option<usize> {
+        self.width
+            .checked_mul(self.height)?
+            .checked_mul(size_of::<[u16; 4]>())
+    }
+}
+
+/// Reads and validates the 16 byte header, leaving `reader` at the first pixel.
+pub fn read_header(mut reader: impl Read) -> Result<FarbfeldHeader, CodecError> {
+    let mut bytes = [0; 16];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => CodecError::Truncated,
+            _ => CodecError::Io(err),
+        })?;
+    if &bytes[..8] != MAGIC {
+        return Err(CodecError::Invalid("not a farbfeld file"));
+    }
+    let width = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    let header = FarbfeldHeader { width, height };
+    if header.data_len().is_none() {
+        return Err(CodecError::Invalid("image size out of range"));
+    }
+    Ok(header)
+}
+
+/// Streams the pixel data straight into `target`, which must have the size of the header.
+fn read_pixels<Source, P>(
+    reader: impl Read,
+    header: &FarbfeldHeader,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: FarbfeldPixel,
+{
+    let len = header.data_len().unwrap() as u64;
+    let mut cursor = target.byte_cursor_mut().with_byte_order(Endian::Big);
+    if io::copy(&mut reader.take(len), &mut cursor)? < len {
+        return Err(CodecError::Truncated);
+    }
+    Ok(())
+}
+
+pub type FarbfeldImage<P> = Image<Box<[P]>, P>;
+
+/// Decodes the header and pixels, leaving anything after the pixel data unread.
+pub fn decode_with_header<P: FarbfeldPixel>(
+    mut reader: impl Read,
+) -> Result<(FarbfeldHeader, FarbfeldImage<P>), CodecError> {
+    let header = read_header(&mut reader)?;
+    // The buffer only grows as data arrives, so a header alone can not claim a huge image.
+    let len = header.data_len().unwrap();
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(CodecError::Truncated);
+    }
+    let image = Image::from_bytes(header.width, header.height, &bytes, Endian::Big)
+        .map_err(|_| CodecError::Truncated)?;
+    Ok((header, image))
+}
+
+pub fn decode<P: FarbfeldPixel>(reader: impl Read) -> Result<FarbfeldImage<P>, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target` without an intermediate copy. `target` may be a region of a larger
+/// image, but must have the size given by the header.
+pub fn decode_into<Source, P>(
+    mut reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: FarbfeldPixel,
+{
+    let header = read_header(&mut reader)?;
+    if [header.width, header.height] != [target.width(), target.height()] {
+        return Err(SizeMismatch {
+            left: [header.width, header.height],
+            right: [target.width(), target.height()],
+        }
+        .into());
+    }
+    read_pixels(reader, &header, target)
+}
+
+/// Writes the header, then streams the pixels row by row without copying the image.
+pub fn encode<Source, P>(mut writer: impl Write, image: &Image<Source, P>) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: FarbfeldPixel,
+{
+    let (Ok(width), Ok(height)) = (u32::try_from(image.width()), u32::try_from(image.height()))
+    else {
+        return Err(CodecError::Invalid("image size out of range"));
+    };
+    let mut header = MAGIC.to_vec();
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    writer.write_all(&header)?;
+    io::copy(
+        &mut image.byte_cursor().with_byte_order(Endian::Big),
+        &mut writer,
+    )?;
+    Ok(())
+}
+
+/// Converts to 16 bit straight RGBA first, unless the image already is.
+pub fn encode_dynamic(writer: impl Write, image: &DynamicImage) -> Result<(), CodecError> {
+    match image.as_image::<Rgba<u16>>() {
+        Some(image) => encode(writer, image),
+        None => encode(writer, &image.to_image::<Rgba<u16>>()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FarbfeldImage<Rgba<u16>> {
+        let mut image = Image::filled(
+            4,
+            3,
+            Rgba {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            },
+        );
+        for ([x, y], pixel) in image.iter_mut() {
+            *pixel = Rgba {
+                r: x as u16 * 0x1001,
+                g: y as u16 * 0x0110,
+                b: 0xff00,
+                a: 0xffff,
+            };
+        }
+        image
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = sample();
+        let mut data = Vec::new();
+        encode(&mut data, &image).unwrap();
+        assert_eq!(data.len(), 16 + 4 * 3 * 8);
+        assert_eq!(
+            &data[16 + 8..16 + 16],
+            &[0x10, 0x01, 0, 0, 0xff, 0, 0xff, 0xff]
+        );
+        assert_eq!(decode::<Rgba<u16>>(data.as_slice()).unwrap(), image);
+        let mut target = Image::filled(
+            6,
+            5,
+            Rgba {
+                r: 1,
+                g: 1,
+                b: 1,
+                a: 1,
+            },
+        );
+        let mut region = target.region_mut([1, 1]..[5, 4]).unwrap();
+        decode_into(data.as_slice(), &mut region).unwrap();
+        assert_eq!(target[[2, 3]], image[[1, 2]]);
+        assert_eq!(
+            target[[0, 0]],
+            Rgba {
+                r: 1,
+                g: 1,
+                b: 1,
+                a: 1
+            }
+        );
+    }
+
+    #[test]
+    fn oversized_header_is_truncated() {
+        let mut data = MAGIC.to_vec();
+        data.extend(0x7fff_ffffu32.to_be_bytes());
+        data.extend(0x0fff_ffffu32.to_be_bytes());
+        data.extend([0; 8]);
+        assert!(matches!(
+            decode::<[u16; 4]>(data.as_slice()),
+            Err(CodecError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            decode::<[u16; 4]>(&b"farbfel"[..]),
+            Err(CodecError::Truncated)
+        ));
+        assert!(matches!(
+            decode::<[u16; 4]>(&b"farbfelt\0\0\0\0\0\0\0\0"[..]),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+}
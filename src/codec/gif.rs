@@ -0,0 +1,1208 @@
+use crate::{
+    codec::copy_into,
+    dither::{DitherMethod, DitherTarget},
+    dynamic::{DynamicImage, DynamicPixel},
+    error::{CodecError, SizeMismatch},
+    palette::PaletteImage,
+    pixel::{Rgb, Rgba},
+    quantize::{Quantizable, QuantizeOptions},
+    Image,
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+const MAX_CODES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GifVersion {
+    Gif87a,
+    Gif89a,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GifHeader {
+    pub version: GifVersion,
+    /// Size of the logical screen that frames are drawn onto.
+    pub width: usize,
+    pub height: usize,
+    pub palette: Option<Vec<Rgb<u8>>>,
+    /// Index into the global palette.
+    pub background: u8,
+    /// Set by the `NETSCAPE2.0` extension, where 0 means forever.
+    pub loop_count: Option<u16>,
+}
+
+/// What happens to the area of a frame once its delay has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GifDisposal {
+    /// Treated like [`GifDisposal::Keep`].
+    Unspecified,
+    Keep,
+    /// Clears the area to transparent, which is what browsers do instead of filling it with the
+    /// background color.
+    Background,
+    /// Restores the area to what it was before the frame was drawn.
+    Previous,
+}
+
+impl GifDisposal {
+    const fn from_code(code: u8) -> Self {
+        match code {
+            1 => Self::Keep,
+            2 => Self::Background,
+            3 => Self::Previous,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+pub struct GifFrame {
+    pub left: usize,
+    pub top: usize,
+    /// In hundredths of a second.
+    pub delay: u16,
+    pub disposal: GifDisposal,
+    pub interlaced: bool,
+    /// The local palette, or a copy of the global one. The transparent index, if any, is its only
+    /// transparent index.
+    pub image: PaletteImage<Box<[u8]>, u8, Rgb<u8>>,
+}
+
+pub struct Gif {
+    pub header: GifHeader,
+    pub frames: Vec<GifFrame>,
+}
+
+struct Blocks<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Blocks<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CodecError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(CodecError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn byte(&mut self) -> Result<u8, CodecError> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, CodecError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+    /// A color table of `2^(size + 1)` entries.
+    fn palette(&mut self, size: u8) -> Result<Vec<Rgb<u8>>, CodecError> {
+        let bytes = self.bytes(3 << (size + 1))?;
+        Ok(bytes
+            .chunks_exact(3)
+            .map(|rgb| Rgb {
+                r: rgb[0],
+                g: rgb[1],
+                b: rgb[2],
+            })
+            .collect())
+    }
+    /// Every data sub-block up to the terminator, each one kept separate.
+    fn sub_blocks(&mut self) -> Result<Vec<&'a [u8]>, CodecError> {
+        let mut blocks = Vec::new();
+        loop {
+            match self.byte()? {
+                0 => return Ok(blocks),
+                len => blocks.push(self.bytes(len as usize)?),
+            }
+        }
+    }
+}
+
+/// Decodes LZW codes starting at `min_size + 1` bits, stopping after `len` indices.
+fn lzw_decode(data: &[u8], min_size: u8, len: usize) -> Result<Vec<u8>, CodecError> {
+    let clear = 1 << min_size;
+    let end = clear + 1;
+    let mut prefix = [0u16; MAX_CODES];
+    let mut suffix = [0u8; MAX_CODES];
+    let mut lengths = [0u16; MAX_CODES];
+    for code in 0..clear {
+        suffix[code] = code as u8;
+        lengths[code] = 1;
+    }
+    let mut next = clear + 2;
+    let mut size = min_size + 1;
+    let mut prev: Option<usize> = None;
+    let mut out = Vec::new();
+    let (mut bits, mut count, mut pos) = (0u32, 0, 0);
+    while out.len() < len {
+        while count < size {
+            let byte = *data.get(pos).ok_or(CodecError::Truncated)?;
+            bits |= (byte as u32) << count;
+            count += 8;
+            pos += 1;
+        }
+        let code = (bits & ((1 << size) - 1)) as usize;
+        bits >>= size;
+        count -= size;
+        if code == clear {
+            next = clear + 2;
+            size = min_size + 1;
+            prev = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let Some(prev) = prev.replace(code) else {
+            if code > clear {
+                return Err(CodecError::Invalid("LZW code before its definition"));
+            }
+            out.push(code as u8);
+            continue;
+        };
+        // A code one past the table is the previous string followed by its own first index.
+        let string = match code {
+            code if code < next => code,
+            code if code == next => prev,
+            _ => return Err(CodecError::Invalid("LZW code before its definition")),
+        };
+        let start = out.len();
+        out.resize(start + lengths[string] as usize, 0);
+        let mut at = string;
+        for index in out[start..].iter_mut().rev() {
+            *index = suffix[at];
+            at = prefix[at] as usize;
+        }
+        let first = out[start];
+        if code == next {
+            out.push(first);
+        }
+        if next < MAX_CODES {
+            prefix[next] = prev as u16;
+            suffix[next] = first;
+            lengths[next] = lengths[prev] + 1;
+            next += 1;
+            if next == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+    }
+    if out.len() < len {
+        return Err(CodecError::Truncated);
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+/// The order interlaced rows are stored in.
+fn interlaced_rows(height: usize) -> impl Iterator<Item = usize> {
+    [(0, 8), (4, 8), (2, 4), (1, 2)]
+        .into_iter()
+        .flat_map(move |(start, step)| (start..height).step_by(step))
+}
+
+/// Reads blocks up to and including the next image, or `None` at the trailer.
+fn next_frame(blocks: &mut Blocks, header: &mut GifHeader) -> Result<Option<GifFrame>, CodecError> {
+    let (mut delay, mut disposal, mut transparent) = (0, GifDisposal::Unspecified, None);
+    loop {
+        // A missing trailer is common enough to accept.
+        if blocks.pos == blocks.data.len() {
+            return Ok(None);
+        }
+        match blocks.byte()? {
+            0x3b => return Ok(None),
+            0x21 => {
+                let label = blocks.byte()?;
+                let sub_blocks = blocks.sub_blocks()?;
+                match (label, sub_blocks.as_slice()) {
+                    (0xf9, [control, ..]) if control.len() >= 4 => {
+                        disposal = GifDisposal::from_code(control[0] >> 2 & 7);
+                        delay = u16::from_le_bytes([control[1], control[2]]);
+                        transparent = (control[0] & 1 == 1).then_some(control[3]);
+                    }
+                    (0xff, [b"NETSCAPE2.0" | b"ANIMEXTS1.0", data, ..])
+                        if data.len() >= 3 && data[0] == 1 =>
+                    {
+                        header.loop_count = Some(u16::from_le_bytes([data[1], data[2]]));
+                    }
+                    _ => {}
+                }
+            }
+            0x2c => {
+                let left = blocks.u16()? as usize;
+                let top = blocks.u16()? as usize;
+                let width = blocks.u16()? as usize;
+                let height = blocks.u16()? as usize;
+                let flags = blocks.byte()?;
+                let interlaced = flags & 0x40 != 0;
+                let palette = if flags & 0x80 != 0 {
+                    blocks.palette(flags & 7)?
+                } else {
+                    header
+                        .palette
+                        .clone()
+                        .ok_or(CodecError::Invalid("no color table"))?
+                };
+                let min_size = blocks.byte()?;
+                if !(1..=8).contains(&min_size) {
+                    return Err(CodecError::Invalid("invalid LZW code size"));
+                }
+                let data = blocks.sub_blocks()?.concat();
+                let decoded = lzw_decode(&data, min_size, width * height)?;
+                let buf = if interlaced && width > 0 {
+                    let mut buf = vec![0; width * height].into_boxed_slice();
+                    for (src, y) in decoded.chunks_exact(width).zip(interlaced_rows(height)) {
+                        buf[y * width..(y + 1) * width].copy_from_slice(src);
+                    }
+                    buf
+                } else {
+                    decoded.into_boxed_slice()
+                };
+                let indices = unsafe { Image::from_source_unchecked(width, height, buf) };
+                let mut image = PaletteImage::new(indices, palette)
+                    .map_err(|_| CodecError::Invalid("index outside the color table"))?;
+                // An index past the color table matches no pixel, so it is ignored like browsers
+                // do.
+                let _ = image.set_transparent_indices(transparent.into_iter().collect());
+                return Ok(Some(GifFrame {
+                    left,
+                    top,
+                    delay,
+                    disposal,
+                    interlaced,
+                    image,
+                }));
+            }
+            _ => return Err(CodecError::Invalid("unknown block")),
+        }
+    }
+}
+
+fn read_header<'a>(data: &'a [u8]) -> Result<(GifHeader, Blocks<'a>), CodecError> {
+    let mut blocks = Blocks { data, pos: 0 };
+    let version = match blocks.bytes(6)? {
+        b"GIF87a" => GifVersion::Gif87a,
+        b"GIF89a" => GifVersion::Gif89a,
+        _ => return Err(CodecError::Invalid("not a GIF file")),
+    };
+    let width = blocks.u16()? as usize;
+    let height = blocks.u16()? as usize;
+    let flags = blocks.byte()?;
+    let background = blocks.byte()?;
+    let _aspect = blocks.byte()?;
+    let palette = if flags & 0x80 != 0 {
+        Some(blocks.palette(flags & 7)?)
+    } else {
+        None
+    };
+    let header = GifHeader {
+        version,
+        width,
+        height,
+        palette,
+        background,
+        loop_count: None,
+    };
+    Ok((header, blocks))
+}
+
+/// Decodes every frame as stored, without drawing them onto the logical screen.
+pub fn decode_animation(mut reader: impl Read) -> Result<Gif, CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (mut header, mut blocks) = read_header(&data)?;
+    let mut frames = Vec::new();
+    while let Some(frame) = next_frame(&mut blocks, &mut header)? {
+        frames.push(frame);
+    }
+    Ok(Gif { header, frames })
+}
+
+type Canvas = Image<Box<[Rgba<u8>]>, Rgba<u8>>;
+
+/// Draws the opaque pixels of `frame` that land on the canvas.
+fn draw(canvas: &mut Canvas, frame: &GifFrame) {
+    let image = &frame.image;
+    for (y, row) in image.indices().iter_rows().enumerate() {
+        for (x, &index) in row.iter().enumerate() {
+            let [x, y] = [frame.left + x, frame.top + y];
+            if x < canvas.width() && y < canvas.height() && !image.is_transparent(index) {
+                let Rgb { r, g, b } = image.palette()[index as usize];
+                canvas[[x, y]] = Rgba { r, g, b, a: 255 };
+            }
+        }
+    }
+}
+
+const CLEAR: Rgba<u8> = Rgba {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+
+/// Clears the part of an area given as left, top, right and bottom that lands on the canvas.
+fn clear(canvas: &mut Canvas, [left, top, right, bottom]: [usize; 4]) {
+    for y in top..bottom.min(canvas.height()) {
+        for x in left..right.min(canvas.width()) {
+            canvas[[x, y]] = CLEAR;
+        }
+    }
+}
+
+/// The most pixels drawn onto logical screens by default, counted over every composited frame.
+pub const MAX_PIXELS: usize = 1 << 27;
+
+/// A transparent logical screen, unless `frames` of it would hold more than `max_pixels`.
+fn canvas(header: &GifHeader, frames: usize, max_pixels: usize) -> Result<Canvas, CodecError> {
+    header
+        .width
+        .checked_mul(header.height)
+        .and_then(|pixels| pixels.checked_mul(frames))
+        .filter(|&pixels| pixels <= max_pixels)
+        .ok_or(CodecError::Invalid("logical screen is too large"))?;
+    Ok(Image::filled(header.width, header.height, CLEAR))
+}
+
+impl Gif {
+    /// Every frame drawn onto the logical screen as it looks while the frame is shown, which
+    /// starts out transparent. Fails when this takes more than [`MAX_PIXELS`].
+    pub fn composite(&self) -> Result<Vec<Canvas>, CodecError> {
+        self.composite_with_limit(MAX_PIXELS)
+    }
+    /// Like [`Gif::composite`], with the frames together holding at most `max_pixels` pixels.
+    pub fn composite_with_limit(&self, max_pixels: usize) -> Result<Vec<Canvas>, CodecError> {
+        let mut canvas = canvas(&self.header, self.frames.len(), max_pixels)?;
+        let mut composited = Vec::with_capacity(self.frames.len());
+        for frame in &self.frames {
+            let previous = (frame.disposal == GifDisposal::Previous).then(|| canvas.map(|&p| p));
+            draw(&mut canvas, frame);
+            composited.push(canvas.map(|&p| p));
+            match (frame.disposal, previous) {
+                (GifDisposal::Background, _) => {
+                    let right = frame.left + frame.image.width();
+                    let bottom = frame.top + frame.image.height();
+                    clear(&mut canvas, [frame.left, frame.top, right, bottom]);
+                }
+                (GifDisposal::Previous, Some(previous)) => canvas = previous,
+                _ => {}
+            }
+        }
+        Ok(composited)
+    }
+}
+
+/// Decodes the first frame drawn onto the logical screen, as 8 bit RGBA. Fails when the logical
+/// screen has more than [`MAX_PIXELS`] pixels.
+pub fn decode_with_header(mut reader: impl Read) -> Result<(GifHeader, DynamicImage), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (mut header, mut blocks) = read_header(&data)?;
+    let frame =
+        next_frame(&mut blocks, &mut header)?.ok_or(CodecError::Invalid("no image data"))?;
+    let mut canvas = canvas(&header, 1, MAX_PIXELS)?;
+    draw(&mut canvas, &frame);
+    Ok((header, canvas.into()))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes the first frame into `target`, converting the pixels. `target` may be a region of a
+/// larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    Ok(copy_into(&decode(reader)?, target)?)
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.bits |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// Encodes indices below `2^min_size`, starting over with a clear code whenever the table fills.
+fn lzw_encode(indices: impl IntoIterator<Item = u8>, min_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let end = clear + 1;
+    let mut table = HashMap::new();
+    let mut next = end + 1;
+    let mut size = min_size + 1;
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    writer.write(clear, size);
+    let mut prefix = None;
+    for index in indices {
+        let Some(code) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&extended) = table.get(&(code, index)) {
+            prefix = Some(extended);
+            continue;
+        }
+        writer.write(code, size);
+        if (next as usize) < MAX_CODES {
+            table.insert((code, index), next);
+            next += 1;
+            // The decoder adds each code one step later, so it widens one code later too.
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        } else {
+            writer.write(clear, size);
+            table.clear();
+            next = end + 1;
+            size = min_size + 1;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(code) = prefix {
+        writer.write(code, size);
+    }
+    writer.write(end, size);
+    writer.finish()
+}
+
+fn write_sub_blocks(out: &mut Vec<u8>, data: &[u8]) {
+    for block in data.chunks(255) {
+        out.push(block.len() as u8);
+        out.extend_from_slice(block);
+    }
+    out.push(0);
+}
+
+/// The size field of a color table holding `len` entries, and the number of entries written.
+fn table_size(len: usize) -> (u8, usize) {
+    let bits = (1..8).find(|&bits| len <= 1 << bits).unwrap_or(8);
+    (bits as u8 - 1, 1 << bits)
+}
+
+fn write_palette(out: &mut Vec<u8>, palette: &[Rgb<u8>]) -> Result<u8, CodecError> {
+    if palette.len() > 256 {
+        return Err(CodecError::Invalid("more than 256 colors"));
+    }
+    let (size, len) = table_size(palette.len());
+    for &Rgb { r, g, b } in palette {
+        out.extend([r, g, b]);
+    }
+    out.resize(out.len() + (len - palette.len()) * 3, 0);
+    Ok(size)
+}
+
+fn u16_field(value: usize) -> Result<[u8; 2], CodecError> {
+    u16::try_from(value)
+        .map(u16::to_le_bytes)
+        .map_err(|_| CodecError::Invalid("image size out of range"))
+}
+
+/// Encodes every frame as stored. Frames whose palette equals the global palette refer to it
+/// instead of carrying a local copy.
+pub fn encode_animation(mut writer: impl Write, gif: &Gif) -> Result<(), CodecError> {
+    let header = &gif.header;
+    let extensions = header.loop_count.is_some()
+        || gif.frames.iter().any(|frame| {
+            frame.delay != 0
+                || frame.disposal != GifDisposal::Unspecified
+                || !frame.image.transparent_indices().is_empty()
+        });
+    let mut out = match (extensions, header.version) {
+        (false, GifVersion::Gif87a) => b"GIF87a".to_vec(),
+        _ => b"GIF89a".to_vec(),
+    };
+    out.extend(u16_field(header.width)?);
+    out.extend(u16_field(header.height)?);
+    let mut table = Vec::new();
+    let flags = match &header.palette {
+        Some(palette) => 0xf0 | write_palette(&mut table, palette)?,
+        None => 0,
+    };
+    out.extend([flags, header.background, 0]);
+    out.append(&mut table);
+    if let Some(count) = header.loop_count {
+        out.extend([0x21, 0xff, 11]);
+        out.extend(b"NETSCAPE2.0");
+        out.extend([3, 1]);
+        out.extend(count.to_le_bytes());
+        out.push(0);
+    }
+    writer.write_all(&out)?;
+
+    for frame in &gif.frames {
+        out.clear();
+        let image = &frame.image;
+        let transparent = image.transparent_indices().first().copied();
+        if frame.delay != 0 || frame.disposal != GifDisposal::Unspecified || transparent.is_some() {
+            let disposal = match frame.disposal {
+                GifDisposal::Unspecified => 0,
+                GifDisposal::Keep => 1,
+                GifDisposal::Background => 2,
+                GifDisposal::Previous => 3,
+            };
+            out.extend([0x21, 0xf9, 4, disposal << 2 | transparent.is_some() as u8]);
+            out.extend(frame.delay.to_le_bytes());
+            out.extend([transparent.unwrap_or(0), 0]);
+        }
+        out.push(0x2c);
+        out.extend(u16_field(frame.left)?);
+        out.extend(u16_field(frame.top)?);
+        out.extend(u16_field(image.width())?);
+        out.extend(u16_field(image.height())?);
+        let local = header.palette.as_deref() != Some(image.palette());
+        let mut flags = if frame.interlaced { 0x40 } else { 0 };
+        if local {
+            flags |= 0x80 | write_palette(&mut table, image.palette())?;
+        }
+        out.push(flags);
+        out.append(&mut table);
+
+        let len = if local {
+            image.palette().len()
+        } else {
+            header.palette.as_ref().map_or(0, Vec::len)
+        };
+        let min_size = (table_size(len).0 + 1).max(2);
+        out.push(min_size);
+        let mut rows: Vec<&[u8]> = image.indices().iter_rows().collect();
+        if frame.interlaced {
+            rows = interlaced_rows(rows.len()).map(|y| rows[y]).collect();
+        }
+        let data = lzw_encode(rows.into_iter().flatten().copied(), min_size);
+        write_sub_blocks(&mut out, &data);
+        writer.write_all(&out)?;
+    }
+    writer.write_all(&[0x3b])?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GifOptions {
+    /// Written as the `NETSCAPE2.0` extension, where 0 loops forever. `None` plays once.
+    pub loop_count: Option<u16>,
+    /// Alpha is never quantized: pixels with alpha below half are transparent, the rest opaque.
+    pub quantize: QuantizeOptions,
+    pub dither: Option<DitherMethod>,
+    /// Quantizes all frames together into one palette, instead of each frame on its own.
+    pub global_palette: bool,
+    /// Writes only the area that changed since the previous frame, leaving the pixels in it
+    /// that did not change transparent.
+    pub optimize: bool,
+}
+
+impl GifOptions {
+    pub const fn new() -> Self {
+        Self {
+            loop_count: Some(0),
+            quantize: QuantizeOptions::new(256),
+            dither: None,
+            global_palette: false,
+            optimize: true,
+        }
+    }
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The smallest area holding every pixel for which `f` holds, as left, top, right and bottom.
+fn bounds(width: usize, height: usize, f: impl Fn(usize, usize) -> bool) -> Option<[usize; 4]> {
+    let mut area = None;
+    for y in 0..height {
+        for x in 0..width {
+            if f(x, y) {
+                let pixel = [x, y, x + 1, y + 1];
+                area = Some(area.map_or(pixel, |area| union(area, pixel)));
+            }
+        }
+    }
+    area
+}
+
+fn union([left, top, right, bottom]: [usize; 4], other: [usize; 4]) -> [usize; 4] {
+    [
+        left.min(other[0]),
+        top.min(other[1]),
+        right.max(other[2]),
+        bottom.max(other[3]),
+    ]
+}
+
+/// The palette and the reduced colors of `image`, whose transparent pixels were filled in.
+fn quantize(
+    image: &Canvas,
+    options: &QuantizeOptions,
+    dither: Option<&DitherMethod>,
+) -> (Vec<Rgba<u8>>, Canvas) {
+    let reduced = image.quantize(*options);
+    let palette = reduced.palette().to_vec();
+    let colors = match dither {
+        Some(method) => {
+            let mut colors = image.map(|&pixel| pixel);
+            colors.dither(&DitherTarget::Palette(&palette, *options), method);
+            colors
+        }
+        None => reduced.to_truecolor(),
+    };
+    (palette, colors)
+}
+
+/// Quantizes full frames of the same size, each shown for its delay in hundredths of a second.
+pub fn encode_frames<'a, Source, P>(
+    writer: impl Write,
+    frames: impl IntoIterator<Item = (&'a Image<Source, P>, u16)>,
+    options: &GifOptions,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + 'a,
+    P: Quantizable + 'a,
+{
+    let (mut images, mut delays) = (Vec::new(), Vec::new());
+    for (image, delay) in frames {
+        images.push(image.map(|&pixel| Rgba::from(pixel.to_rgba())));
+        delays.push(delay);
+    }
+    let Some(first) = images.first() else {
+        return Err(CodecError::Invalid("no frames"));
+    };
+    let (width, height) = (first.width(), first.height());
+    if let Some(image) = images
+        .iter()
+        .find(|image| [image.width(), image.height()] != [width, height])
+    {
+        return Err(SizeMismatch {
+            left: [width, height],
+            right: [image.width(), image.height()],
+        }
+        .into());
+    }
+    let opaque: Vec<_> = images
+        .iter()
+        .map(|image| image.map(|p| p.a >= 128))
+        .collect();
+    let transparency = opaque.iter().any(|o| o.iter_rows().flatten().any(|&o| !o));
+    let optimize = options.optimize && images.len() > 1;
+    // Unchanged pixels are left transparent when optimizing, which takes a palette entry.
+    let reserve = transparency || optimize;
+    let quantize_options = QuantizeOptions {
+        colors: options.quantize.colors.min(256 - reserve as usize),
+        alpha: false,
+        ..options.quantize
+    };
+    // Transparent pixels take the color before them, so their hidden color adds nothing new.
+    for image in &mut images {
+        let mut last = Rgba::from([0, 0, 0, 255]);
+        for pixel in image.iter_rows_mut().flatten() {
+            if pixel.a < 128 {
+                *pixel = last;
+            } else {
+                last = *pixel;
+            }
+        }
+    }
+
+    let dither = options.dither.as_ref();
+    let (palettes, colors): (Vec<_>, Vec<_>) = if options.global_palette {
+        let stacked: Box<[_]> = images
+            .iter()
+            .flat_map(|image| image.iter())
+            .map(|(_, &p)| p)
+            .collect();
+        let stacked =
+            unsafe { Image::from_source_unchecked(width, height * images.len(), stacked) };
+        let (palette, colors) = quantize(&stacked, &quantize_options, None);
+        let rows: Vec<&[Rgba<u8>]> = colors.iter_rows().collect();
+        let colors = images
+            .iter()
+            .enumerate()
+            .map(|(i, image)| match dither {
+                Some(method) => {
+                    let mut colors = image.map(|&pixel| pixel);
+                    colors.dither(&DitherTarget::Palette(&palette, quantize_options), method);
+                    colors
+                }
+                None => {
+                    let rows = rows[i * height..(i + 1) * height].concat();
+                    unsafe { Image::from_source_unchecked(width, height, rows.into()) }
+                }
+            })
+            .collect();
+        (vec![palette], colors)
+    } else {
+        images
+            .iter()
+            .map(|image| quantize(image, &quantize_options, dither))
+            .unzip()
+    };
+    let targets: Vec<Canvas> = colors
+        .into_iter()
+        .zip(&opaque)
+        .map(|(mut colors, opaque)| {
+            let opaque = opaque.iter_rows().flatten();
+            for (pixel, &opaque) in colors.iter_rows_mut().flatten().zip(opaque) {
+                if !opaque {
+                    *pixel = CLEAR;
+                }
+            }
+            colors
+        })
+        .collect();
+
+    // Every frame with its area, its disposal and the canvas it is drawn onto.
+    let mut plans = Vec::with_capacity(targets.len());
+    let mut canvas = Image::filled(width, height, CLEAR);
+    for target in &targets {
+        if let Some((area, disposal, _)) = plans.last_mut().filter(|_| optimize) {
+            // Drawing can not make pixels transparent again, so the previous frame clears them
+            // when it is disposed.
+            let erase = bounds(width, height, |x, y| {
+                target[[x, y]].a == 0 && canvas[[x, y]].a != 0
+            });
+            if let Some(erase) = erase {
+                *area = union(*area, erase);
+                *disposal = GifDisposal::Background;
+                clear(&mut canvas, *area);
+            }
+        }
+        let (area, disposal) = if optimize {
+            let changed = bounds(width, height, |x, y| target[[x, y]] != canvas[[x, y]]);
+            let area = changed.unwrap_or([0, 0, width.min(1), height.min(1)]);
+            (area, GifDisposal::Keep)
+        } else if transparency {
+            ([0, 0, width, height], GifDisposal::Background)
+        } else {
+            ([0, 0, width, height], GifDisposal::Unspecified)
+        };
+        plans.push((area, disposal, canvas));
+        canvas = target.map(|&pixel| pixel);
+    }
+
+    let palettes: Vec<_> = palettes
+        .into_iter()
+        .map(|palette| {
+            let mut lookup = HashMap::new();
+            for (index, &color) in palette.iter().enumerate().rev() {
+                lookup.insert(color, index as u8);
+            }
+            let mut palette: Vec<_> = palette
+                .iter()
+                .map(|&Rgba { r, g, b, .. }| Rgb { r, g, b })
+                .collect();
+            let transparent = reserve.then(|| {
+                palette.push(Rgb { r: 0, g: 0, b: 0 });
+                palette.len() as u8 - 1
+            });
+            (palette, lookup, transparent)
+        })
+        .collect();
+    let mut frames = Vec::with_capacity(plans.len());
+    for (i, ([left, top, right, bottom], disposal, before)) in plans.into_iter().enumerate() {
+        let (palette, lookup, transparent) = &palettes[i.min(palettes.len() - 1)];
+        let target = &targets[i];
+        let mut indices = Vec::with_capacity((right - left) * (bottom - top));
+        for y in top..bottom {
+            for x in left..right {
+                let color = target[[x, y]];
+                indices.push(match *transparent {
+                    Some(index) if color.a == 0 || (optimize && before[[x, y]] == color) => index,
+                    _ => lookup[&color],
+                });
+            }
+        }
+        let indices = unsafe {
+            Image::from_source_unchecked(right - left, bottom - top, indices.into_boxed_slice())
+        };
+        let mut image = unsafe { PaletteImage::new_unchecked(indices, palette.clone()) };
+        image
+            .set_transparent_indices(transparent.iter().copied().collect())
+            .unwrap();
+        frames.push(GifFrame {
+            left,
+            top,
+            delay: delays[i],
+            disposal,
+            interlaced: false,
+            image,
+        });
+    }
+    let header = GifHeader {
+        version: GifVersion::Gif89a,
+        width,
+        height,
+        palette: options.global_palette.then(|| palettes[0].0.clone()),
+        background: 0,
+        loop_count: options.loop_count,
+    };
+    encode_animation(writer, &Gif { header, frames })
+}
+
+/// Encodes a single frame, quantizing it like [`encode_frames`].
+pub fn encode<Source, P>(
+    writer: impl Write,
+    image: &Image<Source, P>,
+    options: &GifOptions,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: Quantizable,
+{
+    encode_frames(writer, [(image, 0)], options)
+}
+
+pub fn encode_dynamic(
+    writer: impl Write,
+    image: &DynamicImage,
+    options: &GifOptions,
+) -> Result<(), CodecError> {
+    encode(writer, &image.to_image::<Rgba<u8>>(), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb<u8> = Rgb { r: 255, g: 0, b: 0 };
+    const GREEN: Rgb<u8> = Rgb { r: 0, g: 255, b: 0 };
+    const BLUE: Rgb<u8> = Rgb { r: 0, g: 0, b: 255 };
+
+    fn opaque(Rgb { r, g, b }: Rgb<u8>) -> Rgba<u8> {
+        Rgba { r, g, b, a: 255 }
+    }
+
+    fn frame(left: usize, width: usize, index: u8, disposal: GifDisposal) -> GifFrame {
+        let indices = Image::filled(width, 1, index);
+        let mut image = PaletteImage::new(indices, vec![RED, GREEN, BLUE, RED]).unwrap();
+        image.set_transparent_indices(vec![3]).unwrap();
+        GifFrame {
+            left,
+            top: 0,
+            delay: 0,
+            disposal,
+            interlaced: false,
+            image,
+        }
+    }
+
+    fn header(width: usize, height: usize) -> GifHeader {
+        GifHeader {
+            version: GifVersion::Gif89a,
+            width,
+            height,
+            palette: None,
+            background: 0,
+            loop_count: None,
+        }
+    }
+
+    #[test]
+    fn lzw_decode_known_streams() {
+        // Clear, 0, end with 3 bit codes.
+        assert_eq!(lzw_decode(&[0x44, 0x01], 2, 1).unwrap(), [0]);
+        // Clear, 0, then code 6 before it is in the table, which repeats the previous string.
+        assert_eq!(lzw_decode(&[0x84, 0x0b], 2, 3).unwrap(), [0, 0, 0]);
+        // Clear, then code 6 with no previous string.
+        assert!(matches!(
+            lzw_decode(&[0x34], 2, 1),
+            Err(CodecError::Invalid(_))
+        ));
+        assert!(matches!(
+            lzw_decode(&[0x44, 0x01], 2, 2),
+            Err(CodecError::Truncated)
+        ));
+        assert!(matches!(
+            lzw_decode(&[0x44], 2, 2),
+            Err(CodecError::Truncated)
+        ));
+    }
+
+    fn random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut state = 0x2545_f491_u32;
+        for min_size in [2, 4, 8] {
+            let colors = 1 << min_size;
+            let indices: Vec<u8> = (0..100_000)
+                .map(|i| match i % 3 {
+                    0 => (random(&mut state) % colors) as u8,
+                    _ => (i / 7 % colors as usize) as u8,
+                })
+                .collect();
+            let data = lzw_encode(indices.iter().copied(), min_size);
+            assert_eq!(lzw_decode(&data, min_size, indices.len()).unwrap(), indices);
+        }
+    }
+
+    #[test]
+    fn interlaced_row_order() {
+        let rows: Vec<usize> = interlaced_rows(10).collect();
+        assert_eq!(rows, [0, 8, 4, 2, 6, 1, 3, 5, 7, 9]);
+        // Indices 0, 1 and 2 in stream order land on rows 0, 2 and 1.
+        let mut data = b"GIF89a\x01\0\x03\0\x81\0\0".to_vec();
+        data.extend([255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 0]);
+        data.extend(b"\x2c\0\0\0\0\x01\0\x03\0\x40\x02\x02\x44\x54\0\x3b");
+        let gif = decode_animation(data.as_slice()).unwrap();
+        assert!(gif.frames[0].interlaced);
+        let indices = gif.frames[0].image.indices();
+        assert_eq!([0, 1, 2].map(|y| indices[[0, y]]), [0, 2, 1]);
+
+        let indices: Box<[u8]> = (0..30).map(|i| (i / 3 % 4) as u8).collect();
+        let indices = Image::from_source(3, 10, indices).ok().unwrap();
+        let image = PaletteImage::new(indices, vec![RED, GREEN, BLUE, RED]).unwrap();
+        let gif = Gif {
+            header: header(3, 10),
+            frames: vec![GifFrame {
+                interlaced: true,
+                image,
+                ..frame(0, 3, 0, GifDisposal::Unspecified)
+            }],
+        };
+        let mut data = Vec::new();
+        encode_animation(&mut data, &gif).unwrap();
+        let decoded = decode_animation(data.as_slice()).unwrap();
+        assert!(decoded.frames[0].interlaced);
+        assert!(decoded.frames[0]
+            .image
+            .indices()
+            .iter()
+            .eq(gif.frames[0].image.indices().iter()));
+    }
+
+    #[test]
+    fn disposal() {
+        let gif = Gif {
+            header: header(3, 1),
+            frames: vec![
+                frame(0, 3, 0, GifDisposal::Keep),
+                frame(1, 1, 1, GifDisposal::Background),
+                frame(2, 1, 2, GifDisposal::Previous),
+                frame(0, 2, 3, GifDisposal::Unspecified),
+            ],
+        };
+        let canvases = gif.composite().unwrap();
+        let rows: Vec<Vec<Rgba<u8>>> = canvases
+            .iter()
+            .map(|canvas| canvas.iter().map(|(_, &p)| p).collect())
+            .collect();
+        let [red, green, blue] = [RED, GREEN, BLUE].map(opaque);
+        assert_eq!(rows[0], [red, red, red]);
+        assert_eq!(rows[1], [red, green, red]);
+        // The green pixel was cleared, and the blue one is undone after it is shown.
+        assert_eq!(rows[2], [red, CLEAR, blue]);
+        assert_eq!(rows[3], [red, CLEAR, red]);
+    }
+
+    #[test]
+    fn transparent_index_past_color_table() {
+        let mut data = b"GIF89a\x01\0\x01\0\x80\0\0\xff\0\0\0\xff\0".to_vec();
+        data.extend(b"\x21\xf9\x04\x01\0\0\x05\0");
+        data.extend(b"\x2c\0\0\0\0\x01\0\x01\0\0\x02\x02\x44\x01\0\x3b");
+        let mut gif = decode_animation(data.as_slice()).unwrap();
+        let image = &mut gif.frames[0].image;
+        assert_eq!(image.palette().len(), 2);
+        assert!(image.transparent_indices().is_empty());
+        image.compact();
+        let canvases = gif.composite().unwrap();
+        assert_eq!(canvases[0][[0, 0]], opaque(RED));
+    }
+
+    #[test]
+    fn oversized_logical_screen() {
+        let mut data = b"GIF89a\xff\xff\xff\xff\x80\0\0\xff\0\0\0\xff\0".to_vec();
+        data.extend(b"\x2c\0\0\0\0\x01\0\x01\0\0\x02\x02\x44\x01\0\x3b");
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Invalid(_))
+        ));
+        let gif = decode_animation(data.as_slice()).unwrap();
+        assert!(gif.composite().is_err());
+        let gif = Gif {
+            header: header(2, 2),
+            frames: vec![frame(0, 1, 0, GifDisposal::Keep)],
+        };
+        assert!(gif.composite_with_limit(4).is_ok());
+        assert!(gif.composite_with_limit(3).is_err());
+    }
+
+    #[test]
+    fn encode_animation_round_trip() {
+        let mut state = 0x1234_5678_u32;
+        let mut palette = || -> Vec<Rgb<u8>> {
+            (0..256)
+                .map(|_| {
+                    let [r, g, b, _] = random(&mut state).to_le_bytes();
+                    Rgb { r, g, b }
+                })
+                .collect()
+        };
+        let (global, local) = (palette(), palette());
+        let mut frames = Vec::new();
+        for (i, palette) in [&global, &local, &global].into_iter().enumerate() {
+            let indices: Box<[u8]> = (0..37 * 23).map(|_| random(&mut state) as u8).collect();
+            let indices = Image::from_source(37, 23, indices).ok().unwrap();
+            let mut image = PaletteImage::new(indices, palette.clone()).unwrap();
+            image.set_transparent_indices(vec![i as u8 * 100]).unwrap();
+            frames.push(GifFrame {
+                left: i,
+                top: 2 * i,
+                delay: 10,
+                disposal: GifDisposal::Keep,
+                interlaced: i == 1,
+                image,
+            });
+        }
+        let gif = Gif {
+            header: GifHeader {
+                palette: Some(global),
+                loop_count: Some(3),
+                ..header(40, 30)
+            },
+            frames,
+        };
+        let mut data = Vec::new();
+        encode_animation(&mut data, &gif).unwrap();
+        let decoded = decode_animation(data.as_slice()).unwrap();
+        assert_eq!(decoded.header, gif.header);
+        assert_eq!(decoded.frames.len(), gif.frames.len());
+        for (frame, decoded) in gif.frames.iter().zip(&decoded.frames) {
+            assert_eq!(
+                [frame.left, frame.top, frame.delay as usize],
+                [decoded.left, decoded.top, decoded.delay as usize]
+            );
+            assert_eq!(frame.disposal, decoded.disposal);
+            assert_eq!(frame.interlaced, decoded.interlaced);
+            assert_eq!(frame.image.palette(), decoded.image.palette());
+            assert_eq!(
+                frame.image.transparent_indices(),
+                decoded.image.transparent_indices()
+            );
+            assert!(frame
+                .image
+                .indices()
+                .iter()
+                .eq(decoded.image.indices().iter()));
+        }
+    }
+
+    /// Frames of a few colors, so that quantizing keeps them exact, with transparent pixels
+    /// appearing and disappearing between frames.
+    fn animation() -> Vec<Canvas> {
+        let colors = [RED, GREEN, BLUE, Rgb { r: 9, g: 8, b: 7 }].map(opaque);
+        let mut state = 0x0bad_cafe_u32;
+        let mut image = Image::filled(12, 8, colors[0]);
+        let mut frames = Vec::new();
+        for i in 0..6 {
+            let [x, y] = [i * 2, i].map(|start| start..start + 3);
+            for y in y {
+                for x in x.clone() {
+                    image[[x % 12, y % 8]] = match random(&mut state) % 5 {
+                        4 => CLEAR,
+                        color => colors[color as usize],
+                    };
+                }
+            }
+            frames.push(image.map(|&pixel| pixel));
+        }
+        // An unchanged frame.
+        frames.push(image);
+        frames
+    }
+
+    #[test]
+    fn encode_frames_round_trip() {
+        let frames = animation();
+        for optimize in [false, true] {
+            for global_palette in [false, true] {
+                let options = GifOptions {
+                    optimize,
+                    global_palette,
+                    ..GifOptions::new()
+                };
+                let mut data = Vec::new();
+                encode_frames(&mut data, frames.iter().map(|frame| (frame, 5)), &options).unwrap();
+                let gif = decode_animation(data.as_slice()).unwrap();
+                assert_eq!(gif.header.palette.is_some(), global_palette);
+                assert_eq!(gif.header.loop_count, Some(0));
+                assert!(gif.frames.iter().all(|frame| frame.delay == 5));
+                let canvases = gif.composite().unwrap();
+                assert_eq!(canvases.len(), frames.len());
+                for (i, (frame, canvas)) in frames.iter().zip(&canvases).enumerate() {
+                    assert!(frame.iter().eq(canvas.iter()), "frame {i} with {options:?}");
+                }
+                let last = &gif.frames[frames.len() - 1].image;
+                if optimize {
+                    assert_eq!([last.width(), last.height()], [1, 1]);
+                } else {
+                    assert_eq!([last.width(), last.height()], [12, 8]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn optimized_area() {
+        let first = Image::filled(20, 10, opaque(RED));
+        let mut second = first.map(|&pixel| pixel);
+        second[[5, 3]] = opaque(GREEN);
+        second[[7, 6]] = opaque(BLUE);
+        let mut data = Vec::new();
+        encode_frames(&mut data, [(&first, 1), (&second, 1)], &GifOptions::new()).unwrap();
+        let gif = decode_animation(data.as_slice()).unwrap();
+        let frame = &gif.frames[1];
+        assert_eq!(
+            [
+                frame.left,
+                frame.top,
+                frame.image.width(),
+                frame.image.height()
+            ],
+            [5, 3, 3, 4]
+        );
+        assert_eq!(gif.frames[0].disposal, GifDisposal::Keep);
+
+        // Clearing a pixel widens the previous frame, which then clears its area.
+        second[[7, 6]] = CLEAR;
+        let mut data = Vec::new();
+        encode_frames(&mut data, [(&first, 1), (&second, 1)], &GifOptions::new()).unwrap();
+        let gif = decode_animation(data.as_slice()).unwrap();
+        assert_eq!(gif.frames[0].disposal, GifDisposal::Background);
+        let canvases = gif.composite().unwrap();
+        assert!(canvases[1].iter().eq(second.iter()));
+    }
+}
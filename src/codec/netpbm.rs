@@ -0,0 +1,592 @@
+use crate::{
+    codec::{copy_into, from_samples, to_samples},
+    dynamic::{ChannelLayout, ChannelType, DynamicImage, DynamicPixel, ImageVisitor},
+    error::CodecError,
+    Image,
+};
+use std::io::{Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetpbmKind {
+    /// PBM, `P1` and `P4`.
+    Bitmap,
+    /// PGM, `P2` and `P5`.
+    Graymap,
+    /// PPM, `P3` and `P6`.
+    Pixmap,
+    /// PAM, `P7`.
+    Arbitrary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetpbmHeader {
+    pub kind: NetpbmKind,
+    pub ascii: bool,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub maxval: u16,
+    /// Only set for PAM.
+    pub tuple_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetpbmOptions {
+    pub kind: NetpbmKind,
+    /// Writes the plain variant with decimal samples. PAM has no plain variant and ignores this.
+    pub ascii: bool,
+    /// Defaults to 255 for 8 bit images and 65535 for the rest. Ignored for PBM.
+    pub maxval: Option<u16>,
+    /// Defaults to the standard tuple type of the layout. PAM only.
+    pub tuple_type: Option<String>,
+}
+
+impl NetpbmOptions {
+    pub const fn new(kind: NetpbmKind) -> Self {
+        Self {
+            kind,
+            ascii: false,
+            maxval: None,
+            tuple_type: None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte == b'#' {
+                while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    fn number(&mut self) -> Result<u32, CodecError> {
+        self.skip_space();
+        let start = self.pos;
+        while self.data.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| CodecError::Invalid("expected a number"))
+    }
+    fn line(&mut self) -> Option<&'a str> {
+        let rest = self.data.get(self.pos..).filter(|rest| !rest.is_empty())?;
+        let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.pos += (len + 1).min(rest.len());
+        std::str::from_utf8(&rest[..len]).ok()
+    }
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CodecError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or(CodecError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+}
+
+fn header(parser: &mut Parser) -> Result<NetpbmHeader, CodecError> {
+    let magic = parser.bytes(2)?;
+    if magic[0] != b'P' || !(b'1'..=b'7').contains(&magic[1]) {
+        return Err(CodecError::Invalid("not a netpbm file"));
+    }
+    let number = magic[1] - b'0';
+    if number == 7 {
+        return pam_header(parser);
+    }
+    let kind = match number {
+        1 | 4 => NetpbmKind::Bitmap,
+        2 | 5 => NetpbmKind::Graymap,
+        _ => NetpbmKind::Pixmap,
+    };
+    let width = parser.number()? as usize;
+    let height = parser.number()? as usize;
+    let maxval = if kind == NetpbmKind::Bitmap {
+        1
+    } else {
+        parser.number()?
+    };
+    if !(1..=65535).contains(&maxval) {
+        return Err(CodecError::Invalid("maxval must be within 1..=65535"));
+    }
+    if number > 3 {
+        // Exactly one whitespace byte separates the header from raw samples.
+        parser.bytes(1)?;
+    }
+    Ok(NetpbmHeader {
+        kind,
+        ascii: number <= 3,
+        width,
+        height,
+        depth: if kind == NetpbmKind::Pixmap { 3 } else { 1 },
+        maxval: maxval as u16,
+        tuple_type: None,
+    })
+}
+
+fn pam_header(parser: &mut Parser) -> Result<NetpbmHeader, CodecError> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut tuple_type: Option<String> = None;
+    loop {
+        let line = parser
+            .line()
+            .ok_or(CodecError::Invalid("PAM header has no ENDHDR"))?
+            .trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+        let number = || -> Result<usize, CodecError> {
+            value
+                .parse()
+                .map_err(|_| CodecError::Invalid("expected a number"))
+        };
+        match key {
+            "ENDHDR" => break,
+            "WIDTH" => width = Some(number()?),
+            "HEIGHT" => height = Some(number()?),
+            "DEPTH" => depth = Some(number()?),
+            "MAXVAL" => maxval = Some(number()?),
+            "TUPLTYPE" => match &mut tuple_type {
+                Some(tuple_type) => {
+                    tuple_type.push(' ');
+                    tuple_type.push_str(value);
+                }
+                None => tuple_type = Some(value.into()),
+            },
+            _ => return Err(CodecError::Invalid("unknown PAM header field")),
+        }
+    }
+    let missing = CodecError::Invalid("PAM header is missing a field");
+    let (Some(width), Some(height), Some(depth), Some(maxval)) = (width, height, depth, maxval)
+    else {
+        return Err(missing);
+    };
+    if !(1..=65535).contains(&maxval) {
+        return Err(CodecError::Invalid("maxval must be within 1..=65535"));
+    }
+    Ok(NetpbmHeader {
+        kind: NetpbmKind::Arbitrary,
+        ascii: false,
+        width,
+        height,
+        depth,
+        maxval: maxval as u16,
+        tuple_type,
+    })
+}
+
+pub fn decode_with_header(
+    mut reader: impl Read,
+) -> Result<(NetpbmHeader, DynamicImage), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut parser = Parser {
+        data: &data,
+        pos: 0,
+    };
+    let header = header(&mut parser)?;
+    let layout = match header.depth {
+        1 => ChannelLayout::Luma,
+        2 => ChannelLayout::LumaA,
+        3 => ChannelLayout::Rgb,
+        4 => ChannelLayout::Rgba,
+        0 => return Err(CodecError::Invalid("depth must be at least 1")),
+        _ => return Err(CodecError::Unsupported("PAM depth above 4")),
+    };
+    let count = header
+        .width
+        .checked_mul(header.height)
+        .and_then(|pixels| pixels.checked_mul(header.depth))
+        .filter(|&count| count <= data.len() * 8)
+        .ok_or(CodecError::Truncated)?;
+    let mut samples = Vec::with_capacity(count);
+    match (header.kind, header.ascii) {
+        (NetpbmKind::Bitmap, true) => {
+            for _ in 0..count {
+                parser.skip_space();
+                match parser.bytes(1)?[0] {
+                    b'0' => samples.push(0),
+                    b'1' => samples.push(1),
+                    _ => return Err(CodecError::Invalid("expected 0 or 1")),
+                }
+            }
+        }
+        (NetpbmKind::Bitmap, false) => {
+            for _ in 0..header.height {
+                let row = parser.bytes(header.width.div_ceil(8))?;
+                samples.extend((0..header.width).map(|x| ((row[x / 8] >> (7 - x % 8)) & 1) as u16));
+            }
+        }
+        (_, true) => {
+            for _ in 0..count {
+                samples.push(
+                    u16::try_from(parser.number()?)
+                        .map_err(|_| CodecError::Invalid("sample exceeds maxval"))?,
+                );
+            }
+        }
+        (_, false) if header.maxval > 255 => {
+            let raw = parser.bytes(count * 2)?;
+            samples.extend(
+                raw.chunks_exact(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]])),
+            );
+        }
+        (_, false) => samples.extend(parser.bytes(count)?.iter().map(|&b| b as u16)),
+    }
+    let wide = header.maxval > 255;
+    let target = if wide { 65535 } else { 255 };
+    let maxval = header.maxval as u32;
+    for sample in &mut samples {
+        if *sample as u32 > maxval {
+            return Err(CodecError::Invalid("sample exceeds maxval"));
+        }
+        let scaled = (*sample as u32 * target + maxval / 2) / maxval;
+        // PBM stores ink, so 1 is black.
+        *sample = if header.kind == NetpbmKind::Bitmap {
+            (target - scaled) as u16
+        } else {
+            scaled as u16
+        };
+    }
+    let image = from_samples(header.width, header.height, layout, wide, &samples);
+    Ok((header, image))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target`, converting the pixels. `target` may be a region of a larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    Ok(copy_into(&decode(reader)?, target)?)
+}
+
+/// Collects decimal samples into lines of at most 70 characters.
+struct AsciiLines {
+    out: Vec<u8>,
+    line: usize,
+}
+
+impl AsciiLines {
+    fn push(&mut self, sample: u16, separated: bool) {
+        let text = sample.to_string();
+        let needed = text.len() + (separated && self.line != 0) as usize;
+        if self.line + needed > 70 {
+            self.out.push(b'\n');
+            self.line = 0;
+        } else if separated && self.line != 0 {
+            self.out.push(b' ');
+            self.line += 1;
+        }
+        self.out.extend_from_slice(text.as_bytes());
+        self.line += text.len();
+    }
+    fn end_row(&mut self) {
+        self.out.push(b'\n');
+        self.line = 0;
+    }
+}
+
+pub fn encode<Source, P>(
+    mut writer: impl Write,
+    image: &Image<Source, P>,
+    options: &NetpbmOptions,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    let format = P::FORMAT;
+    let (width, height) = (image.width(), image.height());
+    let (layout, maxval) = match options.kind {
+        NetpbmKind::Bitmap => (ChannelLayout::Luma, 1),
+        kind => {
+            let layout = match kind {
+                NetpbmKind::Graymap => ChannelLayout::Luma,
+                NetpbmKind::Pixmap => ChannelLayout::Rgb,
+                _ => format.layout,
+            };
+            let default = if format.channel_type == ChannelType::U8 {
+                255
+            } else {
+                65535
+            };
+            (layout, options.maxval.unwrap_or(default))
+        }
+    };
+    if maxval == 0 {
+        return Err(CodecError::Invalid("maxval must be at least 1"));
+    }
+    let ascii = options.ascii && options.kind != NetpbmKind::Arbitrary;
+    let channels = layout.channels();
+
+    let mut out = Vec::new();
+    match options.kind {
+        NetpbmKind::Arbitrary => {
+            let tuple_type = options
+                .tuple_type
+                .as_deref()
+                .unwrap_or(match (layout, maxval) {
+                    (ChannelLayout::Luma, 1) => "BLACKANDWHITE",
+                    (ChannelLayout::LumaA, 1) => "BLACKANDWHITE_ALPHA",
+                    (ChannelLayout::Luma, _) => "GRAYSCALE",
+                    (ChannelLayout::LumaA, _) => "GRAYSCALE_ALPHA",
+                    (ChannelLayout::Rgb, _) => "RGB",
+                    (ChannelLayout::Rgba, _) => "RGB_ALPHA",
+                });
+            write!(
+                out,
+                "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH {channels}\nMAXVAL {maxval}\nTUPLTYPE {tuple_type}\nENDHDR\n"
+            )?;
+        }
+        kind => {
+            let number = match kind {
+                NetpbmKind::Bitmap => 1,
+                NetpbmKind::Graymap => 2,
+                _ => 3,
+            } + if ascii { 0 } else { 3 };
+            write!(out, "P{number}\n{width} {height}\n")?;
+            if kind != NetpbmKind::Bitmap {
+                writeln!(out, "{maxval}")?;
+            }
+        }
+    }
+
+    let mut lines = AsciiLines { out, line: 0 };
+    for row in image.iter_rows() {
+        let samples = row.iter().flat_map(|&pixel| {
+            let mut samples = to_samples(pixel, layout, maxval);
+            if options.kind == NetpbmKind::Bitmap {
+                samples[0] = 1 - samples[0];
+            }
+            samples.into_iter().take(channels)
+        });
+        match (options.kind, ascii) {
+            (NetpbmKind::Bitmap, true) => {
+                samples.for_each(|sample| lines.push(sample, false));
+                lines.end_row();
+            }
+            (_, true) => {
+                samples.for_each(|sample| lines.push(sample, true));
+                lines.end_row();
+            }
+            (NetpbmKind::Bitmap, false) => {
+                let mut bits: Vec<u16> = samples.collect();
+                bits.resize(width.div_ceil(8) * 8, 0);
+                lines.out.extend(
+                    bits.chunks(8)
+                        .map(|byte| byte.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8)),
+                );
+            }
+            (_, false) if maxval > 255 => lines.out.extend(samples.flat_map(u16::to_be_bytes)),
+            (_, false) => lines.out.extend(samples.map(|sample| sample as u8)),
+        }
+    }
+    writer.write_all(&lines.out)?;
+    Ok(())
+}
+
+struct Encode<'a, W> {
+    writer: W,
+    options: &'a NetpbmOptions,
+}
+
+impl<'a, W: Write> ImageVisitor for Encode<'a, W> {
+    type Output = Result<(), CodecError>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        encode(self.writer, image, self.options)
+    }
+}
+
+pub fn encode_dynamic(
+    writer: impl Write,
+    image: &DynamicImage,
+    options: &NetpbmOptions,
+) -> Result<(), CodecError> {
+    image.visit(Encode { writer, options })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::{Luma, LumaA, Rgb, Rgba};
+
+    fn image<P>(pixel: impl Fn(usize, usize) -> P) -> Image<Box<[P]>, P> {
+        let pixels = (0..15).map(|i| pixel(i % 5, i / 5)).collect();
+        Image::from_source(5, 3, pixels).ok().unwrap()
+    }
+
+    fn round_trip<P>(image: &Image<Box<[P]>, P>, options: &NetpbmOptions, magic: &[u8])
+    where
+        P: DynamicPixel + PartialEq + core::fmt::Debug,
+    {
+        let mut data = Vec::new();
+        encode(&mut data, image, options).unwrap();
+        assert!(data.starts_with(magic), "{options:?}");
+        let (header, decoded) = decode_with_header(data.as_slice()).unwrap();
+        assert_eq!(
+            (header.kind, header.ascii),
+            (options.kind, options.ascii && magic != b"P7")
+        );
+        assert_eq!(decoded.as_image::<P>(), Some(image), "{options:?}");
+    }
+
+    #[test]
+    fn round_trips() {
+        let bits = image(|x, y| Luma {
+            l: if (x + y) % 3 == 0 { 255u8 } else { 0 },
+        });
+        let gray = image(|x, y| Luma {
+            l: (x * 50 + y) as u8,
+        });
+        let rgb = image(|x, y| Rgb {
+            r: (x * 50) as u8,
+            g: (y * 100) as u8,
+            b: 7,
+        });
+        let kinds: [(NetpbmKind, &[u8; 2], &[u8; 2]); 3] = [
+            (NetpbmKind::Bitmap, b"P1", b"P4"),
+            (NetpbmKind::Graymap, b"P2", b"P5"),
+            (NetpbmKind::Pixmap, b"P3", b"P6"),
+        ];
+        for (kind, plain, raw) in kinds {
+            for (ascii, magic) in [(true, plain), (false, raw)] {
+                let options = NetpbmOptions {
+                    ascii,
+                    ..NetpbmOptions::new(kind)
+                };
+                match kind {
+                    NetpbmKind::Bitmap => round_trip(&bits, &options, magic),
+                    NetpbmKind::Graymap => round_trip(&gray, &options, magic),
+                    _ => round_trip(&rgb, &options, magic),
+                }
+            }
+        }
+        let options = NetpbmOptions::new(NetpbmKind::Arbitrary);
+        round_trip(&gray, &options, b"P7");
+        round_trip(
+            &image(|x, y| LumaA {
+                l: x as u8,
+                a: y as u8 * 9,
+            }),
+            &options,
+            b"P7",
+        );
+        round_trip(&rgb, &options, b"P7");
+        round_trip(
+            &image(|x, y| Rgba {
+                r: x as u8,
+                g: 1,
+                b: 2,
+                a: y as u8,
+            }),
+            &options,
+            b"P7",
+        );
+    }
+
+    #[test]
+    fn sixteen_bit() {
+        let rgb = image(|x, y| Rgb {
+            r: x as u16 * 13001,
+            g: y as u16 * 30001,
+            b: 0xfffe,
+        });
+        for (ascii, magic) in [(true, b"P3"), (false, b"P6")] {
+            let options = NetpbmOptions {
+                ascii,
+                ..NetpbmOptions::new(NetpbmKind::Pixmap)
+            };
+            round_trip(&rgb, &options, magic);
+        }
+        let gray = image(|x, _| Luma {
+            l: x as u16 * 16000,
+        });
+        round_trip(&gray, &NetpbmOptions::new(NetpbmKind::Arbitrary), b"P7");
+    }
+
+    #[test]
+    fn comments_and_maxval() {
+        let data = b"P2\n# comment\n3 # width\n1\n#\n4\n0 2 # two\n4\n";
+        let (header, decoded) = decode_with_header(&data[..]).unwrap();
+        assert_eq!((header.width, header.height, header.maxval), (3, 1, 4));
+        let decoded = decoded.as_image::<Luma<u8>>().unwrap();
+        let samples: Vec<u8> = decoded.iter().map(|(_, p)| p.l).collect();
+        assert_eq!(samples, [0, 128, 255]);
+
+        // Above 255 the samples become 16 bit.
+        let data =
+            b"P7\n# comment\nWIDTH 2\nHEIGHT 1\nDEPTH 1\nMAXVAL 1000\nENDHDR\n\x01\xf4\x03\xe8";
+        let decoded = decode(&data[..]).unwrap();
+        let decoded = decoded.as_image::<Luma<u16>>().unwrap();
+        let samples: Vec<u16> = decoded.iter().map(|(_, p)| p.l).collect();
+        assert_eq!(samples, [32768, 65535]);
+
+        let options = NetpbmOptions {
+            maxval: Some(15),
+            ..NetpbmOptions::new(NetpbmKind::Graymap)
+        };
+        let mut data = Vec::new();
+        encode(&mut data, &image(|x, _| Luma { l: x as u8 * 17 }), &options).unwrap();
+        assert_eq!(&data[..10], b"P5\n5 3\n15\n");
+        assert_eq!(&data[10..15], [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn invalid() {
+        let truncated: [&[u8]; 6] = [
+            b"P5\n2 2\n255\n\0\0\0",
+            b"P5\n1 1\n1000\n\0",
+            b"P4\n9 2\n\0\0\0",
+            b"P1\n2 2\n0 1 1",
+            b"P6\n100000 100000\n255\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nENDHDR\n\0\0",
+        ];
+        for data in truncated {
+            assert!(
+                matches!(decode(data), Err(CodecError::Truncated)),
+                "{data:?}"
+            );
+        }
+        let invalid: [&[u8]; 5] = [
+            b"P3\n1 1\n255\n0 0",
+            b"P2\n1 1\n3\n4",
+            b"P2\n1 1\n0\n0",
+            b"P8\n1 1\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nMAXVAL 255\n",
+        ];
+        for data in invalid {
+            assert!(
+                matches!(decode(data), Err(CodecError::Invalid(_))),
+                "{data:?}"
+            );
+        }
+    }
+}
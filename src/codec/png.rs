@@ -0,0 +1,1064 @@
+use crate::{
+    codec::{crc::crc32, from_samples, from_samples_pixel, to_samples, zlib},
+    dynamic::{ChannelLayout, ChannelType, DynamicImage, DynamicPixel, ImageVisitor},
+    error::{CodecError, SizeMismatch},
+    pixel::Rgb,
+    Image,
+};
+use std::io::{Read, Write};
+
+pub(crate) const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The origin and spacing of each Adam7 pass, as `[x, y, dx, dy]`.
+const ADAM7: [[usize; 4]; 7] = [
+    [0, 0, 8, 8],
+    [4, 0, 8, 8],
+    [0, 4, 4, 8],
+    [2, 0, 4, 4],
+    [0, 2, 2, 4],
+    [1, 0, 2, 2],
+    [0, 1, 1, 2],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PngColorType {
+    Gray,
+    Rgb,
+    Indexed,
+    GrayAlpha,
+    Rgba,
+}
+
+impl PngColorType {
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Gray),
+            2 => Some(Self::Rgb),
+            3 => Some(Self::Indexed),
+            4 => Some(Self::GrayAlpha),
+            6 => Some(Self::Rgba),
+            _ => None,
+        }
+    }
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::Gray => 0,
+            Self::Rgb => 2,
+            Self::Indexed => 3,
+            Self::GrayAlpha => 4,
+            Self::Rgba => 6,
+        }
+    }
+    /// Samples per pixel in the file.
+    pub const fn channels(self) -> usize {
+        match self {
+            Self::Gray | Self::Indexed => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+/// The contents of a `tRNS` chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PngTransparency {
+    /// Alpha for the first palette entries, the rest are opaque.
+    Palette(Vec<u8>),
+    /// The gray sample that is fully transparent.
+    Gray(u16),
+    /// The color that is fully transparent.
+    Rgb([u16; 3]),
+}
+
+/// The contents of a `pHYs` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PngPhysical {
+    pub x: u32,
+    pub y: u32,
+    /// Pixels per meter when set, otherwise only the aspect ratio is known.
+    pub meters: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PngInfo {
+    pub width: usize,
+    pub height: usize,
+    pub bit_depth: u8,
+    pub color_type: PngColorType,
+    pub interlaced: bool,
+    pub palette: Option<Vec<Rgb<u8>>>,
+    pub transparency: Option<PngTransparency>,
+    /// The `gAMA` value, gamma times 100000.
+    pub gamma: Option<u32>,
+    pub physical: Option<PngPhysical>,
+    /// `tEXt` keywords and values.
+    pub text: Vec<(String, String)>,
+}
+
+impl PngInfo {
+    /// The layout and width of the decoded samples.
+    fn output(&self) -> (ChannelLayout, bool) {
+        let keyed = self.transparency.is_some();
+        let layout = match self.color_type {
+            PngColorType::Gray if keyed => ChannelLayout::LumaA,
+            PngColorType::Gray => ChannelLayout::Luma,
+            PngColorType::Rgb | PngColorType::Indexed if keyed => ChannelLayout::Rgba,
+            PngColorType::Rgb | PngColorType::Indexed => ChannelLayout::Rgb,
+            PngColorType::GrayAlpha => ChannelLayout::LumaA,
+            PngColorType::Rgba => ChannelLayout::Rgba,
+        };
+        (layout, self.bit_depth == 16)
+    }
+    fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn header(data: &[u8]) -> Result<PngInfo, CodecError> {
+    if data.len() != 13 {
+        return Err(CodecError::Invalid("IHDR must be 13 bytes"));
+    }
+    let [width, height] = [be_u32(data), be_u32(&data[4..])];
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(CodecError::Invalid("image size out of range"));
+    }
+    let bit_depth = data[8];
+    let color_type =
+        PngColorType::from_code(data[9]).ok_or(CodecError::Invalid("invalid color type"))?;
+    let depth_ok = match color_type {
+        PngColorType::Gray => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+        PngColorType::Indexed => matches!(bit_depth, 1 | 2 | 4 | 8),
+        _ => matches!(bit_depth, 8 | 16),
+    };
+    if !depth_ok {
+        return Err(CodecError::Invalid("invalid bit depth for the color type"));
+    }
+    if data[10] != 0 || data[11] != 0 {
+        return Err(CodecError::Unsupported("compression or filter method"));
+    }
+    let interlaced = match data[12] {
+        0 => false,
+        1 => true,
+        _ => return Err(CodecError::Unsupported("interlace method")),
+    };
+    Ok(PngInfo {
+        width: width as usize,
+        height: height as usize,
+        bit_depth,
+        color_type,
+        interlaced,
+        palette: None,
+        transparency: None,
+        gamma: None,
+        physical: None,
+        text: Vec::new(),
+    })
+}
+
+fn transparency(info: &PngInfo, data: &[u8]) -> Result<PngTransparency, CodecError> {
+    let sample = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+    match info.color_type {
+        PngColorType::Indexed => {
+            let palette = info
+                .palette
+                .as_ref()
+                .ok_or(CodecError::Invalid("tRNS before PLTE"))?;
+            if data.len() > palette.len() {
+                return Err(CodecError::Invalid("tRNS is longer than the palette"));
+            }
+            Ok(PngTransparency::Palette(data.to_vec()))
+        }
+        PngColorType::Gray if data.len() == 2 => Ok(PngTransparency::Gray(sample(0))),
+        PngColorType::Rgb if data.len() == 6 => {
+            Ok(PngTransparency::Rgb([sample(0), sample(1), sample(2)]))
+        }
+        PngColorType::Gray | PngColorType::Rgb => {
+            Err(CodecError::Invalid("tRNS has the wrong length"))
+        }
+        _ => Err(CodecError::Invalid("tRNS on a color type with alpha")),
+    }
+}
+
+/// Reads the chunks, returning the header and the concatenated `IDAT` data.
+fn chunks(data: &[u8]) -> Result<(PngInfo, Vec<u8>), CodecError> {
+    if data.get(..8).ok_or(CodecError::Truncated)? != SIGNATURE {
+        return Err(CodecError::Invalid("not a PNG file"));
+    }
+    let mut pos = 8;
+    let mut info = None;
+    let mut idat = Vec::new();
+    loop {
+        let len = be_u32(data.get(pos..pos + 4).ok_or(CodecError::Truncated)?);
+        if len > i32::MAX as u32 {
+            return Err(CodecError::Invalid("chunk length out of range"));
+        }
+        let end = pos + 8 + len as usize;
+        // The CRC covers the type and the data.
+        let checked = data.get(pos + 4..end).ok_or(CodecError::Truncated)?;
+        let expected = be_u32(data.get(end..end + 4).ok_or(CodecError::Truncated)?);
+        let found = crc32(checked);
+        if expected != found {
+            return Err(CodecError::ChecksumMismatch { expected, found });
+        }
+        let (kind, body) = checked.split_at(4);
+        pos = end + 4;
+
+        let Some(info) = &mut info else {
+            if kind != b"IHDR" {
+                return Err(CodecError::Invalid("IHDR must come first"));
+            }
+            info = Some(header(body)?);
+            continue;
+        };
+        match kind {
+            b"IHDR" => return Err(CodecError::Invalid("more than one IHDR")),
+            b"PLTE" => {
+                if body.len() % 3 != 0 || body.is_empty() || body.len() > 3 * 256 {
+                    return Err(CodecError::Invalid("PLTE has the wrong length"));
+                }
+                info.palette = Some(
+                    body.chunks_exact(3)
+                        .map(|c| Rgb {
+                            r: c[0],
+                            g: c[1],
+                            b: c[2],
+                        })
+                        .collect(),
+                );
+            }
+            b"tRNS" => info.transparency = Some(transparency(info, body)?),
+            b"gAMA" if body.len() == 4 => info.gamma = Some(be_u32(body)),
+            b"pHYs" if body.len() == 9 => {
+                info.physical = Some(PngPhysical {
+                    x: be_u32(body),
+                    y: be_u32(&body[4..]),
+                    meters: body[8] == 1,
+                })
+            }
+            b"tEXt" => {
+                // Latin-1, which maps directly onto the first 256 code points.
+                let latin1 = |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect();
+                if let Some(split) = body.iter().position(|&b| b == 0) {
+                    info.text
+                        .push((latin1(&body[..split]), latin1(&body[split + 1..])));
+                }
+            }
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            // Unknown ancillary chunks have a lowercase first letter and can be skipped.
+            _ if kind[0].is_ascii_uppercase() => {
+                return Err(CodecError::Unsupported("unknown critical chunk"))
+            }
+            _ => {}
+        }
+    }
+    let info = info.unwrap();
+    if info.color_type == PngColorType::Indexed && info.palette.is_none() {
+        return Err(CodecError::Invalid("indexed image without PLTE"));
+    }
+    Ok((info, idat))
+}
+
+/// The sizes of the non-empty passes, with their Adam7 placement.
+fn passes(info: &PngInfo) -> Vec<([usize; 4], usize, usize)> {
+    let placements: &[[usize; 4]] = if info.interlaced {
+        &ADAM7
+    } else {
+        &[[0, 0, 1, 1]]
+    };
+    placements
+        .iter()
+        .map(|&[x, y, dx, dy]| {
+            let width = info.width.saturating_sub(x).div_ceil(dx);
+            let height = info.height.saturating_sub(y).div_ceil(dy);
+            ([x, y, dx, dy], width, height)
+        })
+        .filter(|&(_, width, height)| width != 0 && height != 0)
+        .collect()
+}
+
+pub(crate) fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses `filter` on `row`, where `bpp` is the distance in bytes to the left neighbour.
+fn unfilter(filter: u8, row: &mut [u8], prev: Option<&[u8]>, bpp: usize) -> Result<(), CodecError> {
+    let up = |i: usize| prev.map_or(0, |prev| prev[i]);
+    match filter {
+        0 => {}
+        1 => {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        2 => {
+            for (byte, &up) in row.iter_mut().zip(prev.unwrap_or_default()) {
+                *byte = byte.wrapping_add(up);
+            }
+        }
+        3 => {
+            for i in 0..row.len() {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                row[i] = row[i].wrapping_add(((left as u16 + up(i) as u16) / 2) as u8);
+            }
+        }
+        4 => {
+            for i in 0..row.len() {
+                let (left, corner) = if i >= bpp {
+                    (row[i - bpp], up(i - bpp))
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, up(i), corner));
+            }
+        }
+        _ => return Err(CodecError::Invalid("invalid filter type")),
+    }
+    Ok(())
+}
+
+/// Converts one unfiltered row of `width` pixels into output samples.
+fn expand(info: &PngInfo, raw: &[u8], width: usize, out: &mut Vec<u16>) -> Result<(), CodecError> {
+    out.clear();
+    let depth = info.bit_depth as usize;
+    let count = width * info.color_type.channels();
+    let sample = |i: usize| -> u16 {
+        match depth {
+            16 => u16::from_be_bytes([raw[2 * i], raw[2 * i + 1]]),
+            8 => raw[i] as u16,
+            _ => {
+                let bit = i * depth;
+                ((raw[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1)) as u16
+            }
+        }
+    };
+    // Low bit depth gray is scaled up to 8 bits.
+    let scale = match depth {
+        1 => 255,
+        2 => 85,
+        4 => 17,
+        _ => 1,
+    };
+    let opaque = if depth == 16 { 65535 } else { 255 };
+    match (info.color_type, &info.transparency) {
+        (PngColorType::Indexed, transparency) => {
+            let palette = info.palette.as_deref().unwrap_or_default();
+            let alpha = match transparency {
+                Some(PngTransparency::Palette(alpha)) => Some(&alpha[..]),
+                _ => None,
+            };
+            for i in 0..count {
+                let index = sample(i) as usize;
+                let color = palette
+                    .get(index)
+                    .ok_or(CodecError::Invalid("palette index out of range"))?;
+                out.extend([color.r, color.g, color.b].map(u16::from));
+                if let Some(alpha) = alpha {
+                    out.push(alpha.get(index).map_or(255, |&a| a as u16));
+                }
+            }
+        }
+        (PngColorType::Gray, Some(PngTransparency::Gray(key))) => {
+            for i in 0..count {
+                let gray = sample(i);
+                out.extend([gray * scale, if gray == *key { 0 } else { opaque }]);
+            }
+        }
+        (PngColorType::Rgb, Some(PngTransparency::Rgb(key))) => {
+            for i in 0..width {
+                let color = [sample(3 * i), sample(3 * i + 1), sample(3 * i + 2)];
+                out.extend(color);
+                out.push(if color == *key { 0 } else { opaque });
+            }
+        }
+        _ => out.extend((0..count).map(|i| sample(i) * scale)),
+    }
+    Ok(())
+}
+
+/// Unfilters the image data and hands each output row to `row`, top to bottom.
+fn decode_rows(info: &PngInfo, idat: &[u8], mut row: impl FnMut(&[u16])) -> Result<(), CodecError> {
+    let bpp = info.bits_per_pixel();
+    let passes = passes(info);
+    let len = passes
+        .iter()
+        .try_fold(0usize, |len, &(_, width, height)| {
+            let stride = width.checked_mul(bpp)?.div_ceil(8).checked_add(1)?;
+            len.checked_add(stride.checked_mul(height)?)
+        })
+        .ok_or(CodecError::Invalid("image is too large"))?;
+    let mut data = zlib::decompress(idat, len)?;
+    if data.len() < len {
+        return Err(CodecError::Truncated);
+    }
+    let left = bpp.div_ceil(8);
+    let (layout, _) = info.output();
+    let channels = layout.channels();
+    let mut samples = Vec::new();
+    // Interlaced passes are gathered into a full image before any row is complete.
+    let mut full = if info.interlaced {
+        let len = info
+            .width
+            .checked_mul(info.height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or(CodecError::Invalid("image is too large"))?;
+        vec![0; len]
+    } else {
+        Vec::new()
+    };
+    let mut rest = &mut data[..];
+    for ([x0, y0, dx, dy], width, height) in passes {
+        let stride = (width * bpp).div_ceil(8) + 1;
+        let mut prev: Option<&[u8]> = None;
+        for y in 0..height {
+            let (current, tail) = core::mem::take(&mut rest).split_at_mut(stride);
+            rest = tail;
+            let (filter, current) = current.split_first_mut().unwrap();
+            unfilter(*filter, current, prev, left)?;
+            expand(info, current, width, &mut samples)?;
+            if info.interlaced {
+                for (i, pixel) in samples.chunks_exact(channels).enumerate() {
+                    let start = ((y0 + y * dy) * info.width + x0 + i * dx) * channels;
+                    full[start..start + channels].copy_from_slice(pixel);
+                }
+            } else {
+                row(&samples);
+            }
+            prev = Some(current);
+        }
+    }
+    if info.interlaced {
+        full.chunks_exact(info.width * channels).for_each(row);
+    }
+    Ok(())
+}
+
+fn read(mut reader: impl Read) -> Result<(PngInfo, Vec<u8>), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    chunks(&data)
+}
+
+pub fn decode_with_info(reader: impl Read) -> Result<(PngInfo, DynamicImage), CodecError> {
+    let (info, idat) = read(reader)?;
+    let (layout, wide) = info.output();
+    // Grows with the rows, which only exist once the image data was found to be long enough.
+    let mut samples = Vec::new();
+    decode_rows(&info, &idat, |row| samples.extend_from_slice(row))?;
+    let image = from_samples(info.width, info.height, layout, wide, &samples);
+    Ok((info, image))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_info(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target` row by row, converting the pixels. `target` may be a region of a
+/// larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    let (info, idat) = read(reader)?;
+    if [info.width, info.height] != [target.width(), target.height()] {
+        return Err(SizeMismatch {
+            left: [info.width, info.height],
+            right: [target.width(), target.height()],
+        }
+        .into());
+    }
+    let (layout, wide) = info.output();
+    let maxval = if wide { 65535 } else { 255 };
+    let mut rows = target.iter_rows_mut();
+    decode_rows(&info, &idat, |samples| {
+        let row = rows.next().unwrap();
+        for (pixel, samples) in row.iter_mut().zip(samples.chunks_exact(layout.channels())) {
+            *pixel = from_samples_pixel(samples, layout, maxval);
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    /// Picks the filter per row whose output has the minimum sum of absolute differences.
+    Adaptive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PngOptions {
+    pub filter: PngFilter,
+    /// 0 stores the data uncompressed, 1 to 9 search progressively harder for matches.
+    pub compression: u8,
+    /// Written as `gAMA`, gamma times 100000.
+    pub gamma: Option<u32>,
+    pub physical: Option<PngPhysical>,
+    /// Written as `tEXt`. Both must be Latin-1, and keywords 1 to 79 bytes long.
+    pub text: Vec<(String, String)>,
+}
+
+impl PngOptions {
+    pub const fn new() -> Self {
+        Self {
+            filter: PngFilter::Adaptive,
+            compression: 6,
+            gamma: None,
+            physical: None,
+            text: Vec::new(),
+        }
+    }
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    out.extend((body.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend_from_slice(body);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+fn latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+/// Appends the filter type and `row` filtered with it.
+fn filter(kind: u8, row: &[u8], prev: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(kind);
+    for i in 0..row.len() {
+        let (left, corner) = if i >= bpp {
+            (row[i - bpp], prev[i - bpp])
+        } else {
+            (0, 0)
+        };
+        let predicted = match kind {
+            0 => 0,
+            1 => left,
+            2 => prev[i],
+            3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+            _ => paeth(left, prev[i], corner),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+pub fn encode<Source, P>(
+    mut writer: impl Write,
+    image: &Image<Source, P>,
+    options: &PngOptions,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 {
+        return Err(CodecError::Invalid("PNG images can not be empty"));
+    }
+    if width > i32::MAX as usize || height > i32::MAX as usize {
+        return Err(CodecError::Invalid("image size out of range"));
+    }
+    let layout = P::FORMAT.layout;
+    let wide = P::FORMAT.channel_type != ChannelType::U8;
+    let color_type = match layout {
+        ChannelLayout::Luma => PngColorType::Gray,
+        ChannelLayout::LumaA => PngColorType::GrayAlpha,
+        ChannelLayout::Rgb => PngColorType::Rgb,
+        ChannelLayout::Rgba => PngColorType::Rgba,
+    };
+    let (bit_depth, maxval) = if wide { (16, 65535) } else { (8, 255) };
+    let channels = layout.channels();
+    let bpp = channels * bit_depth as usize / 8;
+
+    let mut out = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    header.extend([bit_depth, color_type.code(), 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header);
+    if let Some(gamma) = options.gamma {
+        write_chunk(&mut out, b"gAMA", &gamma.to_be_bytes());
+    }
+    if let Some(physical) = options.physical {
+        let mut body = physical.x.to_be_bytes().to_vec();
+        body.extend(physical.y.to_be_bytes());
+        body.push(physical.meters as u8);
+        write_chunk(&mut out, b"pHYs", &body);
+    }
+    for (keyword, value) in &options.text {
+        let (Some(mut body), Some(value)) = (latin1(keyword), latin1(value)) else {
+            return Err(CodecError::Invalid("tEXt must be Latin-1"));
+        };
+        if !(1..=79).contains(&body.len()) || body.contains(&0) {
+            return Err(CodecError::Invalid(
+                "tEXt keywords must be 1 to 79 bytes without nulls",
+            ));
+        }
+        body.push(0);
+        body.extend(value);
+        write_chunk(&mut out, b"tEXt", &body);
+    }
+
+    let stride = width * bpp;
+    let mut filtered = Vec::with_capacity((stride + 1) * height);
+    let mut row = Vec::with_capacity(stride);
+    let mut prev = vec![0; stride];
+    let mut candidate = Vec::with_capacity(stride + 1);
+    for pixels in image.iter_rows() {
+        row.clear();
+        for &pixel in pixels {
+            let samples = to_samples(pixel, layout, maxval);
+            for &sample in &samples[..channels] {
+                if wide {
+                    row.extend(sample.to_be_bytes());
+                } else {
+                    row.push(sample as u8);
+                }
+            }
+        }
+        let kind = match options.filter {
+            PngFilter::None => 0,
+            PngFilter::Sub => 1,
+            PngFilter::Up => 2,
+            PngFilter::Average => 3,
+            PngFilter::Paeth => 4,
+            PngFilter::Adaptive => (0..5)
+                .min_by_key(|&kind| {
+                    candidate.clear();
+                    filter(kind, &row, &prev, bpp, &mut candidate);
+                    candidate[1..]
+                        .iter()
+                        .map(|&byte| (byte as i8).unsigned_abs() as u32)
+                        .sum::<u32>()
+                })
+                .unwrap(),
+        };
+        filter(kind, &row, &prev, bpp, &mut filtered);
+        core::mem::swap(&mut row, &mut prev);
+    }
+    let compressed = zlib::compress(&filtered, options.compression);
+    for part in compressed.chunks(1 << 20) {
+        write_chunk(&mut out, b"IDAT", part);
+    }
+    write_chunk(&mut out, b"IEND", &[]);
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+struct Encode<'a, W> {
+    writer: W,
+    options: &'a PngOptions,
+}
+
+impl<'a, W: Write> ImageVisitor for Encode<'a, W> {
+    type Output = Result<(), CodecError>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        encode(self.writer, image, self.options)
+    }
+}
+
+pub fn encode_dynamic(
+    writer: impl Write,
+    image: &DynamicImage,
+    options: &PngOptions,
+) -> Result<(), CodecError> {
+    image.visit(Encode { writer, options })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::zlib::adler32,
+        pixel::{Luma, LumaA, Rgba},
+    };
+
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+        out.extend((body.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend(kind);
+        out.extend(body);
+        let crc = crc32(&out[start..]);
+        out.extend(crc.to_be_bytes());
+    }
+
+    /// A zlib stream of stored blocks.
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01];
+        let mut blocks = data.chunks(u16::MAX as usize).peekable();
+        if blocks.peek().is_none() {
+            out.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            out.push(blocks.peek().is_none() as u8);
+            out.extend((block.len() as u16).to_le_bytes());
+            out.extend((!(block.len() as u16)).to_le_bytes());
+            out.extend(block);
+        }
+        out.extend(adler32(data).to_be_bytes());
+        out
+    }
+
+    /// A file with the given header fields, extra chunks before the image data, and filtered
+    /// scanlines.
+    fn file(
+        [width, height]: [u32; 2],
+        bit_depth: u8,
+        color_type: u8,
+        interlaced: bool,
+        extra: &[(&[u8; 4], &[u8])],
+        scanlines: &[u8],
+    ) -> Vec<u8> {
+        let mut out = SIGNATURE.to_vec();
+        let mut ihdr = width.to_be_bytes().to_vec();
+        ihdr.extend(height.to_be_bytes());
+        ihdr.extend([bit_depth, color_type, 0, 0, interlaced as u8]);
+        chunk(&mut out, b"IHDR", &ihdr);
+        for (kind, body) in extra {
+            chunk(&mut out, kind, body);
+        }
+        chunk(&mut out, b"IDAT", &stored(scanlines));
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    /// Rows of packed samples prefixed with filter type 0.
+    fn unfiltered(rows: &[&[u8]]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| [&[0][..], row].concat())
+            .collect()
+    }
+
+    fn luma(data: &[u8]) -> Vec<u8> {
+        let image = decode(data).unwrap();
+        let image = image.as_image::<Luma<u8>>().unwrap();
+        image.iter().map(|(_, pixel)| pixel.l).collect()
+    }
+
+    #[test]
+    fn oversized_header_is_an_error() {
+        for interlaced in [false, true] {
+            let data = file([0x7fff_ffff; 2], 8, 2, interlaced, &[], &[0; 16]);
+            assert!(matches!(
+                decode(data.as_slice()),
+                Err(CodecError::Truncated | CodecError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn filters() {
+        // RGB rows, each filtered with a different type, against the row above.
+        let rows: Vec<Vec<u8>> = (0..5u8)
+            .map(|y| {
+                (0..12u8)
+                    .map(|i| i.wrapping_mul(37) ^ y.wrapping_mul(91))
+                    .collect()
+            })
+            .collect();
+        let mut scanlines = Vec::new();
+        let zero = vec![0; 12];
+        for (y, row) in rows.iter().enumerate() {
+            let prev = if y == 0 { &zero } else { &rows[y - 1] };
+            scanlines.push(y as u8);
+            for i in 0..row.len() {
+                let (left, corner) = if i >= 3 {
+                    (row[i - 3], prev[i - 3])
+                } else {
+                    (0, 0)
+                };
+                let predicted = match y {
+                    0 => 0,
+                    1 => left,
+                    2 => prev[i],
+                    3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+                    _ => paeth(left, prev[i], corner),
+                };
+                scanlines.push(row[i].wrapping_sub(predicted));
+            }
+        }
+        let data = file([4, 5], 8, 2, false, &[], &scanlines);
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<Rgb<u8>>().unwrap();
+        let bytes: Vec<u8> = image.iter().flat_map(|(_, p)| [p.r, p.g, p.b]).collect();
+        assert_eq!(bytes, rows.concat());
+
+        scanlines[0] = 5;
+        assert!(matches!(
+            decode(file([4, 5], 8, 2, false, &[], &scanlines).as_slice()),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn low_bit_depths() {
+        let cases: [(u8, &[u8], &[u8]); 3] = [
+            (
+                1,
+                &[0b1011_0000, 0b0100_0000],
+                &[255, 0, 255, 255, 0, 0, 0, 0, 0, 255],
+            ),
+            (2, &[0b0001_1011, 0b1100_0000], &[0, 85, 170, 255, 255]),
+            (4, &[0x0f, 0x85], &[0, 255, 136, 85]),
+        ];
+        for (depth, row, expected) in cases {
+            let width = expected.len() as u32;
+            let data = file([width, 1], depth, 0, false, &[], &unfiltered(&[row]));
+            assert_eq!(luma(&data), expected, "{depth} bits");
+        }
+        // Indices are not scaled.
+        let palette: Vec<u8> = (0..4).flat_map(|i| [i * 60, 0, 0]).collect();
+        let data = file(
+            [5, 1],
+            2,
+            3,
+            false,
+            &[(b"PLTE", &palette)],
+            &unfiltered(&[&[0b0001_1011, 0b1100_0000]]),
+        );
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<Rgb<u8>>().unwrap();
+        let reds: Vec<u8> = image.iter().map(|(_, p)| p.r).collect();
+        assert_eq!(reds, [0, 60, 120, 180, 180]);
+    }
+
+    #[test]
+    fn transparency() {
+        let palette = [10, 0, 0, 20, 0, 0, 30, 0, 0];
+        let data = file(
+            [3, 1],
+            8,
+            3,
+            false,
+            &[(b"PLTE", &palette), (b"tRNS", &[0, 128])],
+            &unfiltered(&[&[0, 1, 2]]),
+        );
+        let (info, image) = decode_with_info(data.as_slice()).unwrap();
+        assert_eq!(
+            info.transparency,
+            Some(PngTransparency::Palette(vec![0, 128]))
+        );
+        let image = image.as_image::<Rgba<u8>>().unwrap();
+        let pixels: Vec<[u8; 2]> = image.iter().map(|(_, p)| [p.r, p.a]).collect();
+        // Entries past the end of tRNS are opaque.
+        assert_eq!(pixels, [[10, 0], [20, 128], [30, 255]]);
+
+        let data = file(
+            [3, 1],
+            8,
+            0,
+            false,
+            &[(b"tRNS", &[0, 7])],
+            &unfiltered(&[&[7, 8, 7]]),
+        );
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<LumaA<u8>>().unwrap();
+        let alpha: Vec<u8> = image.iter().map(|(_, p)| p.a).collect();
+        assert_eq!(alpha, [0, 255, 0]);
+    }
+
+    #[test]
+    fn sixteen_bit() {
+        let row = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xff, 0xff, 0, 0, 0, 1];
+        let data = file([2, 1], 16, 2, false, &[], &unfiltered(&[&row]));
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<Rgb<u16>>().unwrap();
+        let pixels: Vec<[u16; 3]> = image.iter().map(|(_, p)| [p.r, p.g, p.b]).collect();
+        assert_eq!(pixels, [[0x1234, 0x5678, 0x9abc], [0xffff, 0, 1]]);
+
+        // A 16 bit gray key is compared before scaling.
+        let data = file(
+            [2, 1],
+            16,
+            0,
+            false,
+            &[(b"tRNS", &[0x01, 0x02])],
+            &unfiltered(&[&[0x01, 0x02, 0x01, 0x03]]),
+        );
+        let image = decode(data.as_slice()).unwrap();
+        let image = image.as_image::<LumaA<u16>>().unwrap();
+        let pixels: Vec<[u16; 2]> = image.iter().map(|(_, p)| [p.l, p.a]).collect();
+        assert_eq!(pixels, [[0x0102, 0], [0x0103, 0xffff]]);
+    }
+
+    #[test]
+    fn adam7() {
+        for [width, height] in [[9, 7], [1, 1], [3, 2]] {
+            let value = |x: usize, y: usize| (x + 16 * y) as u8;
+            let mut scanlines = Vec::new();
+            for [x0, y0, dx, dy] in ADAM7 {
+                for y in (y0..height).step_by(dy) {
+                    if x0 < width {
+                        scanlines.push(0);
+                        scanlines.extend((x0..width).step_by(dx).map(|x| value(x, y)));
+                    }
+                }
+            }
+            let size = [width as u32, height as u32];
+            let data = file(size, 8, 0, true, &[], &scanlines);
+            let expected: Vec<u8> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| value(x, y)))
+                .collect();
+            assert_eq!(luma(&data), expected, "{width}x{height}");
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut data = file(
+            [1, 1],
+            8,
+            0,
+            false,
+            &[(b"gAMA", &[0, 0, 0xb1, 0x8f])],
+            &[0, 0],
+        );
+        assert!(decode(data.as_slice()).is_ok());
+        // The last byte of the gAMA body.
+        data[8 + 25 + 11] ^= 1;
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::ChecksumMismatch { .. })
+        ));
+    }
+
+    fn gradient<P: DynamicPixel>(width: usize, height: usize) -> Image<Box<[P]>, P> {
+        let pixels = (0..width * height).map(|i| {
+            let [x, y] = [i % width, i / width];
+            let samples =
+                [x * 40 + y, y * 70, 255 - x * y, x * 13 + 90].map(|s| (s * 2741 % 65536) as u16);
+            from_samples_pixel(&samples, P::FORMAT.layout, 65535)
+        });
+        Image::from_source(width, height, pixels.collect())
+            .ok()
+            .unwrap()
+    }
+
+    fn round_trip<P: DynamicPixel + PartialEq + core::fmt::Debug>(options: &PngOptions) {
+        let image = gradient::<P>(7, 5);
+        let mut png = Vec::new();
+        encode(&mut png, &image, options).unwrap();
+        let decoded = decode(png.as_slice()).unwrap();
+        assert_eq!(decoded.as_image::<P>(), Some(&image), "{options:?}");
+    }
+
+    #[test]
+    fn filters_and_levels_round_trip() {
+        let filters = [
+            PngFilter::None,
+            PngFilter::Sub,
+            PngFilter::Up,
+            PngFilter::Average,
+            PngFilter::Paeth,
+            PngFilter::Adaptive,
+        ];
+        for filter in filters {
+            for compression in [0, 1, 6, 9] {
+                let options = PngOptions {
+                    filter,
+                    compression,
+                    ..PngOptions::new()
+                };
+                round_trip::<Rgb<u8>>(&options);
+                round_trip::<Rgba<u16>>(&options);
+            }
+        }
+    }
+
+    #[test]
+    fn layouts_round_trip() {
+        let options = PngOptions::new();
+        round_trip::<Luma<u8>>(&options);
+        round_trip::<Luma<u16>>(&options);
+        round_trip::<LumaA<u8>>(&options);
+        round_trip::<LumaA<u16>>(&options);
+        round_trip::<Rgb<u16>>(&options);
+        round_trip::<Rgba<u8>>(&options);
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let options = PngOptions {
+            gamma: Some(45455),
+            physical: Some(PngPhysical {
+                x: 2835,
+                y: 3780,
+                meters: true,
+            }),
+            text: vec![
+                ("Title".into(), "Caf\u{e9}".into()),
+                ("Author".into(), String::new()),
+            ],
+            ..PngOptions::new()
+        };
+        let mut png = Vec::new();
+        encode_dynamic(
+            &mut png,
+            &DynamicImage::from(gradient::<Rgb<u8>>(2, 2)),
+            &options,
+        )
+        .unwrap();
+        let (info, _) = decode_with_info(png.as_slice()).unwrap();
+        assert_eq!(info.gamma, options.gamma);
+        assert_eq!(info.physical, options.physical);
+        assert_eq!(info.text, options.text);
+    }
+
+    #[test]
+    fn invalid_options_and_images_are_rejected() {
+        let image = gradient::<Luma<u8>>(1, 1);
+        for (keyword, value) in [
+            ("", "x"),
+            (&*"k".repeat(80), "x"),
+            ("a\0b", "x"),
+            ("k", "\u{20ac}"),
+        ] {
+            let options = PngOptions {
+                text: vec![(keyword.into(), value.into())],
+                ..PngOptions::new()
+            };
+            let result = encode(Vec::new(), &image, &options);
+            assert!(matches!(result, Err(CodecError::Invalid(_))), "{keyword:?}");
+        }
+        let empty = gradient::<Luma<u8>>(0, 3);
+        assert!(matches!(
+            encode(Vec::new(), &empty, &PngOptions::new()),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+}
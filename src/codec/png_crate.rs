@@ -0,0 +1,160 @@
+use crate::{
+    codec::{from_samples_pixel, to_samples},
+    dynamic::{ChannelLayout, ChannelType, DynamicPixel},
+    error::CodecError,
+    pixel::Rgba,
+    Image,
+};
+use ::png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use std::io::{Read, Write};
+
+impl<P: DynamicPixel> Image<Box<[P]>, P> {
+    /// Decodes a PNG with the `png` crate, converting the pixels to `P`. Palettes, transparency
+    /// and low bit depths are expanded first.
+    pub fn load_png(reader: impl Read) -> Result<Self, CodecError> {
+        let mut decoder = Decoder::new(reader);
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let layout = match reader.output_color_type() {
+            (ColorType::Grayscale, _) => ChannelLayout::Luma,
+            (ColorType::GrayscaleAlpha, _) => ChannelLayout::LumaA,
+            (ColorType::Rgb, _) => ChannelLayout::Rgb,
+            (ColorType::Rgba, _) => ChannelLayout::Rgba,
+            (ColorType::Indexed, _) => return Err(CodecError::Unsupported("unexpanded palette")),
+        };
+        let wide = match reader.output_color_type().1 {
+            BitDepth::Eight => false,
+            BitDepth::Sixteen => true,
+            _ => return Err(CodecError::Unsupported("unexpanded bit depth")),
+        };
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf)?;
+        let (width, height) = (frame.width as usize, frame.height as usize);
+
+        let blank = P::from_rgba(Rgba {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 0.0,
+        });
+        let mut image = Self::filled(width, height, blank);
+        let (maxval, bytes) = if wide { (65535, 2) } else { (255, 1) };
+        let mut samples = Vec::with_capacity(width * layout.channels());
+        for (row, line) in image.iter_rows_mut().zip(buf.chunks(frame.line_size)) {
+            samples.clear();
+            samples.extend(line.chunks_exact(bytes).map(|sample| match *sample {
+                [high, low] => u16::from_be_bytes([high, low]),
+                [sample] => sample as u16,
+                _ => unreachable!(),
+            }));
+            for (pixel, samples) in row.iter_mut().zip(samples.chunks_exact(layout.channels())) {
+                *pixel = from_samples_pixel(samples, layout, maxval);
+            }
+        }
+        Ok(image)
+    }
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    /// Encodes with the `png` crate, using 16 bits for channels wider than a byte.
+    pub fn save_png(&self, writer: impl Write) -> Result<(), CodecError> {
+        let (Ok(width), Ok(height)) = (u32::try_from(self.width()), u32::try_from(self.height()))
+        else {
+            return Err(CodecError::Invalid("image size out of range"));
+        };
+        let layout = P::FORMAT.layout;
+        let wide = P::FORMAT.channel_type != ChannelType::U8;
+        let mut encoder = Encoder::new(writer, width, height);
+        encoder.set_color(match layout {
+            ChannelLayout::Luma => ColorType::Grayscale,
+            ChannelLayout::LumaA => ColorType::GrayscaleAlpha,
+            ChannelLayout::Rgb => ColorType::Rgb,
+            ChannelLayout::Rgba => ColorType::Rgba,
+        });
+        encoder.set_depth(if wide {
+            BitDepth::Sixteen
+        } else {
+            BitDepth::Eight
+        });
+        let mut writer = encoder.write_header()?;
+        let mut stream = writer.stream_writer()?;
+        let maxval = if wide { 65535 } else { 255 };
+        let mut line = Vec::new();
+        // Rows are written one at a time, so strided images need no copy.
+        for row in self.iter_rows() {
+            line.clear();
+            for &pixel in row {
+                for &sample in &to_samples(pixel, layout, maxval)[..layout.channels()] {
+                    if wide {
+                        line.extend(sample.to_be_bytes());
+                    } else {
+                        line.push(sample as u8);
+                    }
+                }
+            }
+            stream.write_all(&line)?;
+        }
+        stream.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::png,
+        pixel::{Luma, LumaA, Rgb},
+    };
+
+    fn round_trip<P>(image: &Image<Box<[P]>, P>)
+    where
+        P: DynamicPixel + PartialEq + core::fmt::Debug,
+    {
+        let mut data = Vec::new();
+        image.save_png(&mut data).unwrap();
+        assert_eq!(
+            &Image::<Box<[P]>, P>::load_png(data.as_slice()).unwrap(),
+            image
+        );
+        // The built-in decoder reads the same file.
+        assert_eq!(
+            png::decode(data.as_slice()).unwrap().as_image::<P>(),
+            Some(image)
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut rgb = Image::filled(7, 5, Rgb { r: 0u8, g: 0, b: 0 });
+        for ([x, y], pixel) in rgb.iter_mut() {
+            *pixel = Rgb::from([x as u8 * 30, y as u8 * 50, 200]);
+        }
+        round_trip(&rgb);
+        round_trip(&rgb.map(|p| Rgba::from([p.r as u16 * 257, p.g as u16 * 3, 1, 0xfffe])));
+        round_trip(&rgb.map(|p| LumaA { l: p.r, a: p.g }));
+
+        // A strided region is written row by row.
+        let region = rgb.region([2, 1]..[6, 4]).ok().unwrap();
+        let mut data = Vec::new();
+        region.save_png(&mut data).unwrap();
+        let loaded = Image::<Box<[Rgb<u8>]>, _>::load_png(data.as_slice()).unwrap();
+        assert_eq!(loaded, region);
+    }
+
+    #[test]
+    fn converts_on_load() {
+        let gray = Image::filled(3, 2, Luma { l: 0x8000u16 });
+        let mut data = Vec::new();
+        gray.save_png(&mut data).unwrap();
+        let rgba = Image::<Box<[Rgba<u8>]>, _>::load_png(data.as_slice()).unwrap();
+        assert!(rgba
+            .iter()
+            .all(|(_, &p)| p == Rgba::from([128, 128, 128, 255])));
+        assert!(Image::<Box<[Rgb<u8>]>, _>::load_png(&data[..data.len() - 20]).is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    codec::{copy_into, to_samples},
+    dynamic::{ChannelLayout, DynamicImage, DynamicPixel, ImageVisitor},
+    error::CodecError,
+    pixel::{Rgb, Rgba},
+    Image,
+};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"qoif";
+const END: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QoiColorspace {
+    /// sRGB color with linear alpha.
+    Srgb,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QoiHeader {
+    pub width: usize,
+    pub height: usize,
+    pub alpha: bool,
+    pub colorspace: QoiColorspace,
+}
+
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+/// Decodes the chunks after the header into a new image, checking the end marker.
+fn decode_pixels<P: Copy>(
+    data: &[u8],
+    header: &QoiHeader,
+    pixel: impl Fn([u8; 4]) -> P,
+) -> Result<Image<Box<[P]>, P>, CodecError> {
+    let mut image = Image::filled(header.width, header.height, pixel([0, 0, 0, 255]));
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255];
+    let mut run = 0;
+    let mut pos = 0;
+    let mut next = || {
+        let byte = *data.get(pos).ok_or(CodecError::Truncated)?;
+        pos += 1;
+        Ok::<_, CodecError>(byte)
+    };
+    for row in image.iter_rows_mut() {
+        for out in row {
+            if run > 0 {
+                run -= 1;
+                *out = pixel(px);
+                continue;
+            }
+            match next()? {
+                OP_RGB => px = [next()?, next()?, next()?, px[3]],
+                OP_RGBA => px = [next()?, next()?, next()?, next()?],
+                byte => match byte & 0xc0 {
+                    OP_INDEX => px = index[byte as usize],
+                    OP_DIFF => {
+                        for (i, shift) in [4, 2, 0].into_iter().enumerate() {
+                            px[i] = px[i].wrapping_add((byte >> shift & 3).wrapping_sub(2));
+                        }
+                    }
+                    OP_LUMA => {
+                        let dg = (byte & 0x3f).wrapping_sub(32);
+                        let second = next()?;
+                        px[0] = px[0].wrapping_add(dg.wrapping_add(second >> 4).wrapping_sub(8));
+                        px[1] = px[1].wrapping_add(dg);
+                        px[2] = px[2].wrapping_add(dg.wrapping_add(second & 0x0f).wrapping_sub(8));
+                    }
+                    _ => run = byte & 0x3f,
+                },
+            }
+            index[hash(px)] = px;
+            *out = pixel(px);
+        }
+    }
+    if run > 0 {
+        return Err(CodecError::Invalid("run past the end of the image"));
+    }
+    match data.get(pos..) {
+        Some(rest) if rest == END => Ok(image),
+        Some(rest) if rest.len() < END.len() && END.starts_with(rest) => Err(CodecError::Truncated),
+        Some(rest) if rest.starts_with(&END) => {
+            Err(CodecError::Invalid("data after the end marker"))
+        }
+        _ => Err(CodecError::Invalid("missing end marker")),
+    }
+}
+
+pub fn decode_with_header(mut reader: impl Read) -> Result<(QoiHeader, DynamicImage), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let bytes = data.get(..14).ok_or(CodecError::Truncated)?;
+    if &bytes[..4] != MAGIC {
+        return Err(CodecError::Invalid("not a QOI file"));
+    }
+    let width = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    let alpha = match bytes[12] {
+        3 => false,
+        4 => true,
+        _ => return Err(CodecError::Invalid("channels must be 3 or 4")),
+    };
+    let colorspace = match bytes[13] {
+        0 => QoiColorspace::Srgb,
+        1 => QoiColorspace::Linear,
+        _ => return Err(CodecError::Invalid("invalid colorspace")),
+    };
+    // A run byte covers at most 62 pixels, so anything larger can not be complete.
+    if width
+        .checked_mul(height)
+        .filter(|&pixels| pixels <= data.len() * 62)
+        .is_none()
+    {
+        return Err(CodecError::Truncated);
+    }
+    let header = QoiHeader {
+        width,
+        height,
+        alpha,
+        colorspace,
+    };
+    let chunks = &data[14..];
+    let image = if alpha {
+        decode_pixels(chunks, &header, |[r, g, b, a]| Rgba { r, g, b, a })?.into()
+    } else {
+        decode_pixels(chunks, &header, |[r, g, b, _]| Rgb { r, g, b })?.into()
+    };
+    Ok((header, image))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target`, converting the pixels. `target` may be a region of a larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    Ok(copy_into(&decode(reader)?, target)?)
+}
+
+/// Encodes row by row, writing each row as it is finished. Pixels without alpha are written
+/// with 3 channels.
+pub fn encode<Source, P>(
+    mut writer: impl Write,
+    image: &Image<Source, P>,
+    colorspace: QoiColorspace,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    let (Ok(width), Ok(height)) = (u32::try_from(image.width()), u32::try_from(image.height()))
+    else {
+        return Err(CodecError::Invalid("image size out of range"));
+    };
+    let mut out = MAGIC.to_vec();
+    out.extend(width.to_be_bytes());
+    out.extend(height.to_be_bytes());
+    out.push(if P::FORMAT.layout.has_alpha() { 4 } else { 3 });
+    out.push(match colorspace {
+        QoiColorspace::Srgb => 0,
+        QoiColorspace::Linear => 1,
+    });
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0, 0, 0, 255];
+    let mut run = 0u8;
+    for row in image.iter_rows() {
+        for &pixel in row {
+            let px = to_samples(pixel, ChannelLayout::Rgba, 255).map(|sample| sample as u8);
+            if px == prev {
+                run += 1;
+                if run == 62 {
+                    out.push(OP_RUN | (run - 1));
+                    run = 0;
+                }
+                continue;
+            }
+            if run > 0 {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            let slot = hash(px);
+            if index[slot] == px {
+                out.push(OP_INDEX | slot as u8);
+            } else if px[3] == prev[3] {
+                index[slot] = px;
+                let [dr, dg, db] = [0, 1, 2].map(|i| px[i].wrapping_sub(prev[i]) as i8);
+                let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                if [dr, dg, db].iter().all(|d| (-2..=1).contains(d)) {
+                    out.push(OP_DIFF | ((dr + 2) << 4 | (dg + 2) << 2 | (db + 2)) as u8);
+                } else if (-32..=31).contains(&dg)
+                    && (-8..=7).contains(&dr_dg)
+                    && (-8..=7).contains(&db_dg)
+                {
+                    out.push(OP_LUMA | (dg + 32) as u8);
+                    out.push(((dr_dg + 8) << 4 | (db_dg + 8)) as u8);
+                } else {
+                    out.extend([OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                index[slot] = px;
+                out.extend([OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+            prev = px;
+        }
+        writer.write_all(&out)?;
+        out.clear();
+    }
+    if run > 0 {
+        out.push(OP_RUN | (run - 1));
+    }
+    out.extend(END);
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+struct Encode<W> {
+    writer: W,
+    colorspace: QoiColorspace,
+}
+
+impl<W: Write> ImageVisitor for Encode<W> {
+    type Output = Result<(), CodecError>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        encode(self.writer, image, self.colorspace)
+    }
+}
+
+pub fn encode_dynamic(
+    writer: impl Write,
+    image: &DynamicImage,
+    colorspace: QoiColorspace,
+) -> Result<(), CodecError> {
+    image.visit(Encode { writer, colorspace })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A run longer than one chunk holds, followed by small and large changes in color and
+    /// alpha.
+    fn sample() -> Image<Box<[Rgba<u8>]>, Rgba<u8>> {
+        let mut image = Image::filled(
+            40,
+            6,
+            Rgba {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            },
+        );
+        for ([x, y], pixel) in image.iter_mut() {
+            let [x, y] = [x as u8, y as u8];
+            *pixel = match y {
+                0..3 => Rgba {
+                    r: 10,
+                    g: 20,
+                    b: 30,
+                    a: 255,
+                },
+                _ => Rgba {
+                    r: x * 3,
+                    g: x * 3 + y,
+                    b: x.wrapping_mul(37) ^ y,
+                    a: if x % 5 == 0 { 128 } else { 255 },
+                },
+            };
+        }
+        image
+    }
+
+    fn encoded<Source: AsRef<[P]>, P: DynamicPixel>(image: &Image<Source, P>) -> Vec<u8> {
+        let mut data = Vec::new();
+        encode(&mut data, image, QoiColorspace::Srgb).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip_region() {
+        let image = sample();
+        let region = image.region([2, 1]..[38, 5]).unwrap();
+        let (header, decoded) = decode_with_header(encoded(&region).as_slice()).unwrap();
+        assert_eq!([header.width, header.height], [36, 4]);
+        assert!(header.alpha);
+        let decoded = decoded.as_image::<Rgba<u8>>().unwrap();
+        assert!(decoded.iter().eq(region.iter()));
+
+        let rgb = image.map(|&Rgba { r, g, b, .. }| Rgb { r, g, b });
+        let region = rgb.region([2, 1]..[38, 5]).unwrap();
+        let data = encoded(&region);
+        let (header, decoded) = decode_with_header(data.as_slice()).unwrap();
+        assert!(!header.alpha);
+        let decoded = decoded.as_image::<Rgb<u8>>().unwrap();
+        assert!(decoded.iter().eq(region.iter()));
+
+        let mut target = Image::filled(41, 7, Rgb { r: 1, g: 1, b: 1 });
+        let mut inner = target.region_mut([4, 2]..[40, 6]).unwrap();
+        decode_into(data.as_slice(), &mut inner).unwrap();
+        assert_eq!(target[[4, 2]], rgb[[2, 1]]);
+        assert_eq!(target[[39, 5]], rgb[[37, 4]]);
+        assert_eq!(target[[3, 2]], Rgb { r: 1, g: 1, b: 1 });
+    }
+
+    #[test]
+    fn truncated() {
+        let data = encoded(&sample());
+        for len in [0, 10, 14, data.len() / 2, data.len() - 8, data.len() - 1] {
+            assert!(
+                matches!(decode(&data[..len]), Err(CodecError::Truncated)),
+                "{len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut data = encoded(&sample());
+        data.push(0);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn run_past_the_end() {
+        let mut data = MAGIC.to_vec();
+        data.extend(3u32.to_be_bytes());
+        data.extend(1u32.to_be_bytes());
+        data.extend([3, 0]);
+        // Two runs of two pixels for a three pixel image.
+        data.extend([OP_RUN | 1, OP_RUN | 1]);
+        data.extend(END);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Invalid("run past the end of the image"))
+        ));
+    }
+}
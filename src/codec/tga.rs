@@ -0,0 +1,503 @@
+use crate::{
+    alpha::PremultipliedRgba,
+    codec::{copy_into, from_samples, to_samples},
+    dynamic::{AlphaMode, ChannelLayout, DynamicImage, DynamicPixel, ImageVisitor},
+    error::CodecError,
+    Image,
+};
+use std::io::{Read, Write};
+
+const SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+/// Size of the TGA 2.0 extension area, whose last byte describes the alpha channel.
+const EXTENSION_SIZE: usize = 495;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TgaImageType {
+    ColorMapped,
+    TrueColor,
+    Gray,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TgaHeader {
+    pub image_type: TgaImageType,
+    pub rle: bool,
+    pub width: usize,
+    pub height: usize,
+    pub pixel_depth: u8,
+    /// Alpha bits per pixel, from the image descriptor.
+    pub alpha_bits: u8,
+    /// Rows are stored top to bottom instead of the usual bottom to top.
+    pub top_down: bool,
+    pub right_to_left: bool,
+    pub id: Vec<u8>,
+    /// Set when the file ends with a TGA 2.0 footer.
+    pub footer: bool,
+    /// From the extension area if there is one, otherwise from the alpha bits.
+    pub alpha: AlphaMode,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+/// Converts a stored pixel of `depth` bits to RGBA, or to gray and alpha.
+fn color(bytes: &[u8], depth: u8, gray: bool) -> [u8; 4] {
+    match (depth, gray) {
+        (8, true) => [bytes[0], 255, 0, 0],
+        (16, true) => [bytes[0], bytes[1], 0, 0],
+        (15 | 16, _) => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let five = |shift: u16| {
+                let v = (value >> shift & 0x1f) as u8;
+                v << 3 | v >> 2
+            };
+            [
+                five(10),
+                five(5),
+                five(0),
+                if value >> 15 == 1 { 255 } else { 0 },
+            ]
+        }
+        (24, _) => [bytes[2], bytes[1], bytes[0], 255],
+        _ => [bytes[2], bytes[1], bytes[0], bytes[3]],
+    }
+}
+
+/// Reads `count` pixels of `size` bytes starting at `pos`, expanding RLE packets.
+fn pixels(
+    data: &[u8],
+    mut pos: usize,
+    count: usize,
+    size: usize,
+    rle: bool,
+) -> Result<Vec<u8>, CodecError> {
+    let len = count.checked_mul(size).ok_or(CodecError::Truncated)?;
+    if !rle {
+        return data
+            .get(pos..)
+            .and_then(|rest| rest.get(..len))
+            .map(<[u8]>::to_vec)
+            .ok_or(CodecError::Truncated);
+    }
+    let mut out = Vec::with_capacity(len.min(data.len() * 128));
+    while out.len() < len {
+        let packet = *data.get(pos).ok_or(CodecError::Truncated)?;
+        let run = (packet & 0x7f) as usize + 1;
+        pos += 1;
+        let bytes = if packet & 0x80 != 0 { size } else { run * size };
+        let values = data.get(pos..pos + bytes).ok_or(CodecError::Truncated)?;
+        pos += bytes;
+        // Packets may run past the end of a row, but not past the image.
+        let take = (run * size).min(len - out.len());
+        if packet & 0x80 != 0 {
+            out.extend(values.iter().cycle().take(take));
+        } else {
+            out.extend_from_slice(&values[..take]);
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode_with_header(mut reader: impl Read) -> Result<(TgaHeader, DynamicImage), CodecError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() < 18 {
+        return Err(CodecError::Truncated);
+    }
+    let (image_type, rle) = match data[2] {
+        1 => (TgaImageType::ColorMapped, false),
+        2 => (TgaImageType::TrueColor, false),
+        3 => (TgaImageType::Gray, false),
+        9 => (TgaImageType::ColorMapped, true),
+        10 => (TgaImageType::TrueColor, true),
+        11 => (TgaImageType::Gray, true),
+        0 => return Err(CodecError::Invalid("TGA file without image data")),
+        _ => return Err(CodecError::Unsupported("TGA image type")),
+    };
+    let [map_type, map_first, map_len, map_depth] = [
+        data[1] as usize,
+        u16_at(&data, 3) as usize,
+        u16_at(&data, 5) as usize,
+        data[7] as usize,
+    ];
+    let (width, height) = (u16_at(&data, 12) as usize, u16_at(&data, 14) as usize);
+    let depth = data[16];
+    let descriptor = data[17];
+    let depth_ok = match image_type {
+        TgaImageType::ColorMapped => map_type == 1 && matches!(depth, 8 | 16),
+        TgaImageType::TrueColor => matches!(depth, 15 | 16 | 24 | 32),
+        TgaImageType::Gray => matches!(depth, 8 | 16),
+    };
+    if !depth_ok || map_type > 1 {
+        return Err(CodecError::Invalid(
+            "invalid pixel depth for the image type",
+        ));
+    }
+    let mut pos = 18 + data[0] as usize;
+    let id = data.get(18..pos).ok_or(CodecError::Truncated)?.to_vec();
+
+    let mut color_map = Vec::new();
+    if map_type == 1 {
+        if !matches!(map_depth, 15 | 16 | 24 | 32) {
+            return Err(CodecError::Invalid("invalid color map entry size"));
+        }
+        let size = map_depth.div_ceil(8);
+        let entries = data
+            .get(pos..pos + map_len * size)
+            .ok_or(CodecError::Truncated)?;
+        color_map = entries
+            .chunks_exact(size)
+            .map(|entry| color(entry, map_depth as u8, false))
+            .collect();
+        pos += map_len * size;
+    }
+
+    let footer = data.ends_with(SIGNATURE) && data.len() >= 26;
+    let attributes = if footer {
+        let offset = data.len() - 26;
+        let extension = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        data.get(extension + EXTENSION_SIZE - 1)
+            .filter(|_| extension != 0)
+            .copied()
+    } else {
+        None
+    };
+    let alpha_bits = descriptor & 0x0f;
+    let has_alpha = match image_type {
+        TgaImageType::ColorMapped => map_depth == 32 || (map_depth == 16 && alpha_bits != 0),
+        TgaImageType::TrueColor => alpha_bits != 0 && matches!(depth, 16 | 32),
+        TgaImageType::Gray => depth == 16,
+    };
+    let alpha = match attributes {
+        Some(0..=2) => AlphaMode::None,
+        Some(3) => AlphaMode::Straight,
+        Some(4) => AlphaMode::Premultiplied,
+        _ if has_alpha => AlphaMode::Straight,
+        _ => AlphaMode::None,
+    };
+    let header = TgaHeader {
+        image_type,
+        rle,
+        width,
+        height,
+        pixel_depth: depth,
+        alpha_bits,
+        top_down: descriptor & 0x20 != 0,
+        right_to_left: descriptor & 0x10 != 0,
+        id,
+        footer,
+        alpha,
+    };
+
+    let gray = image_type == TgaImageType::Gray;
+    let layout = match (gray, alpha) {
+        (true, AlphaMode::Straight) => ChannelLayout::LumaA,
+        (true, AlphaMode::None) => ChannelLayout::Luma,
+        (false, AlphaMode::None) => ChannelLayout::Rgb,
+        _ => ChannelLayout::Rgba,
+    };
+    let channels = layout.channels();
+    let size = depth.div_ceil(8) as usize;
+    let raw = pixels(&data, pos, width * height, size, rle)?;
+    let mut samples = vec![0u16; width * height * channels];
+    for (i, pixel) in raw.chunks_exact(size).enumerate() {
+        let rgba = if image_type == TgaImageType::ColorMapped {
+            let index = if size == 2 {
+                u16_at(pixel, 0) as usize
+            } else {
+                pixel[0] as usize
+            };
+            *index
+                .checked_sub(map_first)
+                .and_then(|index| color_map.get(index))
+                .ok_or(CodecError::Invalid("color map index out of range"))?
+        } else {
+            color(pixel, depth, gray)
+        };
+        let rgba = match (gray, layout) {
+            (true, ChannelLayout::Rgba) => [rgba[0], rgba[0], rgba[0], rgba[1]],
+            _ => rgba,
+        };
+        let (row, column) = (i / width, i % width);
+        let y = if header.top_down {
+            row
+        } else {
+            height - 1 - row
+        };
+        let x = if header.right_to_left {
+            width - 1 - column
+        } else {
+            column
+        };
+        let start = (y * width + x) * channels;
+        for (sample, &value) in samples[start..start + channels].iter_mut().zip(&rgba) {
+            *sample = value as u16;
+        }
+    }
+    let image = from_samples(width, height, layout, false, &samples);
+    let image = match (alpha, image) {
+        (AlphaMode::Premultiplied, DynamicImage::Rgba8(image)) => image
+            .map(|p| PremultipliedRgba {
+                r: p.r,
+                g: p.g,
+                b: p.b,
+                a: p.a,
+            })
+            .into(),
+        (_, image) => image,
+    };
+    Ok((header, image))
+}
+
+pub fn decode(reader: impl Read) -> Result<DynamicImage, CodecError> {
+    decode_with_header(reader).map(|(_, image)| image)
+}
+
+/// Decodes into `target`, converting the pixels. `target` may be a region of a larger image.
+pub fn decode_into<Source, P>(
+    reader: impl Read,
+    target: &mut Image<Source, P>,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: DynamicPixel,
+{
+    Ok(copy_into(&decode(reader)?, target)?)
+}
+
+/// Appends `row` of `size` byte pixels as RLE packets, which never cross rows.
+fn rle_row(row: &[u8], size: usize, out: &mut Vec<u8>) {
+    let pixels: Vec<&[u8]> = row.chunks_exact(size).collect();
+    let mut i = 0;
+    while i < pixels.len() {
+        let run = pixels[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&pixel| pixel == pixels[i])
+            .count();
+        if run > 1 {
+            out.push(0x80 | (run - 1) as u8);
+            out.extend_from_slice(pixels[i]);
+            i += run;
+            continue;
+        }
+        // A raw packet lasts until two equal pixels start a run.
+        let mut end = i + 1;
+        while end < pixels.len() && end - i < 128 && pixels.get(end + 1) != Some(&pixels[end]) {
+            end += 1;
+        }
+        out.push((end - i - 1) as u8);
+        for pixel in &pixels[i..end] {
+            out.extend_from_slice(pixel);
+        }
+        i = end;
+    }
+}
+
+/// Writes a bottom-up true color TGA with a TGA 2.0 footer, 32 bit if the pixels have alpha and
+/// 24 bit otherwise.
+pub fn encode<Source, P>(
+    mut writer: impl Write,
+    image: &Image<Source, P>,
+    rle: bool,
+) -> Result<(), CodecError>
+where
+    Source: AsRef<[P]>,
+    P: DynamicPixel,
+{
+    let (Ok(width), Ok(height)) = (u16::try_from(image.width()), u16::try_from(image.height()))
+    else {
+        return Err(CodecError::Invalid(
+            "TGA images are at most 65535 pixels wide and high",
+        ));
+    };
+    let alpha = P::FORMAT.layout.has_alpha();
+    let (layout, size) = if alpha {
+        (ChannelLayout::Rgba, 4)
+    } else {
+        (ChannelLayout::Rgb, 3)
+    };
+    let mut out = vec![0, 0, if rle { 10 } else { 2 }, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    out.extend(width.to_le_bytes());
+    out.extend(height.to_le_bytes());
+    out.push(size as u8 * 8);
+    out.push(if alpha { 8 } else { 0 });
+
+    let mut line = Vec::with_capacity(image.width() * size);
+    for row in image.iter_rows().rev() {
+        line.clear();
+        for &pixel in row {
+            let [r, g, b, a] = to_samples(pixel, layout, 255).map(|sample| sample as u8);
+            line.extend_from_slice(&[b, g, r, a][..size]);
+        }
+        if rle {
+            rle_row(&line, size, &mut out);
+        } else {
+            out.extend_from_slice(&line);
+        }
+    }
+    // No extension or developer area.
+    out.extend([0; 8]);
+    out.extend(SIGNATURE);
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+struct Encode<W> {
+    writer: W,
+    rle: bool,
+}
+
+impl<W: Write> ImageVisitor for Encode<W> {
+    type Output = Result<(), CodecError>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        encode(self.writer, image, self.rle)
+    }
+}
+
+pub fn encode_dynamic(
+    writer: impl Write,
+    image: &DynamicImage,
+    rle: bool,
+) -> Result<(), CodecError> {
+    image.visit(Encode { writer, rle })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::{Rgb, Rgba};
+
+    /// A file without a color map.
+    fn file(
+        image_type: u8,
+        [width, height]: [u16; 2],
+        depth: u8,
+        descriptor: u8,
+        body: &[u8],
+    ) -> Vec<u8> {
+        let mut out = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend([depth, descriptor]);
+        out.extend(body);
+        out
+    }
+
+    fn rgb(image: &DynamicImage) -> Vec<[u8; 3]> {
+        let image = image.as_image::<Rgb<u8>>().unwrap();
+        image.iter().map(|(_, p)| [p.r, p.g, p.b]).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        // Runs longer than a packet, runs crossing rows, and lone pixels.
+        let width = 150;
+        let pixel = |i: usize| match i % width {
+            0..130 => Rgba {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 4,
+            },
+            x => Rgba {
+                r: x as u8,
+                g: (i / width) as u8,
+                b: 9,
+                a: 200,
+            },
+        };
+        let pixels: Box<[Rgba<u8>]> = (0..width * 3).map(pixel).collect();
+        let rgba = Image::from_source(width, 3, pixels).ok().unwrap();
+        let rgb = rgba.map(|p| Rgb {
+            r: p.r,
+            g: p.g,
+            b: p.b,
+        });
+        for rle in [false, true] {
+            let mut tga = Vec::new();
+            encode(&mut tga, &rgba, rle).unwrap();
+            let (header, decoded) = decode_with_header(tga.as_slice()).unwrap();
+            assert_eq!(
+                (header.rle, header.pixel_depth, header.footer),
+                (rle, 32, true)
+            );
+            assert_eq!(decoded.as_image::<Rgba<u8>>(), Some(&rgba));
+
+            let mut tga = Vec::new();
+            encode(&mut tga, &rgb, rle).unwrap();
+            let (header, decoded) = decode_with_header(tga.as_slice()).unwrap();
+            assert_eq!((header.alpha, header.pixel_depth), (AlphaMode::None, 24));
+            assert_eq!(decoded.as_image::<Rgb<u8>>(), Some(&rgb));
+            if rle {
+                assert!(tga.len() < width * 3 * 3);
+            }
+        }
+    }
+
+    #[test]
+    fn color_mapped() {
+        // Entries 2 to 4 as 24 bit BGR, then RLE indices: a run of three, then two raw.
+        let mut body = vec![10, 0, 0, 20, 0, 0, 30, 0, 0];
+        body.extend([0x82, 4, 0x01, 2, 3]);
+        let mut data = file(9, [5, 1], 8, 0, &body);
+        data[1] = 1;
+        data[3..8].copy_from_slice(&[2, 0, 3, 0, 24]);
+        let (header, image) = decode_with_header(data.as_slice()).unwrap();
+        assert_eq!(
+            (header.image_type, header.rle),
+            (TgaImageType::ColorMapped, true)
+        );
+        let blues: Vec<u8> = rgb(&image).iter().map(|p| p[2]).collect();
+        assert_eq!(blues, [30, 30, 30, 10, 20]);
+
+        // Below the first entry.
+        data[30] = 1;
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn origins() {
+        let body: Vec<u8> = (1..=4).flat_map(|i| [0, 0, i]).collect();
+        let decode =
+            |descriptor| rgb(&decode(file(2, [2, 2], 24, descriptor, &body).as_slice()).unwrap());
+        let reds = |pixels: Vec<[u8; 3]>| pixels.iter().map(|p| p[0]).collect::<Vec<_>>();
+        assert_eq!(reds(decode(0)), [3, 4, 1, 2]);
+        assert_eq!(reds(decode(0x20)), [1, 2, 3, 4]);
+        assert_eq!(reds(decode(0x10)), [4, 3, 2, 1]);
+        assert_eq!(reds(decode(0x30)), [2, 1, 4, 3]);
+    }
+
+    #[test]
+    fn truncated() {
+        // A raw packet of three pixels with only two present.
+        let data = file(10, [3, 1], 24, 0, &[0x02, 1, 1, 1, 2, 2, 2]);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Truncated)
+        ));
+        // A run packet without its pixel.
+        let data = file(10, [3, 1], 24, 0, &[0x82]);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Truncated)
+        ));
+        // Too few raw pixels.
+        let data = file(2, [3, 1], 24, 0, &[0; 8]);
+        assert!(matches!(
+            decode(data.as_slice()),
+            Err(CodecError::Truncated)
+        ));
+        // Packets are clipped to the image.
+        let data = file(10, [3, 1], 24, 0, &[0x85, 1, 2, 3]);
+        assert_eq!(rgb(&decode(data.as_slice()).unwrap()), [[3, 2, 1]; 3]);
+    }
+}
@@ -0,0 +1,921 @@
+use crate::error::CodecError;
+
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(crate) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order in which the code length code lengths are stored.
+pub(crate) const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// The code lengths of the fixed literal/length and distance codes.
+pub(crate) fn fixed_lengths() -> ([u8; 288], [u8; 30]) {
+    let mut literals = [8; 288];
+    literals[144..256].fill(9);
+    literals[256..280].fill(7);
+    (literals, [5; 30])
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // The largest run that can not overflow before reducing.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Reads bits least significant first, the way deflate packs them.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let Some(&byte) = self.data.get(self.pos) else {
+                break;
+            };
+            self.bits |= (byte as u64) << self.count;
+            self.count += 8;
+            self.pos += 1;
+        }
+    }
+    /// The next `n` bits, padded with zeros past the end of the data.
+    fn peek(&mut self, n: u32) -> u32 {
+        if self.count < n {
+            self.refill();
+        }
+        (self.bits & ((1 << n) - 1)) as u32
+    }
+    fn consume(&mut self, n: u32) -> Result<(), CodecError> {
+        if n > self.count {
+            return Err(CodecError::Truncated);
+        }
+        self.bits >>= n;
+        self.count -= n;
+        Ok(())
+    }
+    fn bits(&mut self, n: u32) -> Result<u32, CodecError> {
+        let value = self.peek(n);
+        self.consume(n)?;
+        Ok(value)
+    }
+    /// Drops the rest of the current byte and returns the position of the next one.
+    fn align(&mut self) -> usize {
+        self.pos -= (self.count / 8) as usize;
+        self.bits = 0;
+        self.count = 0;
+        self.pos
+    }
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CodecError> {
+        let start = self.align();
+        let bytes = self
+            .data
+            .get(start..start + len)
+            .ok_or(CodecError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, decoded through a table indexed by the next `bits` input bits.
+struct Huffman {
+    /// `symbol << 4 | length`, with a length of 0 for unused codes.
+    table: Vec<u16>,
+    bits: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, CodecError> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(CodecError::Invalid("over-subscribed Huffman code"));
+            }
+        }
+        let bits = (1..16).rev().find(|&len| counts[len] != 0).unwrap_or(0) as u32;
+        let mut next = [0u32; 16];
+        for len in 1..16 {
+            next[len] = (next[len - 1] + counts[len - 1] as u32) << 1;
+        }
+        let mut table = vec![0; 1 << bits];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            let reversed = code.reverse_bits() >> (32 - len as u32);
+            let entry = (symbol as u16) << 4 | len as u16;
+            for fill in (reversed as usize..table.len()).step_by(1 << len) {
+                table[fill] = entry;
+            }
+        }
+        Ok(Self { table, bits })
+    }
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, CodecError> {
+        let entry = self.table[reader.peek(self.bits) as usize];
+        let len = (entry & 15) as u32;
+        if len == 0 {
+            return Err(
+                if reader.count < self.bits && reader.pos == reader.data.len() {
+                    CodecError::Truncated
+                } else {
+                    CodecError::Invalid("invalid Huffman code")
+                },
+            );
+        }
+        reader.consume(len)?;
+        Ok(entry >> 4)
+    }
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), CodecError> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = reader.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+    let mut lengths = vec![0u8; literals + distances];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code.decode(reader)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let previous = *i
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or(CodecError::Invalid(
+                        "repeated code length without a previous one",
+                    ))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        lengths
+            .get_mut(i..i + repeat)
+            .ok_or(CodecError::Invalid("code lengths overflow"))?
+            .fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(CodecError::Invalid("missing end of block code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+/// Decompresses a raw deflate stream, failing once the output would exceed `limit` bytes.
+/// Returns the output and the number of input bytes used.
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), CodecError> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bits: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                let header = reader.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(CodecError::Invalid("stored block length is corrupt"));
+                }
+                if out.len() + len as usize > limit {
+                    return Err(CodecError::Invalid("decompressed data is too long"));
+                }
+                out.extend_from_slice(reader.bytes(len as usize)?);
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    let (literals, distances) = fixed_lengths();
+                    (Huffman::new(&literals)?, Huffman::new(&distances)?)
+                } else {
+                    dynamic_codes(&mut reader)?
+                };
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol == 256 {
+                        break;
+                    }
+                    if out.len() == limit {
+                        return Err(CodecError::Invalid("decompressed data is too long"));
+                    }
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    let index = symbol - 257;
+                    if index >= LENGTH_BASE.len() {
+                        return Err(CodecError::Invalid("invalid length code"));
+                    }
+                    let len = LENGTH_BASE[index] as usize
+                        + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                    let index = distances.decode(&mut reader)? as usize;
+                    if index >= DISTANCE_BASE.len() {
+                        return Err(CodecError::Invalid("invalid distance code"));
+                    }
+                    let distance = DISTANCE_BASE[index] as usize
+                        + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(CodecError::Invalid("distance reaches before the start"));
+                    }
+                    if out.len() + len > limit {
+                        return Err(CodecError::Invalid("decompressed data is too long"));
+                    }
+                    let start = out.len() - distance;
+                    if distance >= len {
+                        out.extend_from_within(start..start + len);
+                    } else {
+                        for i in start..start + len {
+                            out.push(out[i]);
+                        }
+                    }
+                }
+            }
+            _ => return Err(CodecError::Invalid("invalid block type")),
+        }
+        if last {
+            return Ok((out, reader.align()));
+        }
+    }
+}
+
+/// Decompresses a zlib stream and checks its Adler-32 checksum.
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, CodecError> {
+    let [cmf, flg, ..] = *data else {
+        return Err(CodecError::Truncated);
+    };
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 || u16::from_be_bytes([cmf, flg]) % 31 > 0 {
+        return Err(CodecError::Invalid("invalid zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(CodecError::Unsupported("zlib preset dictionary"));
+    }
+    let (out, used) = inflate(&data[2..], limit)?;
+    let trailer = data
+        .get(2 + used..2 + used + 4)
+        .ok_or(CodecError::Truncated)?;
+    let expected = u32::from_be_bytes(trailer.try_into().unwrap());
+    let found = adler32(&out);
+    if expected != found {
+        return Err(CodecError::ChecksumMismatch { expected, found });
+    }
+    Ok(out)
+}
+
+const WINDOW: usize = 1 << 15;
+const HASH_BITS: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Tokens per compressed block, which bounds how long a Huffman code is reused.
+const BLOCK_TOKENS: usize = 1 << 14;
+
+/// Writes bits least significant first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+            self.bits = 0;
+            self.count = 0;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, distance: u16 },
+}
+
+fn length_code(len: usize) -> usize {
+    LENGTH_BASE.partition_point(|&base| base as usize <= len) - 1
+}
+
+fn distance_code(distance: usize) -> usize {
+    DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1
+}
+
+/// Finds LZ77 matches through hash chains. Longer chains find better matches more slowly.
+struct Matcher<'a> {
+    data: &'a [u8],
+    /// The most recent position with each hash.
+    head: Vec<usize>,
+    /// The previous position with the same hash, indexed by position modulo the window.
+    prev: Vec<usize>,
+    /// Positions below this have been hashed.
+    hashed: usize,
+    max_chain: usize,
+    /// Stop searching once a match is this long.
+    nice: usize,
+    lazy: bool,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8], level: u8) -> Self {
+        let (max_chain, nice, lazy) = match level {
+            1 => (4, 16, false),
+            2 => (8, 32, false),
+            3 => (16, 64, false),
+            4 => (16, 64, true),
+            5 => (32, 128, true),
+            6 => (64, 128, true),
+            7 => (256, 258, true),
+            8 => (1024, 258, true),
+            _ => (4096, 258, true),
+        };
+        Self {
+            data,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            prev: vec![usize::MAX; WINDOW],
+            hashed: 0,
+            max_chain,
+            nice,
+            lazy,
+        }
+    }
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = &self.data[pos..pos + MIN_MATCH];
+        let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+    /// Hashes every position before `end`.
+    fn insert_until(&mut self, end: usize) {
+        let end = end.min(self.data.len().saturating_sub(MIN_MATCH - 1));
+        while self.hashed < end {
+            let hash = self.hash(self.hashed);
+            self.prev[self.hashed % WINDOW] = self.head[hash];
+            self.head[hash] = self.hashed;
+            self.hashed += 1;
+        }
+    }
+    /// The longest earlier match for `pos`, as `(len, distance)`.
+    fn find(&mut self, pos: usize) -> Option<(usize, usize)> {
+        let max = (self.data.len() - pos).min(MAX_MATCH);
+        if max < MIN_MATCH {
+            return None;
+        }
+        self.insert_until(pos);
+        let mut best = (MIN_MATCH - 1, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        for _ in 0..self.max_chain {
+            if candidate == usize::MAX || pos - candidate > WINDOW {
+                break;
+            }
+            let len = self.data[candidate..]
+                .iter()
+                .zip(&self.data[pos..pos + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best.0 {
+                best = (len, pos - candidate);
+                if len >= self.nice.min(max) {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % WINDOW];
+            if next == usize::MAX || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        (best.1 != 0).then_some(best)
+    }
+    /// Tokens from `pos` up to the block limit, and the position after them.
+    fn block(&mut self, mut pos: usize) -> (Vec<Token>, usize) {
+        let mut tokens = Vec::new();
+        while pos < self.data.len() && tokens.len() < BLOCK_TOKENS {
+            let Some((mut len, mut distance)) = self.find(pos) else {
+                tokens.push(Token::Literal(self.data[pos]));
+                pos += 1;
+                continue;
+            };
+            // Lazy matching: a longer match one byte later wins over this one.
+            if self.lazy && len < self.nice {
+                while let Some(next) = self.find(pos + 1).filter(|next| next.0 > len) {
+                    tokens.push(Token::Literal(self.data[pos]));
+                    pos += 1;
+                    (len, distance) = next;
+                    if tokens.len() == BLOCK_TOKENS {
+                        break;
+                    }
+                }
+            }
+            tokens.push(Token::Match {
+                len: len as u16,
+                distance: distance as u16,
+            });
+            pos += len;
+        }
+        (tokens, pos)
+    }
+}
+
+/// Code lengths of a Huffman code for `freqs`, no longer than `limit` bits. At least two
+/// symbols always get a code, since some decoders reject codes with a single symbol.
+fn huffman_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+    use std::{cmp::Reverse, collections::BinaryHeap};
+    let mut freqs = freqs.to_vec();
+    for symbol in 0..freqs.len() {
+        if freqs.iter().filter(|&&freq| freq > 0).count() >= 2 {
+            break;
+        }
+        if freqs[symbol] == 0 {
+            freqs[symbol] = 1;
+        }
+    }
+    loop {
+        let leaves: Vec<usize> = (0..freqs.len()).filter(|&s| freqs[s] > 0).collect();
+        let mut parents = vec![0; leaves.len()];
+        let mut heap: BinaryHeap<_> = leaves
+            .iter()
+            .enumerate()
+            .map(|(node, &symbol)| Reverse((freqs[symbol] as u64, node)))
+            .collect();
+        while let (Some(Reverse((a, left))), Some(Reverse((b, right)))) = (heap.pop(), heap.pop()) {
+            let node = parents.len();
+            parents.push(0);
+            parents[left] = node;
+            parents[right] = node;
+            heap.push(Reverse((a + b, node)));
+        }
+        // Parents are created after their children, so walking down from the root works.
+        let mut depths = vec![0u8; parents.len()];
+        for node in (0..parents.len() - 1).rev() {
+            depths[node] = depths[parents[node]] + 1;
+        }
+        if depths[..leaves.len()].iter().all(|&depth| depth <= limit) {
+            let mut lengths = vec![0; freqs.len()];
+            for (node, &symbol) in leaves.iter().enumerate() {
+                lengths[symbol] = depths[node];
+            }
+            return lengths;
+        }
+        // Flatten the distribution until the code fits.
+        for freq in freqs.iter_mut().filter(|freq| **freq > 0) {
+            *freq = freq.div_ceil(2);
+        }
+    }
+}
+
+/// Canonical codes for `lengths`, bit reversed for writing.
+fn huffman_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; 16];
+    for &len in lengths {
+        counts[len as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut next = [0u16; 16];
+    for len in 1..16 {
+        next[len] = (next[len - 1] + counts[len - 1]) << 1;
+    }
+    lengths
+        .iter()
+        .map(|&len| {
+            if len == 0 {
+                return 0;
+            }
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            code.reverse_bits() >> (16 - len)
+        })
+        .collect()
+}
+
+/// Run length encodes code lengths as `(symbol, extra bits value)`.
+fn encode_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        if len == 0 && run >= 11 {
+            let run = run.min(138);
+            out.push((18, (run - 11) as u8));
+            i += run;
+        } else if len == 0 && run >= 3 {
+            out.push((17, (run - 3) as u8));
+            i += run;
+        } else if i > 0 && lengths[i - 1] == len && run >= 3 {
+            let run = run.min(6);
+            out.push((16, (run - 3) as u8));
+            i += run;
+        } else {
+            out.push((len, 0));
+            i += 1;
+        }
+    }
+    out
+}
+
+fn extra_bits(symbol: u8) -> u32 {
+    match symbol {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+/// The header of a dynamic block, with the codes it describes.
+struct DynamicCodes {
+    literals: Vec<u8>,
+    distances: Vec<u8>,
+    runs: Vec<(u8, u8)>,
+    code_lengths: [u8; 19],
+    /// Entries of `CODE_LENGTH_ORDER` that are written.
+    stored_code_lengths: usize,
+}
+
+impl DynamicCodes {
+    fn new(literal_freqs: &[u32], distance_freqs: &[u32]) -> Self {
+        let mut literals = huffman_lengths(literal_freqs, 15);
+        let mut distances = huffman_lengths(distance_freqs, 15);
+        let literal_count = 257.max(literals.iter().rposition(|&len| len != 0).unwrap_or(0) + 1);
+        let distance_count = 1.max(distances.iter().rposition(|&len| len != 0).unwrap_or(0) + 1);
+        literals.truncate(literal_count);
+        distances.truncate(distance_count);
+        let runs = encode_lengths(&[&literals[..], &distances[..]].concat());
+        let mut freqs = [0u32; 19];
+        for &(symbol, _) in &runs {
+            freqs[symbol as usize] += 1;
+        }
+        let code_lengths: [u8; 19] = huffman_lengths(&freqs, 7).try_into().unwrap();
+        let stored_code_lengths = 4.max(
+            CODE_LENGTH_ORDER
+                .iter()
+                .rposition(|&symbol| code_lengths[symbol] != 0)
+                .unwrap_or(0)
+                + 1,
+        );
+        Self {
+            literals,
+            distances,
+            runs,
+            code_lengths,
+            stored_code_lengths,
+        }
+    }
+    fn header_bits(&self) -> usize {
+        14 + 3 * self.stored_code_lengths
+            + self
+                .runs
+                .iter()
+                .map(|&(symbol, _)| {
+                    (self.code_lengths[symbol as usize] as u32 + extra_bits(symbol)) as usize
+                })
+                .sum::<usize>()
+    }
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write(self.literals.len() as u32 - 257, 5);
+        writer.write(self.distances.len() as u32 - 1, 5);
+        writer.write(self.stored_code_lengths as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..self.stored_code_lengths] {
+            writer.write(self.code_lengths[symbol] as u32, 3);
+        }
+        let codes = huffman_codes(&self.code_lengths);
+        for &(symbol, extra) in &self.runs {
+            let symbol = symbol as usize;
+            writer.write(codes[symbol] as u32, self.code_lengths[symbol] as u32);
+            writer.write(extra as u32, extra_bits(symbol as u8));
+        }
+    }
+}
+
+/// Bits taken by `tokens` and the end of block code with the given code lengths.
+fn token_bits(tokens: &[Token], literals: &[u8], distances: &[u8]) -> usize {
+    let mut bits = literals[256] as usize;
+    for &token in tokens {
+        bits += match token {
+            Token::Literal(byte) => literals[byte as usize] as usize,
+            Token::Match { len, distance } => {
+                let len = length_code(len as usize);
+                let distance = distance_code(distance as usize);
+                (literals[257 + len]
+                    + LENGTH_EXTRA[len]
+                    + distances[distance]
+                    + DISTANCE_EXTRA[distance]) as usize
+            }
+        };
+    }
+    bits
+}
+
+fn write_tokens(writer: &mut BitWriter, tokens: &[Token], literals: &[u8], distances: &[u8]) {
+    let literal_codes = huffman_codes(literals);
+    let distance_codes = huffman_codes(distances);
+    let symbol = |writer: &mut BitWriter, codes: &[u16], lengths: &[u8], symbol: usize| {
+        writer.write(codes[symbol] as u32, lengths[symbol] as u32)
+    };
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => symbol(writer, &literal_codes, literals, byte as usize),
+            Token::Match { len, distance } => {
+                let (len, distance) = (len as usize, distance as usize);
+                let code = length_code(len);
+                symbol(writer, &literal_codes, literals, 257 + code);
+                writer.write(
+                    (len - LENGTH_BASE[code] as usize) as u32,
+                    LENGTH_EXTRA[code] as u32,
+                );
+                let code = distance_code(distance);
+                symbol(writer, &distance_codes, distances, code);
+                writer.write(
+                    (distance - DISTANCE_BASE[code] as usize) as u32,
+                    DISTANCE_EXTRA[code] as u32,
+                );
+            }
+        }
+    }
+    symbol(writer, &literal_codes, literals, 256);
+}
+
+fn write_stored(writer: &mut BitWriter, data: &[u8], last: bool) {
+    let mut chunks = data.chunks(u16::MAX as usize);
+    // An empty block still needs a header.
+    let empty: &[u8] = &[];
+    let mut next = chunks.next().or(Some(empty));
+    while let Some(chunk) = next {
+        next = chunks.next();
+        writer.write((last && next.is_none()) as u32, 3);
+        writer.align();
+        let len = chunk.len() as u16;
+        writer.out.extend(len.to_le_bytes());
+        writer.out.extend((!len).to_le_bytes());
+        writer.out.extend_from_slice(chunk);
+    }
+}
+
+/// Writes `tokens`, which encode `data`, as whichever block type is smallest.
+fn write_block(writer: &mut BitWriter, tokens: &[Token], data: &[u8], last: bool) {
+    let mut literal_freqs = [0u32; 286];
+    let mut distance_freqs = [0u32; 30];
+    literal_freqs[256] = 1;
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => literal_freqs[byte as usize] += 1,
+            Token::Match { len, distance } => {
+                literal_freqs[257 + length_code(len as usize)] += 1;
+                distance_freqs[distance_code(distance as usize)] += 1;
+            }
+        }
+    }
+    let (fixed_literals, fixed_distances) = fixed_lengths();
+    let dynamic = DynamicCodes::new(&literal_freqs, &distance_freqs);
+    let fixed_bits = 3 + token_bits(tokens, &fixed_literals, &fixed_distances);
+    let dynamic_bits =
+        3 + dynamic.header_bits() + token_bits(tokens, &dynamic.literals, &dynamic.distances);
+    let stored_bits = data.len().div_ceil(u16::MAX as usize).max(1) * 40 + data.len() * 8;
+    if stored_bits <= fixed_bits.min(dynamic_bits) {
+        write_stored(writer, data, last);
+    } else if fixed_bits <= dynamic_bits {
+        writer.write(last as u32 | 1 << 1, 3);
+        write_tokens(writer, tokens, &fixed_literals, &fixed_distances);
+    } else {
+        writer.write(last as u32 | 2 << 1, 3);
+        dynamic.write(writer);
+        write_tokens(writer, tokens, &dynamic.literals, &dynamic.distances);
+    }
+}
+
+/// Compresses `data` into a raw deflate stream. Level 0 stores the data, 1 to 9 search
+/// progressively harder for matches.
+pub(crate) fn deflate(data: &[u8], level: u8) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    if level == 0 {
+        write_stored(&mut writer, data, true);
+        return writer.out;
+    }
+    let mut matcher = Matcher::new(data, level);
+    let mut start = 0;
+    loop {
+        let (tokens, end) = matcher.block(start);
+        let last = end == data.len();
+        write_block(&mut writer, &tokens, &data[start..end], last);
+        if last {
+            writer.align();
+            return writer.out;
+        }
+        start = end;
+    }
+}
+
+/// Compresses `data` into a zlib stream.
+pub(crate) fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    let flevel = match level {
+        0 | 1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    };
+    let header = 0x7800 | flevel << 6;
+    let header = header + 31 - header % 31;
+    let mut out = (header as u16).to_be_bytes().to_vec();
+    out.extend(deflate(data, level));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repeated words at varying distances, mixed with noise.
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state = 0x9e37_79b9_u32;
+        let words: [&[u8]; 4] = [b"deflate ", b"inflate ", b"huffman ", b"window "];
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            match state % 8 {
+                0 => out.push(state as u8),
+                n => out.extend_from_slice(words[n as usize % 4]),
+            }
+        }
+        out.truncate(len);
+        out
+    }
+
+    fn round_trip(data: &[u8], level: u8) -> Vec<u8> {
+        let compressed = compress(data, level);
+        assert_eq!(
+            decompress(&compressed, data.len()).unwrap(),
+            data,
+            "level {level}"
+        );
+        compressed
+    }
+
+    #[test]
+    fn round_trip_levels() {
+        let data = sample(20_000);
+        let stored = round_trip(&data, 0);
+        assert!(stored.len() > data.len());
+        for level in 1..=9 {
+            let compressed = round_trip(&data, level);
+            assert!(compressed.len() < data.len() / 2, "level {level}");
+            round_trip(&data[..1000], level);
+        }
+    }
+
+    #[test]
+    fn round_trip_edge_cases() {
+        let incompressible: Vec<u8> = (0..70_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let repetitive = vec![7; 1 << 17];
+        for level in 0..=9 {
+            round_trip(&[], level);
+            round_trip(&[1], level);
+            // Takes more than one stored block, which holds at most 64 KiB.
+            round_trip(&incompressible, level);
+        }
+        for level in 1..=9 {
+            let compressed = round_trip(&repetitive, level);
+            assert!(compressed.len() < 1024, "level {level}");
+            round_trip(
+                &[&incompressible[..], &repetitive[..], &incompressible[..]].concat(),
+                level,
+            );
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut compressed = compress(b"checksum", 6);
+        *compressed.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decompress(&compressed, 8),
+            Err(CodecError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            decompress(&compressed[..compressed.len() - 1], 8),
+            Err(CodecError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn over_subscribed_code() {
+        let mut writer = BitWriter::default();
+        // A final dynamic block with 257 literal codes, 1 distance code and 4 code length codes.
+        writer.write(1 | 2 << 1, 3);
+        writer.write(0, 5 + 5 + 4);
+        // Four code length codes of a single bit each.
+        for _ in 0..4 {
+            writer.write(1, 3);
+        }
+        writer.align();
+        assert!(matches!(
+            inflate(&writer.out, 100),
+            Err(CodecError::Invalid("over-subscribed Huffman code"))
+        ));
+    }
+
+    fn fixed_block(tokens: &[Token]) -> Vec<u8> {
+        let (literals, distances) = fixed_lengths();
+        let mut writer = BitWriter::default();
+        writer.write(1 | 1 << 1, 3);
+        write_tokens(&mut writer, tokens, &literals, &distances);
+        writer.align();
+        writer.out
+    }
+
+    #[test]
+    fn distance_before_start() {
+        let tokens = [
+            Token::Literal(b'a'),
+            Token::Match {
+                len: 4,
+                distance: 1,
+            },
+        ];
+        assert_eq!(inflate(&fixed_block(&tokens), 5).unwrap().0, b"aaaaa");
+        let tokens = [
+            Token::Literal(b'a'),
+            Token::Match {
+                len: 3,
+                distance: 2,
+            },
+        ];
+        assert!(matches!(
+            inflate(&fixed_block(&tokens), 100),
+            Err(CodecError::Invalid("distance reaches before the start"))
+        ));
+    }
+
+    #[test]
+    fn output_over_limit() {
+        let data = sample(10_000);
+        for level in [0, 1, 6, 9] {
+            let compressed = deflate(&data, level);
+            assert_eq!(inflate(&compressed, data.len()).unwrap().0, data);
+            assert!(
+                matches!(
+                    inflate(&compressed, data.len() - 1),
+                    Err(CodecError::Invalid("decompressed data is too long"))
+                ),
+                "level {level}"
+            );
+        }
+        let repetitive = deflate(&[0; 1000], 6);
+        assert!(matches!(
+            inflate(&repetitive, 999),
+            Err(CodecError::Invalid("decompressed data is too long"))
+        ));
+    }
+}
@@ -0,0 +1,624 @@
+use crate::{error::SizeMismatch, pixel::Rgb, Image};
+
+const LINEAR_SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+const XYZ_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+const BRADFORD_D65_TO_D50: [[f32; 3]; 3] = [
+    [1.047_811_2, 0.022_886_6, -0.050_127],
+    [0.029_542_4, 0.990_484_4, -0.017_049_1],
+    [-0.009_234_5, 0.015_043_6, 0.752_131_6],
+];
+const BRADFORD_D50_TO_D65: [[f32; 3]; 3] = [
+    [0.955_576_6, -0.023_039_3, 0.063_163_6],
+    [-0.028_289_5, 1.009_941_6, 0.021_007_7],
+    [0.012_298_2, -0.020_483, 1.329_909_8],
+];
+const LINEAR_SRGB_TO_LMS: [[f32; 3]; 3] = [
+    [0.412_221_47, 0.536_332_55, 0.051_445_995],
+    [0.211_903_5, 0.680_699_5, 0.107_396_96],
+    [0.088_302_46, 0.281_718_85, 0.629_978_7],
+];
+const LMS_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [4.076_741_7, -3.307_711_6, 0.230_969_94],
+    [-1.268_438, 2.609_757_4, -0.341_319_38],
+    [-0.004_196_086_3, -0.703_418_6, 1.707_614_7],
+];
+const LMS_TO_OKLAB: [[f32; 3]; 3] = [
+    [0.210_454_26, 0.793_617_8, -0.004_072_047],
+    [1.977_998_5, -2.428_592_2, 0.450_593_7],
+    [0.025_904_037, 0.782_771_77, -0.808_675_77],
+];
+const OKLAB_TO_LMS: [[f32; 3]; 3] = [
+    [1.0, 0.396_337_78, 0.215_803_76],
+    [1.0, -0.105_561_346, -0.063_854_17],
+    [1.0, -0.089_484_18, -1.291_485_5],
+];
+
+const LAB_EPSILON: f32 = 216.0 / 24389.0;
+const LAB_KAPPA: f32 = 24389.0 / 27.0;
+
+fn mul(matrix: &[[f32; 3]; 3], [x, y, z]: [f32; 3]) -> [f32; 3] {
+    matrix.map(|[a, b, c]| a * x + b * y + c * z)
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Xyz {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Xyz {
+    pub const D65: Self = Self {
+        x: 0.950_47,
+        y: 1.0,
+        z: 1.088_83,
+    };
+    pub const D50: Self = Self {
+        x: 0.964_22,
+        y: 1.0,
+        z: 0.825_21,
+    };
+
+    /// Linear sRGB to XYZ relative to D65.
+    pub fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        mul(&LINEAR_SRGB_TO_XYZ, rgb.into()).into()
+    }
+    pub fn to_linear_rgb(self) -> Rgb<f32> {
+        mul(&XYZ_TO_LINEAR_SRGB, self.into()).into()
+    }
+    /// Bradford chromatic adaptation from D65 to D50.
+    pub fn d65_to_d50(self) -> Self {
+        mul(&BRADFORD_D65_TO_D50, self.into()).into()
+    }
+    /// Bradford chromatic adaptation from D50 to D65.
+    pub fn d50_to_d65(self) -> Self {
+        mul(&BRADFORD_D50_TO_D65, self.into()).into()
+    }
+}
+
+impl From<[f32; 3]> for Xyz {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Xyz> for [f32; 3] {
+    fn from(Xyz { x, y, z }: Xyz) -> Self {
+        [x, y, z]
+    }
+}
+
+/// CIELAB. Conversions from RGB are relative to D50, after Bradford adaptation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Lab {
+    pub fn from_xyz(xyz: Xyz, white: Xyz) -> Self {
+        let f = |t: f32| {
+            if t > LAB_EPSILON {
+                t.cbrt()
+            } else {
+                (LAB_KAPPA * t + 16.0) / 116.0
+            }
+        };
+        let fx = f(xyz.x / white.x);
+        let fy = f(xyz.y / white.y);
+        let fz = f(xyz.z / white.z);
+        Self {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+    pub fn to_xyz(self, white: Xyz) -> Xyz {
+        let fy = (self.l + 16.0) / 116.0;
+        let fx = fy + self.a / 500.0;
+        let fz = fy - self.b / 200.0;
+        let f_inv = |f: f32| {
+            let cubed = f * f * f;
+            if cubed > LAB_EPSILON {
+                cubed
+            } else {
+                (116.0 * f - 16.0) / LAB_KAPPA
+            }
+        };
+        let yr = if self.l > LAB_KAPPA * LAB_EPSILON {
+            fy * fy * fy
+        } else {
+            self.l / LAB_KAPPA
+        };
+        Xyz {
+            x: f_inv(fx) * white.x,
+            y: yr * white.y,
+            z: f_inv(fz) * white.z,
+        }
+    }
+    pub fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Self::from_xyz(Xyz::from_linear_rgb(rgb).d65_to_d50(), Xyz::D50)
+    }
+    pub fn to_linear_rgb(self) -> Rgb<f32> {
+        self.to_xyz(Xyz::D50).d50_to_d65().to_linear_rgb()
+    }
+    pub fn to_lch(self) -> Lch {
+        let [l, c, h] = to_polar([self.l, self.a, self.b]);
+        Lch { l, c, h }
+    }
+    pub fn delta_e(self, other: Self, formula: DeltaE) -> f32 {
+        match formula {
+            DeltaE::Cie76 => delta_e_76(self, other),
+            DeltaE::Cie94 => delta_e_94(self, other),
+            DeltaE::Ciede2000 => delta_e_2000(self, other),
+        }
+    }
+}
+
+/// Cylindrical CIELAB, with the hue in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Lch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+impl Lch {
+    pub fn to_lab(self) -> Lab {
+        let [l, a, b] = from_polar([self.l, self.c, self.h]);
+        Lab { l, a, b }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Oklab {
+    pub fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        let lms = mul(&LINEAR_SRGB_TO_LMS, rgb.into()).map(f32::cbrt);
+        let [l, a, b] = mul(&LMS_TO_OKLAB, lms);
+        Self { l, a, b }
+    }
+    pub fn to_linear_rgb(self) -> Rgb<f32> {
+        let lms = mul(&OKLAB_TO_LMS, [self.l, self.a, self.b]).map(|v| v * v * v);
+        mul(&LMS_TO_LINEAR_SRGB, lms).into()
+    }
+    pub fn to_oklch(self) -> Oklch {
+        let [l, c, h] = to_polar([self.l, self.a, self.b]);
+        Oklch { l, c, h }
+    }
+}
+
+/// Cylindrical Oklab, with the hue in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Oklch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+impl Oklch {
+    pub fn to_oklab(self) -> Oklab {
+        let [l, a, b] = from_polar([self.l, self.c, self.h]);
+        Oklab { l, a, b }
+    }
+}
+
+fn to_polar([l, a, b]: [f32; 3]) -> [f32; 3] {
+    [l, a.hypot(b), b.atan2(a).to_degrees().rem_euclid(360.0)]
+}
+
+fn from_polar([l, c, h]: [f32; 3]) -> [f32; 3] {
+    let (sin, cos) = h.to_radians().sin_cos();
+    [l, c * cos, c * sin]
+}
+
+pub trait ColorSpace: Copy {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self;
+    fn to_linear_rgb(self) -> Rgb<f32>;
+}
+
+impl ColorSpace for Rgb<f32> {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        rgb
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        self
+    }
+}
+
+impl ColorSpace for Xyz {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Xyz::from_linear_rgb(rgb)
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        Xyz::to_linear_rgb(self)
+    }
+}
+
+impl ColorSpace for Lab {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Lab::from_linear_rgb(rgb)
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        Lab::to_linear_rgb(self)
+    }
+}
+
+impl ColorSpace for Lch {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Lab::from_linear_rgb(rgb).to_lch()
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        self.to_lab().to_linear_rgb()
+    }
+}
+
+impl ColorSpace for Oklab {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Oklab::from_linear_rgb(rgb)
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        Oklab::to_linear_rgb(self)
+    }
+}
+
+impl ColorSpace for Oklch {
+    fn from_linear_rgb(rgb: Rgb<f32>) -> Self {
+        Oklab::from_linear_rgb(rgb).to_oklch()
+    }
+    fn to_linear_rgb(self) -> Rgb<f32> {
+        self.to_oklab().to_linear_rgb()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaE {
+    Cie76,
+    /// Graphic arts weighting (kL = 1, K1 = 0.045, K2 = 0.015).
+    Cie94,
+    Ciede2000,
+}
+
+pub fn delta_e_76(lhs: Lab, rhs: Lab) -> f32 {
+    let (dl, da, db) = (lhs.l - rhs.l, lhs.a - rhs.a, lhs.b - rhs.b);
+    (dl * dl + da * da + db * db).sqrt()
+}
+
+pub fn delta_e_94(lhs: Lab, rhs: Lab) -> f32 {
+    let c1 = lhs.a.hypot(lhs.b);
+    let c2 = rhs.a.hypot(rhs.b);
+    let dl = lhs.l - rhs.l;
+    let dc = c1 - c2;
+    let (da, db) = (lhs.a - rhs.a, lhs.b - rhs.b);
+    let dh_squared = (da * da + db * db - dc * dc).max(0.0);
+    let sc = 1.0 + 0.045 * c1;
+    let sh = 1.0 + 0.015 * c1;
+    (dl * dl + (dc / sc).powi(2) + dh_squared / (sh * sh)).sqrt()
+}
+
+pub fn delta_e_2000(lhs: Lab, rhs: Lab) -> f32 {
+    const POW_25_7: f32 = 6_103_515_625.0;
+    let c_bar = (lhs.a.hypot(lhs.b) + rhs.a.hypot(rhs.b)) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + POW_25_7)).sqrt());
+    let a1 = (1.0 + g) * lhs.a;
+    let a2 = (1.0 + g) * rhs.a;
+    let c1 = a1.hypot(lhs.b);
+    let c2 = a2.hypot(rhs.b);
+    let hue = |b: f32, a: f32| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let h1 = hue(lhs.b, a1);
+    let h2 = hue(rhs.b, a2);
+
+    let dl = rhs.l - lhs.l;
+    let dc = c2 - c1;
+    let dh = if c1 * c2 == 0.0 {
+        0.0
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else if h2 - h1 < -180.0 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1
+    };
+    let dh = 2.0 * (c1 * c2).sqrt() * (dh.to_radians() / 2.0).sin();
+
+    let l_bar = (lhs.l + rhs.l) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_bar).to_radians().cos()
+        + 0.32 * (3.0 * h_bar + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_bar - 63.0).to_radians().cos();
+    let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let rc = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + POW_25_7)).sqrt();
+    let sl = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let sc = 1.0 + 0.045 * c_bar;
+    let sh = 1.0 + 0.015 * c_bar * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let (l_term, c_term, h_term) = (dl / sl, dc / sc, dh / sh);
+    (l_term * l_term + c_term * c_term + h_term * h_term + rt * c_term * h_term).sqrt()
+}
+
+impl<Source, Pixel> Image<Source, Pixel>
+where
+    Source: AsRef<[Pixel]>,
+    Pixel: ColorSpace,
+{
+    pub fn convert_color<T: ColorSpace>(&self) -> Image<Box<[T]>, T> {
+        self.map(|pixel| T::from_linear_rgb(pixel.to_linear_rgb()))
+    }
+}
+
+impl<Source> Image<Source, Lab>
+where
+    Source: AsRef<[Lab]>,
+{
+    pub fn delta_e<Other: AsRef<[Lab]>>(
+        &self,
+        other: &Image<Other, Lab>,
+        formula: DeltaE,
+    ) -> Result<Image<Box<[f32]>, f32>, SizeMismatch> {
+        if self.width() != other.width() || self.height() != other.height() {
+            return Err(SizeMismatch {
+                left: [self.width(), self.height()],
+                right: [other.width(), other.height()],
+            });
+        }
+        let mut buf = Box::new_uninit_slice(self.width() * self.height());
+        let pairs = self
+            .iter_rows()
+            .zip(other.iter_rows())
+            .flat_map(|(lhs, rhs)| lhs.iter().zip(rhs));
+        for (dst, (lhs, rhs)) in buf.iter_mut().zip(pairs) {
+            dst.write(lhs.delta_e(*rhs, formula));
+        }
+        Ok(unsafe { Image::from_source_unchecked(self.width(), self.height(), buf.assume_init()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3], tolerance: f32) {
+        let close = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (a - e).abs() <= tolerance);
+        assert!(close, "{actual:?} != {expected:?}");
+    }
+
+    fn lab([l, a, b]: [f32; 3]) -> Lab {
+        Lab { l, a, b }
+    }
+
+    fn lab_array(lab: Lab) -> [f32; 3] {
+        [lab.l, lab.a, lab.b]
+    }
+
+    /// Linear RGB colors spread over the cube, including its corners.
+    fn samples() -> impl Iterator<Item = Rgb<f32>> {
+        let steps = [0.0, 0.001, 0.2, 0.5, 0.9, 1.0];
+        steps.into_iter().flat_map(move |r| {
+            steps
+                .into_iter()
+                .flat_map(move |g| steps.map(|b| Rgb { r, g, b }))
+        })
+    }
+
+    #[test]
+    fn srgb_transfer() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-6);
+        assert!((srgb_to_linear(0.04045) - 0.003_130_8).abs() < 1e-6);
+        assert!((linear_to_srgb(0.003_130_8) - 0.04045).abs() < 1e-6);
+        for code in 0..=255 {
+            let value = code as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn lab_reference_values() {
+        let white = Rgb {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        assert_close(
+            lab_array(Lab::from_linear_rgb(white)),
+            [100.0, 0.0, 0.0],
+            0.01,
+        );
+        let red = Rgb {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        assert_close(
+            lab_array(Lab::from_linear_rgb(red)),
+            [54.29, 80.80, 69.89],
+            0.05,
+        );
+        let lch = Lab::from_linear_rgb(red).to_lch();
+        assert_close([lch.l, lch.c, lch.h], [54.29, 106.84, 40.86], 0.05);
+        let oklab = Oklab::from_linear_rgb(red);
+        assert_close(
+            [oklab.l, oklab.a, oklab.b],
+            [0.627_96, 0.224_86, 0.125_85],
+            1e-4,
+        );
+        let oklab = Oklab::from_linear_rgb(white);
+        assert_close([oklab.l, oklab.a, oklab.b], [1.0, 0.0, 0.0], 1e-4);
+    }
+
+    #[test]
+    fn round_trips() {
+        fn check<T: ColorSpace>(rgb: Rgb<f32>) {
+            let back = T::from_linear_rgb(rgb).to_linear_rgb();
+            assert_close(back.into(), rgb.into(), 1e-4);
+        }
+        for rgb in samples() {
+            check::<Xyz>(rgb);
+            check::<Lab>(rgb);
+            check::<Lch>(rgb);
+            check::<Oklab>(rgb);
+            check::<Oklch>(rgb);
+            let xyz = Xyz::from_linear_rgb(rgb);
+            assert_close(xyz.d65_to_d50().d50_to_d65().into(), xyz.into(), 1e-5);
+        }
+    }
+
+    /// Pairs and results from Sharma, Wu and Dalal, "The CIEDE2000 Color-Difference Formula:
+    /// Implementation Notes, Supplementary Test Data, and Mathematical Observations" (2005).
+    const SHARMA: [([f32; 3], [f32; 3], f32); 30] = [
+        ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+        ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+        ([50.0, 2.8361, -74.0200], [50.0, 0.0, -82.7485], 3.4412),
+        ([50.0, -1.3802, -84.2814], [50.0, 0.0, -82.7485], 1.0),
+        ([50.0, -1.1848, -84.8006], [50.0, 0.0, -82.7485], 1.0),
+        ([50.0, -0.9009, -85.5211], [50.0, 0.0, -82.7485], 1.0),
+        ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+        ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+        ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0009], 7.1792),
+        ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0010], 7.1792),
+        ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0011], 7.2195),
+        ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0012], 7.2195),
+        ([50.0, -0.001, 2.49], [50.0, 0.0009, -2.49], 4.8045),
+        ([50.0, -0.001, 2.49], [50.0, 0.0011, -2.49], 4.7461),
+        ([50.0, 2.5, 0.0], [50.0, 0.0, -2.5], 4.3065),
+        ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+        ([50.0, 2.5, 0.0], [61.0, -5.0, 29.0], 22.8977),
+        ([50.0, 2.5, 0.0], [56.0, -27.0, -3.0], 31.9030),
+        ([50.0, 2.5, 0.0], [58.0, 24.0, 15.0], 19.4535),
+        ([50.0, 2.5, 0.0], [50.0, 3.1736, 0.5854], 1.0),
+        ([50.0, 2.5, 0.0], [50.0, 3.2972, 0.0], 1.0),
+        (
+            [60.2574, -34.0099, 36.2677],
+            [60.4626, -34.1751, 39.4387],
+            1.2644,
+        ),
+        (
+            [63.0109, -31.0961, -5.8663],
+            [62.8187, -29.7946, -4.0864],
+            1.2630,
+        ),
+        (
+            [61.2901, 3.7196, -5.3901],
+            [61.4292, 2.2480, -4.9620],
+            1.8731,
+        ),
+        (
+            [35.0831, -44.1164, 3.7933],
+            [35.0232, -40.0716, 1.5901],
+            1.8645,
+        ),
+        (
+            [22.7233, 20.0904, -46.6940],
+            [23.0331, 14.9730, -42.5619],
+            2.0373,
+        ),
+        (
+            [36.4612, 47.8580, 18.3852],
+            [36.2715, 50.5065, 21.2231],
+            1.4146,
+        ),
+        (
+            [90.8027, -2.0831, 1.4410],
+            [91.1528, -1.6435, 0.0447],
+            1.4441,
+        ),
+        (
+            [6.7747, -0.2908, -2.4247],
+            [5.8714, -0.0985, -2.2286],
+            0.6377,
+        ),
+        (
+            [2.0776, 0.0795, -1.1350],
+            [0.9033, -0.0636, -0.5514],
+            0.9082,
+        ),
+    ];
+
+    #[test]
+    fn ciede2000_reference_pairs() {
+        for (lhs, rhs, expected) in SHARMA {
+            let actual = delta_e_2000(lab(lhs), lab(rhs));
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{lhs:?} {rhs:?}: {actual}"
+            );
+            let swapped = delta_e_2000(lab(rhs), lab(lhs));
+            assert!(
+                (swapped - expected).abs() < 1e-3,
+                "{rhs:?} {lhs:?}: {swapped}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_differences() {
+        let (lhs, rhs) = (lab([50.0, 0.0, 0.0]), lab([53.0, 4.0, 0.0]));
+        assert_eq!(delta_e_76(lhs, rhs), 5.0);
+        // Lightness is unweighted, chroma is scaled by 1 + 0.045 C1.
+        assert_eq!(delta_e_94(lhs, lab([47.0, 0.0, 0.0])), 3.0);
+        let chroma = delta_e_94(lab([50.0, 20.0, 0.0]), lab([50.0, 10.0, 0.0]));
+        assert!((chroma - 10.0 / 1.9).abs() < 1e-5);
+        assert_eq!(lhs.delta_e(rhs, DeltaE::Cie76), 5.0);
+
+        let left = Image::filled(3, 2, lhs);
+        let right = Image::filled(3, 2, rhs);
+        let difference = left.delta_e(&right, DeltaE::Cie76).unwrap();
+        assert!(difference.iter().all(|(_, &d)| d == 5.0));
+        let SizeMismatch { left, right } = left
+            .delta_e(&Image::filled(2, 2, rhs), DeltaE::Cie76)
+            .unwrap_err();
+        assert_eq!([left, right], [[3, 2], [2, 2]]);
+    }
+}
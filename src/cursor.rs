@@ -1,4 +1,8 @@
-use crate::Image;
+use crate::{
+    endian::{Endian, ScalarLayout},
+    swizzle::{in_bounds, ChannelMap},
+    Image,
+};
 use core::{cmp, marker::PhantomData, mem::size_of};
 use std::io::{self, Read, Seek, Write};
 
@@ -8,6 +12,8 @@ where
 {
     image: I,
     index: usize,
+    /// For every byte of a pixel as read or written, the byte of the stored pixel it maps to.
+    byte_map: Option<Box<[usize]>>,
     _p: PhantomData<(Source, Pixel)>,
 }
 
@@ -54,35 +60,36 @@ where
     Source: AsRef<[Pixel]> + AsMut<[Pixel]>,
 {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let byte_map = &self.byte_map;
         let image = self.image.as_mut();
         if self.index < image.stride() * image.height() * size_of::<Pixel>() {
             let current_row_index =
                 (self.index / (image.stride() * size_of::<Pixel>())) * image.stride();
+            let first_element_of_row = current_row_index * size_of::<Pixel>();
             let last_element_of_row = (current_row_index + image.width()) * size_of::<Pixel>();
-            let rest_of_row = unsafe {
+            let row = unsafe {
                 std::slice::from_raw_parts_mut(
                     image
                         .source_mut()
                         .as_mut()
                         .as_mut_ptr()
                         .cast::<u8>()
-                        .add(self.index),
-                    last_element_of_row - self.index,
+                        .add(first_element_of_row),
+                    last_element_of_row - first_element_of_row,
                 )
             };
-
-            match buf.len().cmp(&rest_of_row.len()) {
-                cmp::Ordering::Less => {
-                    self.index += buf.len();
-                    rest_of_row[..buf.len()].copy_from_slice(buf);
-                    Ok(buf.len())
-                }
-                cmp::Ordering::Equal | cmp::Ordering::Greater => {
-                    rest_of_row.copy_from_slice(&buf[..rest_of_row.len()]);
-                    self.index += image.stride() * size_of::<Pixel>();
-                    Ok(rest_of_row.len())
+            let offset = self.index - first_element_of_row;
+            let len = cmp::min(buf.len(), row.len() - offset);
+            match byte_map {
+                None => row[offset..offset + len].copy_from_slice(&buf[..len]),
+                Some(map) => {
+                    for (i, byte) in buf[..len].iter().enumerate() {
+                        row[mapped(map, offset + i)] = *byte;
+                    }
                 }
             }
+            self.advance(offset + len == row.len(), len);
+            Ok(len)
         } else {
             Ok(0)
         }
@@ -106,9 +113,39 @@ where
         Self {
             image,
             index: 0,
+            byte_map: None,
             _p: PhantomData,
         }
     }
+    /// Reads and writes every scalar of the pixels in the given byte order.
+    pub fn with_byte_order(mut self, endian: Endian) -> Self
+    where
+        Pixel: ScalarLayout,
+    {
+        if endian != Endian::NATIVE {
+            self.remap(|offset| swapped(offset, Pixel::SCALAR_SIZE));
+        }
+        self
+    }
+    /// Makes every byte at `offset` within a pixel show what was previously shown at `f(offset)`.
+    fn remap(&mut self, f: impl Fn(usize) -> usize) {
+        let previous = self.byte_map.take();
+        self.byte_map = Some(
+            (0..size_of::<Pixel>())
+                .map(|offset| previous.as_ref().map_or(f(offset), |map| map[f(offset)]))
+                .collect(),
+        );
+    }
+    fn advance(&mut self, row_finished: bool, len: usize) {
+        let image = self.image.as_ref();
+        if row_finished {
+            let current_row_index =
+                (self.index / (image.stride() * size_of::<Pixel>())) * image.stride();
+            self.index = (current_row_index + image.stride()) * size_of::<Pixel>();
+        } else {
+            self.index += len;
+        }
+    }
 }
 
 impl<Source, Pixel, I: AsRef<Image<Source, Pixel>>> Read for ImageCursor<Source, Pixel, I>
@@ -120,32 +157,60 @@ where
         if self.index < image.stride() * image.height() * size_of::<Pixel>() {
             let current_row_index =
                 (self.index / (image.stride() * size_of::<Pixel>())) * image.stride();
+            let first_element_of_row = current_row_index * size_of::<Pixel>();
             let last_element_of_row = (current_row_index + image.width()) * size_of::<Pixel>();
-            let rest_of_row = unsafe {
+            let row = unsafe {
                 std::slice::from_raw_parts(
                     image
                         .source()
                         .as_ref()
                         .as_ptr()
                         .cast::<u8>()
-                        .add(self.index),
-                    last_element_of_row - self.index,
+                        .add(first_element_of_row),
+                    last_element_of_row - first_element_of_row,
                 )
             };
-            match buf.len().cmp(&rest_of_row.len()) {
-                cmp::Ordering::Less => {
-                    self.index += buf.len();
-                    buf.copy_from_slice(&rest_of_row[..buf.len()]);
-                    Ok(buf.len())
-                }
-                cmp::Ordering::Equal | cmp::Ordering::Greater => {
-                    buf[..rest_of_row.len()].copy_from_slice(rest_of_row);
-                    self.index += image.stride() * size_of::<Pixel>();
-                    Ok(rest_of_row.len())
+            let offset = self.index - first_element_of_row;
+            let len = cmp::min(buf.len(), row.len() - offset);
+            match &self.byte_map {
+                None => buf[..len].copy_from_slice(&row[offset..offset + len]),
+                Some(map) => {
+                    for (i, byte) in buf[..len].iter_mut().enumerate() {
+                        *byte = row[mapped(map, offset + i)];
+                    }
                 }
             }
+            self.advance(offset + len == row.len(), len);
+            Ok(len)
         } else {
             Ok(0)
         }
     }
 }
+
+impl<Source, T, const N: usize, I: AsRef<Image<Source, [T; N]>>> ImageCursor<Source, [T; N], I>
+where
+    Source: AsRef<[[T; N]]>,
+{
+    /// Reads and writes pixels with their channels reordered by `Map`.
+    ///
+    /// When writing with a map that repeats a channel, the last of its copies is stored.
+    pub fn with_swizzle<Map: ChannelMap<N>>(mut self) -> Self {
+        assert!(in_bounds(Map::MAP, N), "channel map out of bounds");
+        let size = size_of::<T>();
+        self.remap(|offset| Map::MAP[offset / size] * size + offset % size);
+        self
+    }
+}
+
+/// The position of the byte at `offset` within a row, after mapping it within its pixel.
+fn mapped(map: &[usize], offset: usize) -> usize {
+    let within = offset % map.len();
+    offset - within + map[within]
+}
+
+/// The position of the byte that lands at `offset` once every scalar is reversed.
+const fn swapped(offset: usize, swap_size: usize) -> usize {
+    let within = offset % swap_size;
+    offset - within + (swap_size - 1 - within)
+}
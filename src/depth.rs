@@ -0,0 +1,317 @@
+use crate::{
+    error::SizeMismatch,
+    packed::{expand, field, reduce, Bgr565, Rgb10A2, Rgb565, Rgba4444, Rgba5551},
+    pixel::{Luma, LumaA, Rgb, Rgba},
+    Image,
+};
+
+/// One channel of a pixel, either an unsigned integer code or a float in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sample {
+    Code { value: u32, bits: u32 },
+    Float(f32),
+}
+
+impl Sample {
+    pub fn to_unit(self) -> f32 {
+        match self {
+            Sample::Code { value, bits } => value as f32 / ((1u64 << bits) - 1) as f32,
+            Sample::Float(value) => value,
+        }
+    }
+    /// Converts to `bits` bits, or to a float for `None`.
+    ///
+    /// Narrowing rounds to nearest after adding `noise`, in units of the target's least
+    /// significant bit. Widening an integer repeats its bits and ignores `noise`.
+    pub fn convert(self, bits: Option<u32>, noise: f32) -> Self {
+        match (self, bits) {
+            (Sample::Code { value, bits: from }, Some(to)) if to >= from => Sample::Code {
+                value: expand(value, from, to),
+                bits: to,
+            },
+            (Sample::Code { value, bits: from }, Some(to)) if noise == 0.0 => Sample::Code {
+                value: reduce(value, from, to),
+                bits: to,
+            },
+            (sample, Some(to)) => {
+                let max = ((1u64 << to) - 1) as f32;
+                Sample::Code {
+                    value: (sample.to_unit() * max + noise).round().clamp(0.0, max) as u32,
+                    bits: to,
+                }
+            }
+            (sample, None) => Sample::Float(sample.to_unit()),
+        }
+    }
+    /// The code of an integer sample, or the float scaled to `bits` bits.
+    fn code(self, bits: u32) -> u32 {
+        match self.convert(Some(bits), 0.0) {
+            Sample::Code { value, .. } => value,
+            Sample::Float(_) => unreachable!(),
+        }
+    }
+}
+
+/// Scalars usable as a channel: `u8`, `u16` and `f32`.
+pub trait Depth: Copy {
+    /// `None` for floating point.
+    const BITS: Option<u32>;
+    fn to_sample(self) -> Sample;
+    /// `sample` has already been converted to `Self::BITS`.
+    fn from_sample(sample: Sample) -> Self;
+}
+
+impl Depth for u8 {
+    const BITS: Option<u32> = Some(8);
+    fn to_sample(self) -> Sample {
+        Sample::Code {
+            value: self as u32,
+            bits: 8,
+        }
+    }
+    fn from_sample(sample: Sample) -> Self {
+        sample.code(8) as u8
+    }
+}
+
+impl Depth for u16 {
+    const BITS: Option<u32> = Some(16);
+    fn to_sample(self) -> Sample {
+        Sample::Code {
+            value: self as u32,
+            bits: 16,
+        }
+    }
+    fn from_sample(sample: Sample) -> Self {
+        sample.code(16) as u16
+    }
+}
+
+impl Depth for f32 {
+    const BITS: Option<u32> = None;
+    fn to_sample(self) -> Sample {
+        Sample::Float(self)
+    }
+    fn from_sample(sample: Sample) -> Self {
+        sample.to_unit()
+    }
+}
+
+/// Pixels made of `N` channels of possibly differing depths.
+pub trait DepthPixel<const N: usize>: Copy {
+    const BITS: [Option<u32>; N];
+    fn to_samples(self) -> [Sample; N];
+    /// Every sample has already been converted to the matching entry of `Self::BITS`.
+    fn from_samples(samples: [Sample; N]) -> Self;
+}
+
+impl<T: Depth> DepthPixel<1> for T {
+    const BITS: [Option<u32>; 1] = [T::BITS];
+    fn to_samples(self) -> [Sample; 1] {
+        [self.to_sample()]
+    }
+    fn from_samples([sample]: [Sample; 1]) -> Self {
+        T::from_sample(sample)
+    }
+}
+
+impl<T: Depth, const N: usize> DepthPixel<N> for [T; N] {
+    const BITS: [Option<u32>; N] = [T::BITS; N];
+    fn to_samples(self) -> [Sample; N] {
+        self.map(T::to_sample)
+    }
+    fn from_samples(samples: [Sample; N]) -> Self {
+        samples.map(T::from_sample)
+    }
+}
+
+impl<T: Depth> DepthPixel<1> for Luma<T> {
+    const BITS: [Option<u32>; 1] = [T::BITS];
+    fn to_samples(self) -> [Sample; 1] {
+        [self.l.to_sample()]
+    }
+    fn from_samples(samples: [Sample; 1]) -> Self {
+        samples.map(T::from_sample).into()
+    }
+}
+
+impl<T: Depth> DepthPixel<2> for LumaA<T> {
+    const BITS: [Option<u32>; 2] = [T::BITS; 2];
+    fn to_samples(self) -> [Sample; 2] {
+        [self.l, self.a].map(T::to_sample)
+    }
+    fn from_samples(samples: [Sample; 2]) -> Self {
+        samples.map(T::from_sample).into()
+    }
+}
+
+impl<T: Depth> DepthPixel<3> for Rgb<T> {
+    const BITS: [Option<u32>; 3] = [T::BITS; 3];
+    fn to_samples(self) -> [Sample; 3] {
+        [self.r, self.g, self.b].map(T::to_sample)
+    }
+    fn from_samples(samples: [Sample; 3]) -> Self {
+        samples.map(T::from_sample).into()
+    }
+}
+
+impl<T: Depth> DepthPixel<4> for Rgba<T> {
+    const BITS: [Option<u32>; 4] = [T::BITS; 4];
+    fn to_samples(self) -> [Sample; 4] {
+        [self.r, self.g, self.b, self.a].map(T::to_sample)
+    }
+    fn from_samples(samples: [Sample; 4]) -> Self {
+        samples.map(T::from_sample).into()
+    }
+}
+
+/// Splits `word` into codes of `bits` bits at `shifts`.
+fn unpack<const N: usize>(word: u32, shifts: [u32; N], bits: [u32; N]) -> [Sample; N] {
+    core::array::from_fn(|i| Sample::Code {
+        value: field(word, shifts[i], bits[i]),
+        bits: bits[i],
+    })
+}
+
+fn pack<const N: usize>(samples: [Sample; N], shifts: [u32; N], bits: [u32; N]) -> u32 {
+    (0..N).fold(0, |word, i| word | samples[i].code(bits[i]) << shifts[i])
+}
+
+impl DepthPixel<3> for Rgb565 {
+    const BITS: [Option<u32>; 3] = [Some(5), Some(6), Some(5)];
+    fn to_samples(self) -> [Sample; 3] {
+        unpack(self.0 as u32, [11, 5, 0], [5, 6, 5])
+    }
+    fn from_samples(samples: [Sample; 3]) -> Self {
+        Self(pack(samples, [11, 5, 0], [5, 6, 5]) as u16)
+    }
+}
+
+impl DepthPixel<3> for Bgr565 {
+    const BITS: [Option<u32>; 3] = [Some(5), Some(6), Some(5)];
+    fn to_samples(self) -> [Sample; 3] {
+        unpack(self.0 as u32, [0, 5, 11], [5, 6, 5])
+    }
+    fn from_samples(samples: [Sample; 3]) -> Self {
+        Self(pack(samples, [0, 5, 11], [5, 6, 5]) as u16)
+    }
+}
+
+impl DepthPixel<4> for Rgba4444 {
+    const BITS: [Option<u32>; 4] = [Some(4); 4];
+    fn to_samples(self) -> [Sample; 4] {
+        unpack(self.0 as u32, [12, 8, 4, 0], [4; 4])
+    }
+    fn from_samples(samples: [Sample; 4]) -> Self {
+        Self(pack(samples, [12, 8, 4, 0], [4; 4]) as u16)
+    }
+}
+
+impl DepthPixel<4> for Rgba5551 {
+    const BITS: [Option<u32>; 4] = [Some(5), Some(5), Some(5), Some(1)];
+    fn to_samples(self) -> [Sample; 4] {
+        unpack(self.0 as u32, [11, 6, 1, 0], [5, 5, 5, 1])
+    }
+    fn from_samples(samples: [Sample; 4]) -> Self {
+        Self(pack(samples, [11, 6, 1, 0], [5, 5, 5, 1]) as u16)
+    }
+}
+
+impl DepthPixel<4> for Rgb10A2 {
+    const BITS: [Option<u32>; 4] = [Some(10), Some(10), Some(10), Some(2)];
+    fn to_samples(self) -> [Sample; 4] {
+        unpack(self.0, [0, 10, 20, 30], [10, 10, 10, 2])
+    }
+    fn from_samples(samples: [Sample; 4]) -> Self {
+        Self(pack(samples, [0, 10, 20, 30], [10, 10, 10, 2]))
+    }
+}
+
+/// Triangular noise in `-1.0..1.0`, fixed for every position and channel.
+fn tpdf(x: usize, y: usize, channel: usize) -> f32 {
+    let mut state = ((x as u64) << 40) ^ ((y as u64) << 8) ^ channel as u64;
+    let mut uniform = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    };
+    uniform() - uniform()
+}
+
+/// Converts a pixel to another depth, adding triangular noise of one least significant bit of
+/// the target when `dither` is set and narrowing.
+pub fn convert_depth<P, Q, const N: usize>(pixel: P, dither: Option<[usize; 2]>) -> Q
+where
+    P: DepthPixel<N>,
+    Q: DepthPixel<N>,
+{
+    let samples = pixel.to_samples();
+    Q::from_samples(core::array::from_fn(|c| {
+        let noise = dither.map_or(0.0, |[x, y]| tpdf(x, y, c));
+        samples[c].convert(Q::BITS[c], noise)
+    }))
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]>,
+{
+    /// The image converted to another depth, with the same stride. Gaps between rows are zero.
+    pub fn convert_depth<Q, const N: usize>(&self, dither: bool) -> Image<Box<[Q]>, Q>
+    where
+        P: DepthPixel<N>,
+        Q: DepthPixel<N>,
+    {
+        let zero = Q::from_samples(Q::BITS.map(|bits| Sample::Float(0.0).convert(bits, 0.0)));
+        let len = if self.width() == 0 || self.height() == 0 {
+            0
+        } else {
+            self.max_index() + 1
+        };
+        let mut buf = vec![zero; len].into_boxed_slice();
+        for (y, row) in self.iter_rows().enumerate() {
+            let start = y * self.stride();
+            for (x, (dst, &pixel)) in buf[start..start + self.width()]
+                .iter_mut()
+                .zip(row)
+                .enumerate()
+            {
+                *dst = convert_depth(pixel, dither.then_some([x, y]));
+            }
+        }
+        unsafe {
+            Image::from_source_with_stride_unchecked(
+                self.width(),
+                self.height(),
+                self.stride(),
+                buf,
+            )
+        }
+    }
+    /// Converts into `target`, which may be a region of a larger image.
+    pub fn convert_depth_into<T, Q, const N: usize>(
+        &self,
+        target: &mut Image<T, Q>,
+        dither: bool,
+    ) -> Result<(), SizeMismatch>
+    where
+        T: AsRef<[Q]> + AsMut<[Q]>,
+        P: DepthPixel<N>,
+        Q: DepthPixel<N>,
+    {
+        if [self.width(), self.height()] != [target.width(), target.height()] {
+            return Err(SizeMismatch {
+                left: [self.width(), self.height()],
+                right: [target.width(), target.height()],
+            });
+        }
+        for (y, (src, dst)) in self.iter_rows().zip(target.iter_rows_mut()).enumerate() {
+            for (x, (&pixel, dst)) in src.iter().zip(dst).enumerate() {
+                *dst = convert_depth(pixel, dither.then_some([x, y]));
+            }
+        }
+        Ok(())
+    }
+}
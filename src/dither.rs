@@ -0,0 +1,402 @@
+use crate::{
+    quantize::{nearest_index, Quantizable, QuantizeOptions},
+    Image,
+};
+
+/// What dithered pixels are reduced to.
+pub enum DitherTarget<'a, P> {
+    /// The nearest entry of a palette, under the metric of the options.
+    Palette(&'a [P], QuantizeOptions),
+    /// Every channel rounded to the given number of bits, then expanded back to 8 bits.
+    Bits(u32),
+}
+
+impl<'a, P: Quantizable> DitherTarget<'a, P> {
+    fn quantize(&self, color: [f32; 4]) -> [u8; 4] {
+        let clamped = color.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        match self {
+            DitherTarget::Palette(palette, options) => {
+                palette[nearest_index(palette, P::from_rgba(clamped), options)].to_rgba()
+            }
+            DitherTarget::Bits(bits) => {
+                let levels = (1u32 << bits.clamp(&1, &8)) - 1;
+                clamped.map(|c| {
+                    let level = (c as u32 * levels + 127) / 255;
+                    ((level * 255 + levels / 2) / levels) as u8
+                })
+            }
+        }
+    }
+    /// The typical distance between neighbouring output levels, used to scale threshold maps.
+    fn spread(&self) -> f32 {
+        match self {
+            DitherTarget::Palette(palette, _) => 255.0 / (palette.len() as f32).cbrt().max(1.0),
+            DitherTarget::Bits(bits) => 255.0 / ((1u32 << bits.clamp(&1, &8)) - 1) as f32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffusionKernel {
+    FloydSteinberg,
+    JarvisJudiceNinke,
+    Stucki,
+    Atkinson,
+    Sierra,
+}
+
+impl DiffusionKernel {
+    /// `(dx, dy, weight)` taps and the divisor of the weights.
+    pub const fn taps(self) -> (&'static [(isize, usize, f32)], f32) {
+        match self {
+            DiffusionKernel::FloydSteinberg => {
+                (&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0)
+            }
+            DiffusionKernel::JarvisJudiceNinke => (
+                &[
+                    (1, 0, 7.0),
+                    (2, 0, 5.0),
+                    (-2, 1, 3.0),
+                    (-1, 1, 5.0),
+                    (0, 1, 7.0),
+                    (1, 1, 5.0),
+                    (2, 1, 3.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 3.0),
+                    (0, 2, 5.0),
+                    (1, 2, 3.0),
+                    (2, 2, 1.0),
+                ],
+                48.0,
+            ),
+            DiffusionKernel::Stucki => (
+                &[
+                    (1, 0, 8.0),
+                    (2, 0, 4.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 8.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 4.0),
+                    (1, 2, 2.0),
+                    (2, 2, 1.0),
+                ],
+                42.0,
+            ),
+            DiffusionKernel::Atkinson => (
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            ),
+            DiffusionKernel::Sierra => (
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            ),
+        }
+    }
+}
+
+/// A square, tiled map of thresholds in `0.0..1.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdMap {
+    size: usize,
+    values: Box<[f32]>,
+}
+
+impl ThresholdMap {
+    fn from_ranks(size: usize, ranks: Vec<usize>) -> Self {
+        let cells = (size * size) as f32;
+        Self {
+            size,
+            values: ranks
+                .into_iter()
+                .map(|r| (r as f32 + 0.5) / cells)
+                .collect(),
+        }
+    }
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.values[x % self.size + (y % self.size) * self.size]
+    }
+    /// The `2^order` by `2^order` Bayer matrix, with `order` clamped to 8.
+    pub fn bayer(order: u32) -> Self {
+        let order = order.min(8);
+        let size = 1 << order;
+        let mut ranks = vec![0; size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let mut rank = 0;
+                for bit in 0..order {
+                    let (bx, by) = ((x >> bit) & 1, (y >> bit) & 1);
+                    rank |= ((bx ^ by) << 1 | by) << (2 * (order - 1 - bit));
+                }
+                ranks[x + y * size] = rank;
+            }
+        }
+        Self::from_ranks(size, ranks)
+    }
+    /// A blue noise map generated with the void-and-cluster method.
+    pub fn blue_noise(size: usize) -> Self {
+        const SIGMA: f32 = 1.5;
+        let size = size.max(2);
+        let cells = size * size;
+        let wrap = |d: usize| d.min(size - d) as f32;
+        let kernel: Vec<f32> = (0..cells)
+            .map(|i| {
+                let (dx, dy) = (wrap(i % size), wrap(i / size));
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+        let toggle = |energy: &mut [f32], i: usize, sign: f32| {
+            let (x, y) = (i % size, i / size);
+            for (j, e) in energy.iter_mut().enumerate() {
+                let (dx, dy) = ((j % size + size - x) % size, (j / size + size - y) % size);
+                *e += sign * kernel[dx + dy * size];
+            }
+        };
+        let extreme = |energy: &[f32], pattern: &[bool], value: bool, max: bool| {
+            (0..cells)
+                .filter(|&i| pattern[i] == value)
+                .fold(None, |best: Option<usize>, i| match best {
+                    Some(b) if (energy[b] >= energy[i]) == max => Some(b),
+                    _ => Some(i),
+                })
+                .unwrap()
+        };
+
+        // Seed with a deterministic sparse pattern, then relax it into the initial binary pattern.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut pattern = vec![false; cells];
+        let mut energy = vec![0.0; cells];
+        let ones = (cells / 10).max(1);
+        let mut placed = 0;
+        while placed < ones {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let i = (state % cells as u64) as usize;
+            if !pattern[i] {
+                pattern[i] = true;
+                toggle(&mut energy, i, 1.0);
+                placed += 1;
+            }
+        }
+        loop {
+            let cluster = extreme(&energy, &pattern, true, true);
+            pattern[cluster] = false;
+            toggle(&mut energy, cluster, -1.0);
+            let void = extreme(&energy, &pattern, false, false);
+            pattern[void] = true;
+            toggle(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; cells];
+        let (initial, initial_energy) = (pattern.clone(), energy.clone());
+        for rank in (0..ones).rev() {
+            let cluster = extreme(&energy, &pattern, true, true);
+            pattern[cluster] = false;
+            toggle(&mut energy, cluster, -1.0);
+            ranks[cluster] = rank;
+        }
+        let (mut pattern, mut energy) = (initial, initial_energy);
+        for rank in ones..cells {
+            let void = extreme(&energy, &pattern, false, false);
+            pattern[void] = true;
+            toggle(&mut energy, void, 1.0);
+            ranks[void] = rank;
+        }
+        Self::from_ranks(size, ranks)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DitherMethod {
+    /// Error diffusion, going back and forth on alternate rows when `serpentine` is set.
+    ErrorDiffusion {
+        kernel: DiffusionKernel,
+        serpentine: bool,
+    },
+    Ordered(ThresholdMap),
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: Quantizable,
+{
+    /// Reduces every pixel to `target` in place, dithering with `method`. An empty palette leaves
+    /// the image unchanged.
+    pub fn dither(&mut self, target: &DitherTarget<P>, method: &DitherMethod) {
+        if matches!(target, DitherTarget::Palette(palette, _) if palette.is_empty()) {
+            return;
+        }
+        match method {
+            DitherMethod::ErrorDiffusion { kernel, serpentine } => {
+                self.diffuse(target, *kernel, *serpentine)
+            }
+            DitherMethod::Ordered(map) => {
+                let spread = target.spread();
+                for (y, row) in self.iter_rows_mut().enumerate() {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let offset = (map.get(x, y) - 0.5) * spread;
+                        let color = pixel.to_rgba().map(|c| c as f32 + offset);
+                        *pixel = P::from_rgba(target.quantize(color));
+                    }
+                }
+            }
+        }
+    }
+
+    fn diffuse(&mut self, target: &DitherTarget<P>, kernel: DiffusionKernel, serpentine: bool) {
+        let (taps, divisor) = kernel.taps();
+        let width = self.width();
+        let rows = taps.iter().map(|&(_, dy, _)| dy).max().unwrap_or(0) + 1;
+        let mut errors = vec![vec![[0.0f32; 4]; width]; rows];
+        for (y, row) in self.iter_rows_mut().enumerate() {
+            let reverse = serpentine && y % 2 == 1;
+            for step in 0..width {
+                let x = if reverse { width - 1 - step } else { step };
+                let original = row[x].to_rgba();
+                let mut color = [0.0; 4];
+                for c in 0..4 {
+                    color[c] = original[c] as f32 + errors[0][x][c];
+                }
+                let quantized = target.quantize(color);
+                row[x] = P::from_rgba(quantized);
+                for &(dx, dy, weight) in taps {
+                    let dx = if reverse { -dx } else { dx };
+                    let Some(tx) = x.checked_add_signed(dx).filter(|&tx| tx < width) else {
+                        continue;
+                    };
+                    for c in 0..4 {
+                        errors[dy][tx][c] += (color[c] - quantized[c] as f32) * weight / divisor;
+                    }
+                }
+            }
+            errors.rotate_left(1);
+            errors[rows - 1].fill([0.0; 4]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::Rgb;
+
+    const KERNELS: [DiffusionKernel; 5] = [
+        DiffusionKernel::FloydSteinberg,
+        DiffusionKernel::JarvisJudiceNinke,
+        DiffusionKernel::Stucki,
+        DiffusionKernel::Atkinson,
+        DiffusionKernel::Sierra,
+    ];
+
+    fn methods() -> Vec<DitherMethod> {
+        let mut methods: Vec<_> = KERNELS
+            .into_iter()
+            .flat_map(|kernel| {
+                [false, true].map(|serpentine| DitherMethod::ErrorDiffusion { kernel, serpentine })
+            })
+            .collect();
+        methods.push(DitherMethod::Ordered(ThresholdMap::bayer(3)));
+        methods.push(DitherMethod::Ordered(ThresholdMap::blue_noise(8)));
+        methods
+    }
+
+    fn gradient() -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+        let pixels = (0..24 * 9).map(|i| {
+            let (x, y) = ((i % 24) as u8, (i / 24) as u8);
+            Rgb {
+                r: x * 11,
+                g: y * 28,
+                b: 255 - x * 5 - y * 7,
+            }
+        });
+        Image::from_source(24, 9, pixels.collect()).ok().unwrap()
+    }
+
+    #[test]
+    fn bayer() {
+        let ranks = |map: &ThresholdMap| {
+            let cells = (map.size() * map.size()) as f32;
+            (0..map.size() * map.size())
+                .map(|i| (map.get(i % map.size(), i / map.size()) * cells - 0.5).round() as usize)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranks(&ThresholdMap::bayer(0)), [0]);
+        assert_eq!(ranks(&ThresholdMap::bayer(1)), [0, 2, 3, 1]);
+        assert_eq!(
+            ranks(&ThresholdMap::bayer(2)),
+            [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5]
+        );
+        let mut sorted = ranks(&ThresholdMap::bayer(4));
+        sorted.sort_unstable();
+        assert!(sorted.into_iter().eq(0..256));
+        // Tiled.
+        let map = ThresholdMap::bayer(1);
+        assert_eq!(map.get(3, 2), map.get(1, 0));
+        assert_eq!(ThresholdMap::bayer(40), ThresholdMap::bayer(8));
+    }
+
+    #[test]
+    fn only_palette_colors() {
+        let palette = [[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 90, 200]].map(Rgb::from);
+        let target = DitherTarget::Palette(&palette, QuantizeOptions::new(4));
+        for method in methods() {
+            let mut image = gradient();
+            image.dither(&target, &method);
+            for (pos, pixel) in image.iter() {
+                assert!(palette.contains(pixel), "{method:?} {pos:?} {pixel:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn one_bit_is_black_and_white() {
+        for method in methods() {
+            let mut image = gradient();
+            image.dither(&DitherTarget::Bits(1), &method);
+            let mut channels = image.iter().flat_map(|(_, &pixel)| <[u8; 3]>::from(pixel));
+            assert!(
+                channels.into_iter().all(|c| c == 0 || c == 255),
+                "{method:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_palette() {
+        let target = DitherTarget::Palette(&[], QuantizeOptions::new(2));
+        for method in methods() {
+            let mut image = gradient();
+            image.dither(&target, &method);
+            assert_eq!(image, gradient());
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use crate::{
+    alpha::PremultipliedRgba,
+    depth::{Depth, Sample},
+    pixel::{Luma, LumaA, Rgb, Rgba},
+    ycbcr::YCbCrMatrix,
+    Image, ImageIndex,
+};
+use core::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    Luma,
+    LumaA,
+    Rgb,
+    Rgba,
+}
+
+impl ChannelLayout {
+    pub const fn channels(self) -> usize {
+        match self {
+            ChannelLayout::Luma => 1,
+            ChannelLayout::LumaA => 2,
+            ChannelLayout::Rgb => 3,
+            ChannelLayout::Rgba => 4,
+        }
+    }
+    pub const fn has_alpha(self) -> bool {
+        matches!(self, ChannelLayout::LumaA | ChannelLayout::Rgba)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelType {
+    U8,
+    U16,
+    F32,
+}
+
+impl ChannelType {
+    pub const fn size(self) -> usize {
+        match self {
+            ChannelType::U8 => 1,
+            ChannelType::U16 => 2,
+            ChannelType::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    None,
+    Straight,
+    Premultiplied,
+}
+
+/// Describes the pixels of an image whose type is only known at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelFormat {
+    pub layout: ChannelLayout,
+    pub channel_type: ChannelType,
+    /// Significant bits per channel, which may be fewer than `channel_type` holds.
+    pub bits: u32,
+    pub alpha: AlphaMode,
+}
+
+impl PixelFormat {
+    pub const fn bytes_per_pixel(&self) -> usize {
+        self.layout.channels() * self.channel_type.size()
+    }
+}
+
+/// Scalars that a [`DynamicImage`] can hold.
+pub trait DynamicChannel: Depth {
+    const TYPE: ChannelType;
+    const DEPTH: u32;
+}
+
+impl DynamicChannel for u8 {
+    const TYPE: ChannelType = ChannelType::U8;
+    const DEPTH: u32 = 8;
+}
+impl DynamicChannel for u16 {
+    const TYPE: ChannelType = ChannelType::U16;
+    const DEPTH: u32 = 16;
+}
+impl DynamicChannel for f32 {
+    const TYPE: ChannelType = ChannelType::F32;
+    const DEPTH: u32 = 32;
+}
+
+fn unit<T: Depth>(value: T) -> f32 {
+    value.to_sample().to_unit()
+}
+
+fn from_unit<T: Depth>(value: f32) -> T {
+    T::from_sample(Sample::Float(value).convert(T::BITS, 0.0))
+}
+
+fn luma(rgba: Rgba<f32>) -> f32 {
+    let [kr, kg, kb] = YCbCrMatrix::Bt709.luma_coefficients();
+    kr as f32 * rgba.r + kg as f32 * rgba.g + kb as f32 * rgba.b
+}
+
+/// Pixel types of the variants of [`DynamicImage`].
+pub trait DynamicPixel: Copy {
+    const FORMAT: PixelFormat;
+    /// Straight alpha, with every channel in `0.0..=1.0`.
+    fn to_rgba(self) -> Rgba<f32>;
+    fn from_rgba(rgba: Rgba<f32>) -> Self;
+    fn into_dynamic(image: Image<Box<[Self]>, Self>) -> DynamicImage;
+    fn from_dynamic(image: &DynamicImage) -> Option<&Image<Box<[Self]>, Self>>;
+    fn from_dynamic_mut(image: &mut DynamicImage) -> Option<&mut Image<Box<[Self]>, Self>>;
+    fn try_from_dynamic(image: DynamicImage) -> Result<Image<Box<[Self]>, Self>, DynamicImage>;
+}
+
+/// Runs the expression once for every variant, with `$image` bound to the inner image.
+macro_rules! dispatch {
+    ($value:expr, $image:ident => $body:expr) => {
+        match $value {
+            DynamicImage::Luma8($image) => $body,
+            DynamicImage::LumaA8($image) => $body,
+            DynamicImage::Rgb8($image) => $body,
+            DynamicImage::Rgba8($image) => $body,
+            DynamicImage::Luma16($image) => $body,
+            DynamicImage::LumaA16($image) => $body,
+            DynamicImage::Rgb16($image) => $body,
+            DynamicImage::Rgba16($image) => $body,
+            DynamicImage::Luma32F($image) => $body,
+            DynamicImage::LumaA32F($image) => $body,
+            DynamicImage::Rgb32F($image) => $body,
+            DynamicImage::Rgba32F($image) => $body,
+            DynamicImage::PremultipliedRgba8($image) => $body,
+            DynamicImage::PremultipliedRgba16($image) => $body,
+            DynamicImage::PremultipliedRgba32F($image) => $body,
+        }
+    };
+}
+
+/// Implements [`DynamicPixel`] for the pixel type of a variant.
+macro_rules! dynamic_pixel {
+    ($pixel:ty, $variant:ident, $layout:ident, $alpha:ident) => {
+        impl DynamicPixel for $pixel {
+            const FORMAT: PixelFormat = PixelFormat {
+                layout: ChannelLayout::$layout,
+                channel_type: <Channel<$pixel> as DynamicChannel>::TYPE,
+                bits: <Channel<$pixel> as DynamicChannel>::DEPTH,
+                alpha: AlphaMode::$alpha,
+            };
+            fn to_rgba(self) -> Rgba<f32> {
+                ToRgba::to_rgba(self)
+            }
+            fn from_rgba(rgba: Rgba<f32>) -> Self {
+                ToRgba::from_rgba(rgba)
+            }
+            fn into_dynamic(image: Image<Box<[Self]>, Self>) -> DynamicImage {
+                DynamicImage::$variant(image)
+            }
+            fn from_dynamic(image: &DynamicImage) -> Option<&Image<Box<[Self]>, Self>> {
+                match image {
+                    DynamicImage::$variant(image) => Some(image),
+                    _ => None,
+                }
+            }
+            fn from_dynamic_mut(image: &mut DynamicImage) -> Option<&mut Image<Box<[Self]>, Self>> {
+                match image {
+                    DynamicImage::$variant(image) => Some(image),
+                    _ => None,
+                }
+            }
+            fn try_from_dynamic(
+                image: DynamicImage,
+            ) -> Result<Image<Box<[Self]>, Self>, DynamicImage> {
+                match image {
+                    DynamicImage::$variant(image) => Ok(image),
+                    image => Err(image),
+                }
+            }
+        }
+    };
+}
+
+/// Conversion to and from straight unit RGBA, shared by every channel type.
+trait ToRgba: Copy {
+    type Channel;
+    fn to_rgba(self) -> Rgba<f32>;
+    fn from_rgba(rgba: Rgba<f32>) -> Self;
+}
+
+type Channel<P> = <P as ToRgba>::Channel;
+
+impl<T: Depth> ToRgba for Luma<T> {
+    type Channel = T;
+    fn to_rgba(self) -> Rgba<f32> {
+        let l = unit(self.l);
+        Rgba {
+            r: l,
+            g: l,
+            b: l,
+            a: 1.0,
+        }
+    }
+    fn from_rgba(rgba: Rgba<f32>) -> Self {
+        Luma {
+            l: from_unit(luma(rgba)),
+        }
+    }
+}
+
+impl<T: Depth> ToRgba for LumaA<T> {
+    type Channel = T;
+    fn to_rgba(self) -> Rgba<f32> {
+        let l = unit(self.l);
+        Rgba {
+            r: l,
+            g: l,
+            b: l,
+            a: unit(self.a),
+        }
+    }
+    fn from_rgba(rgba: Rgba<f32>) -> Self {
+        LumaA {
+            l: from_unit(luma(rgba)),
+            a: from_unit(rgba.a),
+        }
+    }
+}
+
+impl<T: Depth> ToRgba for Rgb<T> {
+    type Channel = T;
+    fn to_rgba(self) -> Rgba<f32> {
+        Rgba {
+            r: unit(self.r),
+            g: unit(self.g),
+            b: unit(self.b),
+            a: 1.0,
+        }
+    }
+    fn from_rgba(rgba: Rgba<f32>) -> Self {
+        Rgb {
+            r: from_unit(rgba.r),
+            g: from_unit(rgba.g),
+            b: from_unit(rgba.b),
+        }
+    }
+}
+
+impl<T: Depth> ToRgba for Rgba<T> {
+    type Channel = T;
+    fn to_rgba(self) -> Rgba<f32> {
+        [self.r, self.g, self.b, self.a].map(unit).into()
+    }
+    fn from_rgba(rgba: Rgba<f32>) -> Self {
+        <[f32; 4]>::from(rgba).map(from_unit).into()
+    }
+}
+
+impl<T: Depth> ToRgba for PremultipliedRgba<T> {
+    type Channel = T;
+    fn to_rgba(self) -> Rgba<f32> {
+        PremultipliedRgba {
+            r: unit(self.r),
+            g: unit(self.g),
+            b: unit(self.b),
+            a: unit(self.a),
+        }
+        .unpremultiply()
+    }
+    fn from_rgba(rgba: Rgba<f32>) -> Self {
+        let p = rgba.premultiply();
+        PremultipliedRgba {
+            r: from_unit(p.r),
+            g: from_unit(p.g),
+            b: from_unit(p.b),
+            a: from_unit(p.a),
+        }
+    }
+}
+
+dynamic_pixel!(Luma<u8>, Luma8, Luma, None);
+dynamic_pixel!(LumaA<u8>, LumaA8, LumaA, Straight);
+dynamic_pixel!(Rgb<u8>, Rgb8, Rgb, None);
+dynamic_pixel!(Rgba<u8>, Rgba8, Rgba, Straight);
+dynamic_pixel!(Luma<u16>, Luma16, Luma, None);
+dynamic_pixel!(LumaA<u16>, LumaA16, LumaA, Straight);
+dynamic_pixel!(Rgb<u16>, Rgb16, Rgb, None);
+dynamic_pixel!(Rgba<u16>, Rgba16, Rgba, Straight);
+dynamic_pixel!(Luma<f32>, Luma32F, Luma, None);
+dynamic_pixel!(LumaA<f32>, LumaA32F, LumaA, Straight);
+dynamic_pixel!(Rgb<f32>, Rgb32F, Rgb, None);
+dynamic_pixel!(Rgba<f32>, Rgba32F, Rgba, Straight);
+dynamic_pixel!(
+    PremultipliedRgba<u8>,
+    PremultipliedRgba8,
+    Rgba,
+    Premultiplied
+);
+dynamic_pixel!(
+    PremultipliedRgba<u16>,
+    PremultipliedRgba16,
+    Rgba,
+    Premultiplied
+);
+dynamic_pixel!(
+    PremultipliedRgba<f32>,
+    PremultipliedRgba32F,
+    Rgba,
+    Premultiplied
+);
+
+/// An owned image whose pixel type is chosen at runtime.
+#[derive(Debug)]
+pub enum DynamicImage {
+    Luma8(Image<Box<[Luma<u8>]>, Luma<u8>>),
+    LumaA8(Image<Box<[LumaA<u8>]>, LumaA<u8>>),
+    Rgb8(Image<Box<[Rgb<u8>]>, Rgb<u8>>),
+    Rgba8(Image<Box<[Rgba<u8>]>, Rgba<u8>>),
+    Luma16(Image<Box<[Luma<u16>]>, Luma<u16>>),
+    LumaA16(Image<Box<[LumaA<u16>]>, LumaA<u16>>),
+    Rgb16(Image<Box<[Rgb<u16>]>, Rgb<u16>>),
+    Rgba16(Image<Box<[Rgba<u16>]>, Rgba<u16>>),
+    Luma32F(Image<Box<[Luma<f32>]>, Luma<f32>>),
+    LumaA32F(Image<Box<[LumaA<f32>]>, LumaA<f32>>),
+    Rgb32F(Image<Box<[Rgb<f32>]>, Rgb<f32>>),
+    Rgba32F(Image<Box<[Rgba<f32>]>, Rgba<f32>>),
+    PremultipliedRgba8(Image<Box<[PremultipliedRgba<u8>]>, PremultipliedRgba<u8>>),
+    PremultipliedRgba16(Image<Box<[PremultipliedRgba<u16>]>, PremultipliedRgba<u16>>),
+    PremultipliedRgba32F(Image<Box<[PremultipliedRgba<f32>]>, PremultipliedRgba<f32>>),
+}
+
+/// Generic code run on whichever variant a [`DynamicImage`] holds.
+pub trait ImageVisitor {
+    type Output;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel;
+}
+
+pub trait ImageVisitorMut {
+    type Output;
+    fn visit_mut<P: DynamicPixel>(self, image: &mut Image<Box<[P]>, P>) -> Self::Output;
+}
+
+struct Convert<P>(core::marker::PhantomData<P>);
+
+impl<Q: DynamicPixel> ImageVisitor for Convert<Q> {
+    type Output = Image<Box<[Q]>, Q>;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> Self::Output
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        image.map(|&pixel| Q::from_rgba(pixel.to_rgba()))
+    }
+}
+
+struct Crop;
+
+impl ImageVisitor for Crop {
+    type Output = DynamicImage;
+    fn visit<Source, P>(self, image: &Image<Source, P>) -> DynamicImage
+    where
+        Source: AsRef<[P]>,
+        P: DynamicPixel,
+    {
+        P::into_dynamic(image.map(|&pixel| pixel))
+    }
+}
+
+impl DynamicImage {
+    pub fn format(&self) -> PixelFormat {
+        fn format<P: DynamicPixel>(_: &Image<Box<[P]>, P>) -> PixelFormat {
+            P::FORMAT
+        }
+        dispatch!(self, image => format(image))
+    }
+    pub fn width(&self) -> usize {
+        dispatch!(self, image => image.width())
+    }
+    pub fn height(&self) -> usize {
+        dispatch!(self, image => image.height())
+    }
+    pub fn visit<V: ImageVisitor>(&self, visitor: V) -> V::Output {
+        dispatch!(self, image => visitor.visit(image))
+    }
+    pub fn visit_mut<V: ImageVisitorMut>(&mut self, visitor: V) -> V::Output {
+        dispatch!(self, image => visitor.visit_mut(image))
+    }
+    /// Runs `visitor` on a region, without copying it.
+    pub fn visit_region<V: ImageVisitor, I: ImageIndex>(
+        &self,
+        range: Range<I>,
+        visitor: V,
+    ) -> Result<V::Output, crate::Error<[(); 0], ()>> {
+        dispatch!(self, image => match image.region(range) {
+            Ok(region) => Ok(visitor.visit(&region)),
+            Err(err) => Err(erase(err)),
+        })
+    }
+    /// Copies a region into a new image of the same format.
+    pub fn crop<I: ImageIndex>(&self, range: Range<I>) -> Result<Self, crate::Error<[(); 0], ()>> {
+        self.visit_region(range, Crop)
+    }
+    pub fn as_image<P: DynamicPixel>(&self) -> Option<&Image<Box<[P]>, P>> {
+        P::from_dynamic(self)
+    }
+    pub fn as_image_mut<P: DynamicPixel>(&mut self) -> Option<&mut Image<Box<[P]>, P>> {
+        P::from_dynamic_mut(self)
+    }
+    /// Converts to any pixel type, copying when it already is that type.
+    pub fn to_image<P: DynamicPixel>(&self) -> Image<Box<[P]>, P> {
+        match self.as_image::<P>() {
+            Some(image) => image.map(|&pixel| pixel),
+            None => self.visit(Convert(core::marker::PhantomData)),
+        }
+    }
+    /// The inner image when it already has pixel type `P`, or `self` back otherwise.
+    pub fn into_image<P: DynamicPixel>(self) -> Result<Image<Box<[P]>, P>, Self> {
+        P::try_from_dynamic(self)
+    }
+    /// Converts to the pixel type described by `format`, ignoring its `bits`.
+    pub fn convert(&self, format: PixelFormat) -> Option<Self> {
+        use AlphaMode as A;
+        use ChannelLayout as L;
+        use ChannelType::*;
+        Some(match (format.layout, format.channel_type, format.alpha) {
+            (L::Luma, U8, A::None) => Self::Luma8(self.to_image()),
+            (L::LumaA, U8, A::Straight) => Self::LumaA8(self.to_image()),
+            (L::Rgb, U8, A::None) => Self::Rgb8(self.to_image()),
+            (L::Rgba, U8, A::Straight) => Self::Rgba8(self.to_image()),
+            (L::Luma, U16, A::None) => Self::Luma16(self.to_image()),
+            (L::LumaA, U16, A::Straight) => Self::LumaA16(self.to_image()),
+            (L::Rgb, U16, A::None) => Self::Rgb16(self.to_image()),
+            (L::Rgba, U16, A::Straight) => Self::Rgba16(self.to_image()),
+            (L::Luma, F32, A::None) => Self::Luma32F(self.to_image()),
+            (L::LumaA, F32, A::Straight) => Self::LumaA32F(self.to_image()),
+            (L::Rgb, F32, A::None) => Self::Rgb32F(self.to_image()),
+            (L::Rgba, F32, A::Straight) => Self::Rgba32F(self.to_image()),
+            (L::Rgba, U8, A::Premultiplied) => Self::PremultipliedRgba8(self.to_image()),
+            (L::Rgba, U16, A::Premultiplied) => Self::PremultipliedRgba16(self.to_image()),
+            (L::Rgba, F32, A::Premultiplied) => Self::PremultipliedRgba32F(self.to_image()),
+            _ => return None,
+        })
+    }
+}
+
+impl<P: DynamicPixel> From<Image<Box<[P]>, P>> for DynamicImage {
+    fn from(image: Image<Box<[P]>, P>) -> Self {
+        P::into_dynamic(image)
+    }
+}
+
+/// Region errors carry no source, so they can be returned for every variant alike.
+fn erase<Source: AsRef<[P]>, P>(err: crate::Error<Source, P>) -> crate::Error<[(); 0], ()> {
+    match err {
+        crate::Error::IndexOutOfRange(err) => crate::Error::IndexOutOfRange(err),
+        crate::Error::PositionOutOfRange(err) => crate::Error::PositionOutOfRange(err),
+        crate::Error::SourceTooSmall(_) => unreachable!("regions never check the source size"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image<P>(pixels: impl IntoIterator<Item = P>) -> DynamicImage
+    where
+        P: DynamicPixel,
+    {
+        let pixels: Box<[P]> = pixels.into_iter().collect();
+        let width = pixels.len();
+        Image::from_source(width, 1, pixels).ok().unwrap().into()
+    }
+
+    fn pixels<P: DynamicPixel>(image: &DynamicImage) -> Vec<P> {
+        let image = image.as_image::<P>().unwrap();
+        image.iter().map(|(_, &pixel)| pixel).collect()
+    }
+
+    #[test]
+    fn depths() {
+        let rgb8 = image([[0, 128, 255], [18, 51, 1]].map(Rgb::<u8>::from));
+        let rgb16 = rgb8.convert(Rgb::<u16>::FORMAT).unwrap();
+        let wide = pixels::<Rgb<u16>>(&rgb16).into_iter().map(<[u16; 3]>::from);
+        assert!(wide.eq([[0, 0x8080, 0xffff], [0x1212, 0x3333, 0x0101]]));
+        let back = pixels::<Rgb<u8>>(&rgb16.convert(Rgb::<u8>::FORMAT).unwrap());
+        assert_eq!(back, pixels::<Rgb<u8>>(&rgb8));
+
+        let narrow = image([[0x1234, 0x7f7f, 0x8080]].map(Rgb::<u16>::from)).to_image::<Rgb<u8>>();
+        assert_eq!(narrow[[0, 0]], Rgb::from([0x12, 0x7f, 0x80]));
+
+        let float = pixels::<Rgb<f32>>(&rgb8.convert(Rgb::<f32>::FORMAT).unwrap());
+        assert_eq!(float[0], Rgb::from([0.0, 128.0 / 255.0, 1.0]));
+        assert_eq!(float[1].g, 0.2);
+        let clamped = image([[-0.5, 0.5, 2.0]].map(Rgb::<f32>::from)).to_image::<Rgb<u8>>();
+        assert_eq!(clamped[[0, 0]], Rgb::from([0, 128, 255]));
+    }
+
+    #[test]
+    fn layouts() {
+        let rgba = image([[255, 0, 0, 255], [10, 20, 30, 40]].map(Rgba::<u8>::from));
+        assert_eq!(rgba.format(), Rgba::<u8>::FORMAT);
+        // Luma follows BT.709, and alpha is dropped without compositing.
+        let luma = pixels::<Luma<u8>>(&rgba.convert(Luma::<u8>::FORMAT).unwrap());
+        assert_eq!(luma, [Luma { l: 54 }, Luma { l: 19 }]);
+        let luma_alpha = pixels::<LumaA<u8>>(&rgba.convert(LumaA::<u8>::FORMAT).unwrap());
+        assert_eq!(luma_alpha[1], LumaA { l: 19, a: 40 });
+        let rgb = pixels::<Rgb<u8>>(&rgba.convert(Rgb::<u8>::FORMAT).unwrap());
+        assert_eq!(rgb[1], Rgb::from([10, 20, 30]));
+
+        let gray = image([LumaA {
+            l: 0x1234u16,
+            a: 0x8000,
+        }]);
+        let expanded = pixels::<Rgba<u16>>(&gray.convert(Rgba::<u16>::FORMAT).unwrap());
+        assert_eq!(expanded, [Rgba::from([0x1234, 0x1234, 0x1234, 0x8000])]);
+        let opaque = image([Luma { l: 7u8 }]).to_image::<Rgba<u8>>();
+        assert_eq!(opaque[[0, 0]], Rgba::from([7, 7, 7, 255]));
+    }
+
+    #[test]
+    fn premultiplied() {
+        let rgba = image([[200, 100, 50, 128], [9, 9, 9, 0]].map(Rgba::<u8>::from));
+        let premultiplied = rgba.convert(PremultipliedRgba::<u8>::FORMAT).unwrap();
+        assert_eq!(premultiplied.format().alpha, AlphaMode::Premultiplied);
+        let stored = pixels::<PremultipliedRgba<u8>>(&premultiplied);
+        assert_eq!(
+            [stored[0].r, stored[0].g, stored[0].b, stored[0].a],
+            [100, 50, 25, 128]
+        );
+        assert_eq!([stored[1].r, stored[1].a], [0, 0]);
+        let straight = pixels::<Rgba<u8>>(&premultiplied.convert(Rgba::<u8>::FORMAT).unwrap());
+        // Red lost a bit of precision to premultiplying.
+        assert_eq!(straight[0], Rgba::from([199, 100, 50, 128]));
+    }
+
+    #[test]
+    fn invalid_formats_and_variants() {
+        let rgb = image([Rgb::from([1u8, 2, 3])]);
+        let mut format = Rgb::<u8>::FORMAT;
+        format.alpha = AlphaMode::Straight;
+        assert!(rgb.convert(format).is_none());
+        format.alpha = AlphaMode::Premultiplied;
+        assert!(rgb.convert(format).is_none());
+        // `bits` is ignored.
+        format = Rgb::<u8>::FORMAT;
+        format.bits = 5;
+        assert!(rgb.convert(format).unwrap().as_image::<Rgb<u8>>().is_some());
+
+        assert!(rgb.as_image::<Rgba<u8>>().is_none());
+        let rgb = rgb.into_image::<Rgb<u16>>().unwrap_err();
+        assert_eq!(
+            rgb.into_image::<Rgb<u8>>().unwrap()[[0, 0]],
+            Rgb::from([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn crop() {
+        let pixels: Box<[Luma<u16>]> = (0..12).map(|l| Luma { l }).collect();
+        let image = DynamicImage::from(Image::from_source(4, 3, pixels).ok().unwrap());
+        let cropped = image.crop([1, 1]..[3, 2]).unwrap();
+        assert_eq!([cropped.width(), cropped.height()], [2, 1]);
+        let cropped = cropped.as_image::<Luma<u16>>().unwrap();
+        assert!(cropped.iter().map(|(_, p)| p.l).eq([5, 6]));
+        assert!(image.crop([1, 1]..[5, 2]).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    alpha::{PremultipliedLumaA, PremultipliedRgba},
+    error::SourceTooSmall,
+    pixel::{Luma, LumaA, Rgb, Rgba},
+    ycbcr::YCbCr,
+    Image,
+};
+use core::mem::size_of;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Self = Endian::Little;
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Self = Endian::Big;
+}
+
+/// Pixels laid out as a sequence of scalars that are `SCALAR_SIZE` bytes each.
+///
+/// Byte order conversion reverses every scalar independently.
+///
+/// # Safety
+/// `size_of::<Self>()` must be a multiple of `SCALAR_SIZE`, with no padding bytes, and every bit
+/// pattern must be a valid value.
+pub unsafe trait ScalarLayout: Copy {
+    const SCALAR_SIZE: usize;
+}
+
+unsafe impl ScalarLayout for u8 {
+    const SCALAR_SIZE: usize = 1;
+}
+unsafe impl ScalarLayout for i8 {
+    const SCALAR_SIZE: usize = 1;
+}
+unsafe impl ScalarLayout for u16 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for i16 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for u32 {
+    const SCALAR_SIZE: usize = 4;
+}
+unsafe impl ScalarLayout for i32 {
+    const SCALAR_SIZE: usize = 4;
+}
+unsafe impl ScalarLayout for f32 {
+    const SCALAR_SIZE: usize = 4;
+}
+unsafe impl ScalarLayout for u64 {
+    const SCALAR_SIZE: usize = 8;
+}
+unsafe impl ScalarLayout for f64 {
+    const SCALAR_SIZE: usize = 8;
+}
+
+unsafe impl<T: ScalarLayout, const N: usize> ScalarLayout for [T; N] {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for Luma<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for LumaA<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for Rgb<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for Rgba<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for YCbCr<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for PremultipliedRgba<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+unsafe impl<T: ScalarLayout> ScalarLayout for PremultipliedLumaA<T> {
+    const SCALAR_SIZE: usize = T::SCALAR_SIZE;
+}
+
+impl<Source, Pixel> Image<Source, Pixel>
+where
+    Source: AsRef<[Pixel]>,
+    Pixel: ScalarLayout,
+{
+    /// Every row without its stride, with every scalar in the given byte order.
+    pub fn to_bytes(&self, endian: Endian) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.width() * self.height() * size_of::<Pixel>());
+        for row in self.iter_rows() {
+            let start = bytes.len();
+            bytes.extend_from_slice(unsafe {
+                core::slice::from_raw_parts(row.as_ptr().cast::<u8>(), size_of_val(row))
+            });
+            if endian != Endian::NATIVE {
+                for scalar in bytes[start..].chunks_exact_mut(Pixel::SCALAR_SIZE) {
+                    scalar.reverse();
+                }
+            }
+        }
+        bytes
+    }
+    pub fn to_be_bytes(&self) -> Vec<u8> {
+        self.to_bytes(Endian::Big)
+    }
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.to_bytes(Endian::Little)
+    }
+}
+
+impl<Pixel: ScalarLayout> Image<Box<[Pixel]>, Pixel> {
+    /// Reads `width * height` tightly packed pixels, with every scalar in the given byte order.
+    /// The error gives the stride in bytes, saturated if the size does not fit in `usize`.
+    pub fn from_bytes(
+        width: usize,
+        height: usize,
+        bytes: &[u8],
+        endian: Endian,
+    ) -> Result<Self, SourceTooSmall<&[u8], u8>> {
+        let stride = width.checked_mul(size_of::<Pixel>());
+        let Some(len) = stride
+            .and_then(|stride| stride.checked_mul(height))
+            .filter(|&len| len <= bytes.len())
+        else {
+            let stride = stride.unwrap_or(usize::MAX);
+            return Err(SourceTooSmall::new(bytes, width, height, stride));
+        };
+        let mut buf = Box::<[Pixel]>::new_uninit_slice(width * height);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), len) };
+        dst.copy_from_slice(&bytes[..len]);
+        if endian != Endian::NATIVE {
+            for scalar in dst.chunks_exact_mut(Pixel::SCALAR_SIZE) {
+                scalar.reverse();
+            }
+        }
+        Ok(unsafe { Image::from_source_unchecked(width, height, buf.assume_init()) })
+    }
+    pub fn from_be_bytes(
+        width: usize,
+        height: usize,
+        bytes: &[u8],
+    ) -> Result<Self, SourceTooSmall<&[u8], u8>> {
+        Self::from_bytes(width, height, bytes, Endian::Big)
+    }
+    pub fn from_le_bytes(
+        width: usize,
+        height: usize,
+        bytes: &[u8],
+    ) -> Result<Self, SourceTooSmall<&[u8], u8>> {
+        Self::from_bytes(width, height, bytes, Endian::Little)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Image<Box<[Rgb<u16>]>, Rgb<u16>> {
+        let mut image = Image::filled(4, 3, Rgb { r: 0, g: 0, b: 0 });
+        for ([x, y], pixel) in image.iter_mut() {
+            let v = (x + y * 4) as u16;
+            *pixel = Rgb {
+                r: v << 8 | 1,
+                g: v << 8 | 2,
+                b: v << 8 | 3,
+            };
+        }
+        image
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = sample();
+        let be = image.to_be_bytes();
+        let le = image.to_le_bytes();
+        assert_eq!(be.len(), 4 * 3 * 6);
+        assert_eq!(&be[6..12], [1, 1, 1, 2, 1, 3]);
+        assert_eq!(&le[6..12], [1, 1, 2, 1, 3, 1]);
+        assert_eq!(Image::from_be_bytes(4, 3, &be).unwrap(), image);
+        assert_eq!(Image::from_le_bytes(4, 3, &le).unwrap(), image);
+        assert_ne!(
+            Image::<Box<[Rgb<u16>]>, _>::from_le_bytes(4, 3, &be).unwrap(),
+            image
+        );
+        // Trailing bytes are ignored.
+        let mut long = be.clone();
+        long.push(0);
+        assert_eq!(Image::from_be_bytes(4, 3, &long).unwrap(), image);
+    }
+
+    #[test]
+    fn region_drops_the_stride() {
+        let image = sample();
+        let region = image.region([1, 0]..[3, 2]).ok().unwrap();
+        let bytes = region.to_be_bytes();
+        assert_eq!(bytes.len(), 2 * 2 * 6);
+        let copy = Image::<Box<[Rgb<u16>]>, _>::from_be_bytes(2, 2, &bytes).unwrap();
+        assert!(copy.iter().eq(region.iter()));
+    }
+
+    #[test]
+    fn too_small() {
+        let bytes = sample().to_le_bytes();
+        let Err(err) = Image::<Box<[Rgb<u16>]>, _>::from_le_bytes(4, 3, &bytes[..71]) else {
+            panic!("a short buffer was accepted");
+        };
+        assert_eq!([err.width, err.height, err.stride], [4, 3, 24]);
+        let Err(err) = Image::<Box<[Rgb<u16>]>, _>::from_le_bytes(1 << 32, 1 << 32, &[]) else {
+            panic!("an overflowing size was accepted");
+        };
+        assert_eq!(err.stride, 6 << 32);
+        assert!(Image::<Box<[u8]>, u8>::from_le_bytes(usize::MAX, 2, &[]).is_err());
+    }
+}
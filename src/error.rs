@@ -46,6 +46,38 @@ where
     }
 }
 #[derive(Debug)]
+pub struct InvalidPlane {
+    pub plane: usize,
+    pub reason: InvalidPlaneReason,
+}
+#[derive(Debug)]
+pub enum InvalidPlaneReason {
+    StrideTooSmall,
+    PastEnd,
+    /// The plane's size, on the right, does not match the one implied by the luma plane.
+    SizeMismatch(SizeMismatch),
+}
+#[derive(Debug)]
+pub struct TooManyColors {
+    pub max: usize,
+}
+#[derive(Debug)]
+pub struct OutsidePalette {
+    pub pos: [usize; 2],
+    pub index: usize,
+    pub palette_len: usize,
+}
+#[derive(Debug)]
+pub struct TransparentOutsidePalette {
+    pub index: usize,
+    pub palette_len: usize,
+}
+#[derive(Debug)]
+pub struct SizeMismatch {
+    pub left: [usize; 2],
+    pub right: [usize; 2],
+}
+#[derive(Debug)]
 pub enum WhichAxes {
     X,
     Y,
@@ -107,3 +139,81 @@ where
     Pixel: Debug,
 {
 }
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(std::io::Error),
+    /// The data does not follow the format.
+    Invalid(&'static str),
+    /// Valid data using a feature that is not implemented.
+    Unsupported(&'static str),
+    /// The data ends before the format says it should.
+    Truncated,
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    SizeMismatch(SizeMismatch),
+    #[cfg(feature = "png")]
+    PngDecoding(png::DecodingError),
+    #[cfg(feature = "png")]
+    PngEncoding(png::EncodingError),
+}
+
+impl From<std::io::Error> for CodecError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<SizeMismatch> for CodecError {
+    fn from(value: SizeMismatch) -> Self {
+        Self::SizeMismatch(value)
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<png::DecodingError> for CodecError {
+    fn from(value: png::DecodingError) -> Self {
+        match value {
+            png::DecodingError::IoError(err) => Self::Io(err),
+            value => Self::PngDecoding(value),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<png::EncodingError> for CodecError {
+    fn from(value: png::EncodingError) -> Self {
+        match value {
+            png::EncodingError::IoError(err) => Self::Io(err),
+            value => Self::PngEncoding(value),
+        }
+    }
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Io(err) => Display::fmt(err, f),
+            CodecError::Invalid(reason) => write!(f, "invalid data: {reason}"),
+            CodecError::Unsupported(feature) => write!(f, "unsupported: {feature}"),
+            CodecError::Truncated => write!(f, "data is truncated"),
+            CodecError::ChecksumMismatch { expected, found } => {
+                write!(
+                    f,
+                    "checksum mismatch: expected {expected:08x}, found {found:08x}"
+                )
+            }
+            CodecError::SizeMismatch(SizeMismatch { left, right }) => {
+                write!(f, "size mismatch: {left:?} and {right:?}")
+            }
+            #[cfg(feature = "png")]
+            CodecError::PngDecoding(err) => Display::fmt(err, f),
+            #[cfg(feature = "png")]
+            CodecError::PngEncoding(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
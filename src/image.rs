@@ -268,8 +268,8 @@ where
 {
     pub fn map<T>(&self, f: impl Fn(&Pixel) -> T) -> Image<Box<[T]>, T> {
         let mut buf = Box::new_uninit_slice(self.width * self.height);
-        for (i, (_, p)) in self.iter().enumerate() {
-            buf[i].write(f(p));
+        for (dst, p) in buf.iter_mut().zip(self.iter_rows().flatten()) {
+            dst.write(f(p));
         }
         unsafe { Image::from_source_unchecked(self.width, self.height, buf.assume_init()) }
     }
@@ -301,10 +301,10 @@ where
             reason: {
                 if i % self.stride >= self.width {
                     IndexOutOfRangeReason::OutsideStride
-                } else if i >= self.max_index() {
+                } else if i > self.max_index() {
                     IndexOutOfRangeReason::PastEnd
                 } else {
-                    return Ok([i % self.width, i / self.width]);
+                    return Ok([i % self.stride, i / self.stride]);
                 }
             },
         })
@@ -475,3 +475,45 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_to_pos_with_stride() {
+        let source: Box<[u8]> = (0..10).collect();
+        let image = Image::from_source_with_stride(3, 2, 5, source).ok().unwrap();
+        assert_eq!(image.max_index(), 7);
+        assert!(matches!(image.index_to_pos(5), Ok([0, 1])));
+        // The last pixel, which is not at the end of the source.
+        assert!(matches!(image.index_to_pos(7), Ok([2, 1])));
+        assert!(matches!(
+            image.index_to_pos(8),
+            Err(IndexOutOfRange {
+                reason: IndexOutOfRangeReason::OutsideStride,
+                ..
+            })
+        ));
+        assert!(matches!(
+            image.index_to_pos(10),
+            Err(IndexOutOfRange {
+                reason: IndexOutOfRangeReason::PastEnd,
+                ..
+            })
+        ));
+        let pixels: Vec<_> = image.iter().map(|(pos, &pixel)| (pos, pixel)).collect();
+        assert_eq!(pixels.len(), 6);
+        assert_eq!(pixels.last(), Some(&([2, 1], 7)));
+    }
+
+    #[test]
+    fn region_iter_reaches_last_pixel() {
+        let source: Box<[u8]> = (0..20).collect();
+        let image = Image::from_source(5, 4, source).ok().unwrap();
+        let region = image.region([1, 1]..[4, 3]).ok().unwrap();
+        assert_eq!([region.width(), region.height(), region.stride()], [3, 2, 5]);
+        let pixels: Vec<u8> = region.iter().map(|(_, &pixel)| pixel).collect();
+        assert_eq!(pixels, [6, 7, 8, 11, 12, 13]);
+    }
+}
@@ -45,6 +45,8 @@ where
 {
     image: &'a Image<Source, Pixel>,
     row: usize,
+    /// One past the last row not yet returned from the back.
+    end: usize,
 }
 
 impl<'a, Source, Pixel> IterRows<'a, Source, Pixel>
@@ -52,7 +54,20 @@ where
     Source: AsRef<[Pixel]>,
 {
     pub const fn new(image: &'a Image<Source, Pixel>) -> Self {
-        Self { image, row: 0 }
+        Self {
+            image,
+            row: 0,
+            end: image.height(),
+        }
+    }
+    fn get(&self, row: usize) -> &'a [Pixel] {
+        unsafe {
+            self.image.source().as_ref().index({
+                let start = row * self.image.stride();
+                let end = start + self.image.width();
+                start..end
+            })
+        }
     }
 }
 
@@ -63,22 +78,30 @@ where
     type Item = &'a [Pixel];
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.row == self.image.height() {
+        if self.row == self.end {
             None
         } else {
-            let ret = unsafe {
-                self.image.source().as_ref().index({
-                    let start = self.row * self.image.stride();
-                    let end = start + self.image.width();
-                    start..end
-                })
-            };
+            let ret = self.get(self.row);
             self.row += 1;
             Some(ret)
         }
     }
 }
 
+impl<'a, Source, Pixel> DoubleEndedIterator for IterRows<'a, Source, Pixel>
+where
+    Source: AsRef<[Pixel]>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.row == self.end {
+            None
+        } else {
+            self.end -= 1;
+            Some(self.get(self.end))
+        }
+    }
+}
+
 pub struct IterMut<'a, Source, Pixel>
 where
     Source: AsRef<[Pixel]> + AsMut<[Pixel]>,
@@ -124,6 +147,8 @@ where
 {
     image: &'a mut Image<Source, Pixel>,
     row: usize,
+    /// One past the last row not yet returned from the back.
+    end: usize,
 }
 
 impl<'a, Source, Pixel> IterRowsMut<'a, Source, Pixel>
@@ -131,7 +156,13 @@ where
     Source: AsRef<[Pixel]> + AsMut<[Pixel]>,
 {
     pub fn new(image: &'a mut Image<Source, Pixel>) -> Self {
-        Self { image, row: 0 }
+        let end = image.height();
+        Self { image, row: 0, end }
+    }
+    fn get(&mut self, row: usize) -> &'a mut [Pixel] {
+        let start = row * self.image.stride();
+        let end = start + self.image.width();
+        unsafe { &mut *(self.image.source_mut().as_mut().index_mut(start..end) as *mut _) }
     }
 }
 
@@ -142,16 +173,26 @@ where
     type Item = &'a mut [Pixel];
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.row == self.image.height() {
+        if self.row == self.end {
             None
         } else {
-            let ret = {
-                let start = self.row * self.image.stride();
-                let end = start + self.image.width();
-                unsafe { &mut *(self.image.source_mut().as_mut().index_mut(start..end) as *mut _) }
-            };
+            let ret = self.get(self.row);
             self.row += 1;
             Some(ret)
         }
     }
 }
+
+impl<'a, Source, Pixel> DoubleEndedIterator for IterRowsMut<'a, Source, Pixel>
+where
+    Source: AsRef<[Pixel]> + AsMut<[Pixel]>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.row == self.end {
+            None
+        } else {
+            self.end -= 1;
+            Some(self.get(self.end))
+        }
+    }
+}
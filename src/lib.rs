@@ -17,17 +17,85 @@
 /// new_uninit feature gate
 /// const_trait feature gate (no need for const_refs_to_cell)
 /// POST 1.0:
-/// png feature gate
 /// basic drawing
 /// improve cursor.
 /// chunks iterator
 /// std feature gate
+pub mod alpha;
+pub mod bits;
+pub mod channel;
+pub mod codec;
+pub mod color;
 pub mod cursor;
+pub mod depth;
+pub mod dither;
+pub mod dynamic;
+pub mod endian;
 pub mod error;
 pub mod image;
 pub mod index;
 pub mod iterator;
+pub mod packed;
+pub mod palette;
+pub mod pixel;
+pub mod plain;
+pub mod planar;
+pub mod quantize;
+pub mod swizzle;
+pub mod ycbcr;
+pub mod yuv;
 pub use cursor::ImageCursor;
 pub use error::Error;
 pub use image::Image;
 pub use index::ImageIndex;
+pub use pixel::{Luma, LumaA, Rgb, Rgba};
+pub use plain::{Pixel, PlainData};
+
+/// ```
+/// use generic_image::{self as image, Pixel};
+///
+/// #[derive(Clone, Copy, Pixel)]
+/// #[generic_image(crate = image)]
+/// #[repr(C)]
+/// struct Gray {
+///     value: u16,
+/// }
+///
+/// assert_eq!(Gray::channel("value").unwrap().ty, "u16");
+/// ```
+///
+/// Padding, generics and anything but structs are rejected:
+///
+/// ```compile_fail,E0080
+/// # use generic_image::Pixel;
+/// #[derive(Clone, Copy, Pixel)]
+/// #[repr(C)]
+/// struct Padded {
+///     value: u16,
+///     flag: u8,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use generic_image::Pixel;
+/// #[derive(Clone, Copy, Pixel)]
+/// #[repr(C)]
+/// struct Generic<T> {
+///     value: T,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use generic_image::Pixel;
+/// #[derive(Clone, Copy, Pixel)]
+/// #[repr(u8)]
+/// enum Level {
+///     Low,
+///     High,
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use generic_image_derive::Pixel;
+
+// Lets the derive macros name this crate from within it as well.
+extern crate self as generic_image;
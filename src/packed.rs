@@ -0,0 +1,226 @@
+use crate::{
+    endian::ScalarLayout,
+    pixel::{Rgb, Rgba},
+};
+
+/// Scales a `from`-bit value to `to` bits, rounding to nearest.
+pub(crate) const fn reduce(value: u32, from: u32, to: u32) -> u32 {
+    let from_max = (1 << from) - 1;
+    (value * ((1 << to) - 1) + from_max / 2) / from_max
+}
+
+/// Widens a `from`-bit value to `to` bits by repeating its bits.
+pub(crate) const fn expand(value: u32, from: u32, to: u32) -> u32 {
+    let mut out = 0;
+    let mut filled = 0;
+    while filled < to {
+        out = (out << from) | value;
+        filled += from;
+    }
+    out >> (filled - to)
+}
+
+pub(crate) const fn field(word: u32, shift: u32, bits: u32) -> u32 {
+    (word >> shift) & ((1 << bits) - 1)
+}
+
+/// Red in the top 5 bits, then 6 bits of green and 5 of blue.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb565(pub u16);
+
+/// Blue in the top 5 bits, then 6 bits of green and 5 of red.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bgr565(pub u16);
+
+/// Red, green, blue and alpha, 4 bits each from the top.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgba4444(pub u16);
+
+/// Red, green and blue, 5 bits each from the top, and alpha in the lowest bit.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgba5551(pub u16);
+
+/// Red, green and blue, 10 bits each from the lowest bit, and alpha in the top 2 bits.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb10A2(pub u32);
+
+impl From<Rgb<u8>> for Rgb565 {
+    fn from(Rgb { r, g, b }: Rgb<u8>) -> Self {
+        let (r, g, b) = (
+            reduce(r as u32, 8, 5),
+            reduce(g as u32, 8, 6),
+            reduce(b as u32, 8, 5),
+        );
+        Self((r << 11 | g << 5 | b) as u16)
+    }
+}
+
+impl From<Rgb565> for Rgb<u8> {
+    fn from(Rgb565(word): Rgb565) -> Self {
+        let word = word as u32;
+        Rgb {
+            r: expand(field(word, 11, 5), 5, 8) as u8,
+            g: expand(field(word, 5, 6), 6, 8) as u8,
+            b: expand(field(word, 0, 5), 5, 8) as u8,
+        }
+    }
+}
+
+impl From<Rgb<u8>> for Bgr565 {
+    fn from(Rgb { r, g, b }: Rgb<u8>) -> Self {
+        Self(Rgb565::from(Rgb { r: b, g, b: r }).0)
+    }
+}
+
+impl From<Bgr565> for Rgb<u8> {
+    fn from(Bgr565(word): Bgr565) -> Self {
+        let Rgb { r, g, b } = Rgb565(word).into();
+        Rgb { r: b, g, b: r }
+    }
+}
+
+impl From<Rgba<u8>> for Rgba4444 {
+    fn from(Rgba { r, g, b, a }: Rgba<u8>) -> Self {
+        let [r, g, b, a] = [r, g, b, a].map(|c| reduce(c as u32, 8, 4));
+        Self((r << 12 | g << 8 | b << 4 | a) as u16)
+    }
+}
+
+impl From<Rgba4444> for Rgba<u8> {
+    fn from(Rgba4444(word): Rgba4444) -> Self {
+        let [r, g, b, a] =
+            [12, 8, 4, 0].map(|shift| expand(field(word as u32, shift, 4), 4, 8) as u8);
+        Rgba { r, g, b, a }
+    }
+}
+
+impl From<Rgba<u8>> for Rgba5551 {
+    fn from(Rgba { r, g, b, a }: Rgba<u8>) -> Self {
+        let [r, g, b] = [r, g, b].map(|c| reduce(c as u32, 8, 5));
+        let a = reduce(a as u32, 8, 1);
+        Self((r << 11 | g << 6 | b << 1 | a) as u16)
+    }
+}
+
+impl From<Rgba5551> for Rgba<u8> {
+    fn from(Rgba5551(word): Rgba5551) -> Self {
+        let [r, g, b] = [11, 6, 1].map(|shift| expand(field(word as u32, shift, 5), 5, 8) as u8);
+        Rgba {
+            r,
+            g,
+            b,
+            a: expand(field(word as u32, 0, 1), 1, 8) as u8,
+        }
+    }
+}
+
+impl From<Rgba<u16>> for Rgb10A2 {
+    fn from(Rgba { r, g, b, a }: Rgba<u16>) -> Self {
+        let [r, g, b] = [r, g, b].map(|c| reduce(c as u32, 16, 10));
+        let a = reduce(a as u32, 16, 2);
+        Self(a << 30 | b << 20 | g << 10 | r)
+    }
+}
+
+impl From<Rgb10A2> for Rgba<u16> {
+    fn from(Rgb10A2(word): Rgb10A2) -> Self {
+        let [r, g, b] = [0, 10, 20].map(|shift| expand(field(word, shift, 10), 10, 16) as u16);
+        Rgba {
+            r,
+            g,
+            b,
+            a: expand(field(word, 30, 2), 2, 16) as u16,
+        }
+    }
+}
+
+unsafe impl ScalarLayout for Rgb565 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for Bgr565 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for Rgba4444 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for Rgba5551 {
+    const SCALAR_SIZE: usize = 2;
+}
+unsafe impl ScalarLayout for Rgb10A2 {
+    const SCALAR_SIZE: usize = 4;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb565() {
+        let pack = |rgb: [u8; 3]| Rgb565::from(Rgb::from(rgb)).0;
+        assert_eq!(pack([255, 0, 0]), 0xf800);
+        assert_eq!(pack([0, 255, 0]), 0x07e0);
+        assert_eq!(pack([0, 0, 255]), 0x001f);
+        assert_eq!(pack([8, 4, 255]), 0x083f);
+        assert_eq!(<[u8; 3]>::from(Rgb::from(Rgb565(0x083f))), [8, 4, 255]);
+        assert_eq!(Bgr565::from(Rgb::from([8, 4, 255])).0, 0xf821);
+        assert_eq!(<[u8; 3]>::from(Rgb::from(Bgr565(0xf821))), [8, 4, 255]);
+        for word in 0..=u16::MAX {
+            assert_eq!(Rgb565::from(Rgb::from(Rgb565(word))).0, word);
+            assert_eq!(Bgr565::from(Rgb::from(Bgr565(word))).0, word);
+        }
+    }
+
+    #[test]
+    fn rgba4444() {
+        let pack = |rgba: [u8; 4]| Rgba4444::from(Rgba::from(rgba)).0;
+        assert_eq!(pack([0x11, 0x22, 0x33, 0xff]), 0x123f);
+        assert_eq!(pack([0x18, 0x27, 0, 0]), 0x1200);
+        assert_eq!(
+            <[u8; 4]>::from(Rgba::from(Rgba4444(0x123f))),
+            [0x11, 0x22, 0x33, 0xff]
+        );
+        for word in 0..=u16::MAX {
+            assert_eq!(Rgba4444::from(Rgba::from(Rgba4444(word))).0, word);
+        }
+    }
+
+    #[test]
+    fn rgba5551() {
+        let pack = |rgba: [u8; 4]| Rgba5551::from(Rgba::from(rgba)).0;
+        assert_eq!(pack([255, 0, 0, 255]), 0xf801);
+        assert_eq!(pack([0, 0, 255, 0]), 0x003e);
+        // Alpha rounds at half.
+        assert_eq!(pack([0, 0, 0, 127]), 0);
+        assert_eq!(pack([0, 0, 0, 128]), 1);
+        assert_eq!(
+            <[u8; 4]>::from(Rgba::from(Rgba5551(0x0843))),
+            [8, 8, 8, 255]
+        );
+        for word in 0..=u16::MAX {
+            assert_eq!(Rgba5551::from(Rgba::from(Rgba5551(word))).0, word);
+        }
+    }
+
+    #[test]
+    fn rgb10a2() {
+        let pack = |rgba: [u16; 4]| Rgb10A2::from(Rgba::from(rgba)).0;
+        assert_eq!(pack([0xffff, 0, 0, 0]), 0x0000_03ff);
+        assert_eq!(pack([0, 0xffff, 0, 0]), 0x000f_fc00);
+        assert_eq!(pack([0, 0, 0xffff, 0]), 0x3ff0_0000);
+        assert_eq!(pack([0, 0, 0, 0xffff]), 0xc000_0000);
+        let unpack = |word| <[u16; 4]>::from(Rgba::from(Rgb10A2(word)));
+        assert_eq!(unpack(0x4000_0155), [0x5555, 0, 0, 0x5555]);
+        assert_eq!(unpack(0x8018_0000), [0, 0x8020, 0x0040, 0xaaaa]);
+        for value in 0..1 << 10 {
+            for shift in [0, 10, 20] {
+                let word = (value & 3) << 30 | value << shift;
+                assert_eq!(Rgb10A2::from(Rgba::from(Rgb10A2(word))).0, word);
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    error::{OutsidePalette, TooManyColors, TransparentOutsidePalette},
+    Image, ImageIndex,
+};
+use core::{hash::Hash, ops::Index};
+use std::collections::HashMap;
+
+pub trait PaletteIndex: Copy + Eq + Hash + Into<usize> + TryFrom<usize> {}
+
+impl PaletteIndex for u8 {}
+impl PaletteIndex for u16 {}
+
+/// An image of indices into a palette of colors.
+pub struct PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]>,
+    I: PaletteIndex,
+{
+    indices: Image<Source, I>,
+    palette: Vec<P>,
+    transparent: Vec<I>,
+}
+
+impl<Source, I, P> PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]>,
+    I: PaletteIndex,
+{
+    /// Fails if any index points past the end of the palette.
+    pub fn new(indices: Image<Source, I>, palette: Vec<P>) -> Result<Self, OutsidePalette> {
+        for (y, row) in indices.iter_rows().enumerate() {
+            if let Some(x) = row.iter().position(|&i| i.into() >= palette.len()) {
+                return Err(OutsidePalette {
+                    pos: [x, y],
+                    index: row[x].into(),
+                    palette_len: palette.len(),
+                });
+            }
+        }
+        Ok(unsafe { Self::new_unchecked(indices, palette) })
+    }
+    /// # Safety
+    /// Every index must point into `palette`. Looking up colors panics otherwise, and
+    /// [`PaletteImage::remap`] and [`PaletteImage::compact`] may produce invalid indices.
+    pub unsafe fn new_unchecked(indices: Image<Source, I>, palette: Vec<P>) -> Self {
+        Self {
+            indices,
+            palette,
+            transparent: Vec::new(),
+        }
+    }
+    pub const fn width(&self) -> usize {
+        self.indices.width()
+    }
+    pub const fn height(&self) -> usize {
+        self.indices.height()
+    }
+    pub const fn indices(&self) -> &Image<Source, I> {
+        &self.indices
+    }
+    pub fn palette(&self) -> &[P] {
+        &self.palette
+    }
+    pub fn palette_mut(&mut self) -> &mut [P] {
+        &mut self.palette
+    }
+    pub fn into_parts(self) -> (Image<Source, I>, Vec<P>) {
+        (self.indices, self.palette)
+    }
+    pub fn transparent_indices(&self) -> &[I] {
+        &self.transparent
+    }
+    /// Fails if any index points past the end of the palette, keeping the previous indices.
+    pub fn set_transparent_indices(
+        &mut self,
+        transparent: Vec<I>,
+    ) -> Result<(), TransparentOutsidePalette> {
+        if let Some(&index) = transparent
+            .iter()
+            .find(|&&i| i.into() >= self.palette.len())
+        {
+            return Err(TransparentOutsidePalette {
+                index: index.into(),
+                palette_len: self.palette.len(),
+            });
+        }
+        self.transparent = transparent;
+        Ok(())
+    }
+    pub fn is_transparent(&self, index: I) -> bool {
+        self.transparent.contains(&index)
+    }
+    /// The palette index at `index`, as opposed to the color that indexing the image gives.
+    pub fn index_at(&self, index: impl ImageIndex) -> I {
+        self.indices[index]
+    }
+    pub fn iter(&self) -> impl Iterator<Item = ([usize; 2], &P)> {
+        self.iter_rows()
+            .enumerate()
+            .flat_map(|(y, row)| row.enumerate().map(move |(x, color)| ([x, y], color)))
+    }
+    pub fn iter_rows(&self) -> impl Iterator<Item = impl Iterator<Item = &P>> {
+        self.indices
+            .iter_rows()
+            .map(|row| row.iter().map(|&i| &self.palette[i.into()]))
+    }
+}
+
+impl<Source, I, P> PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]>,
+    I: PaletteIndex,
+    P: Copy,
+{
+    pub fn to_truecolor(&self) -> Image<Box<[P]>, P> {
+        self.indices.map(|&i| self.palette[i.into()])
+    }
+    /// Like [`PaletteImage::to_truecolor`], with every transparent index replaced by `transparent`.
+    pub fn to_truecolor_or(&self, transparent: P) -> Image<Box<[P]>, P> {
+        self.indices.map(|&i| {
+            if self.is_transparent(i) {
+                transparent
+            } else {
+                self.palette[i.into()]
+            }
+        })
+    }
+}
+
+impl<Source, I, P> PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]> + AsMut<[I]>,
+    I: PaletteIndex,
+{
+    pub fn indices_mut(&mut self) -> &mut Image<Source, I> {
+        &mut self.indices
+    }
+    /// Replaces the palette, moving every pixel from index `old` to `mapping[old]`.
+    ///
+    /// # Panics
+    /// If `mapping` is shorter than the current palette or maps outside `palette`.
+    pub fn remap(&mut self, mapping: &[I], palette: Vec<P>) {
+        assert!(mapping.len() >= self.palette.len());
+        assert!(mapping[..self.palette.len()]
+            .iter()
+            .all(|&i| i.into() < palette.len()));
+        for row in self.indices.iter_rows_mut() {
+            for i in row {
+                *i = mapping[(*i).into()];
+            }
+        }
+        for i in &mut self.transparent {
+            *i = mapping[(*i).into()];
+        }
+        self.palette = palette;
+    }
+}
+
+impl<Source, I, P> PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]> + AsMut<[I]>,
+    I: PaletteIndex,
+    P: Copy + Eq + Hash,
+{
+    /// Drops unused and duplicate palette entries, keeping the order of first occurrences.
+    /// Duplicates are only merged when both or neither are transparent.
+    pub fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+        for &i in self.indices.iter_rows().flatten() {
+            used[i.into()] = true;
+        }
+        let mut palette = Vec::new();
+        let mut seen = HashMap::new();
+        let mapping: Vec<usize> = self
+            .palette
+            .iter()
+            .enumerate()
+            .map(|(old, &color)| {
+                if !used[old] {
+                    return usize::MAX;
+                }
+                let transparent = self.transparent.iter().any(|&i| i.into() == old);
+                *seen.entry((color, transparent)).or_insert_with(|| {
+                    palette.push(color);
+                    palette.len() - 1
+                })
+            })
+            .collect();
+        let to_index = |old: I| I::try_from(mapping[old.into()]).ok().unwrap();
+        for i in self.indices.iter_rows_mut().flatten() {
+            *i = to_index(*i);
+        }
+        let mut transparent: Vec<I> = self
+            .transparent
+            .iter()
+            .filter(|&&i| used[i.into()])
+            .map(|&i| to_index(i))
+            .collect();
+        transparent.sort_by_key(|&i| i.into());
+        transparent.dedup();
+        self.transparent = transparent;
+        self.palette = palette;
+    }
+}
+
+impl<I, P> PaletteImage<Box<[I]>, I, P>
+where
+    I: PaletteIndex,
+    P: Copy + Eq + Hash,
+{
+    /// Fails if the image has more than `max_colors` distinct colors, or more than `I` can index.
+    pub fn from_truecolor<Source: AsRef<[P]>>(
+        image: &Image<Source, P>,
+        max_colors: usize,
+    ) -> Result<Self, TooManyColors> {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut buf = Vec::with_capacity(image.width() * image.height());
+        for &color in image.iter_rows().flatten() {
+            let index = match lookup.get(&color) {
+                Some(&index) => index,
+                None => {
+                    let index = I::try_from(palette.len())
+                        .ok()
+                        .filter(|_| palette.len() < max_colors)
+                        .ok_or(TooManyColors { max: max_colors })?;
+                    palette.push(color);
+                    lookup.insert(color, index);
+                    index
+                }
+            };
+            buf.push(index);
+        }
+        let indices = unsafe {
+            Image::from_source_unchecked(image.width(), image.height(), buf.into_boxed_slice())
+        };
+        Ok(unsafe { Self::new_unchecked(indices, palette) })
+    }
+}
+
+impl<Source, I, P, Idx: ImageIndex> Index<Idx> for PaletteImage<Source, I, P>
+where
+    Source: AsRef<[I]>,
+    I: PaletteIndex,
+{
+    type Output = P;
+
+    fn index(&self, index: Idx) -> &P {
+        &self.palette[self.indices[index].into()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transparent_indices_stay_in_palette() {
+        let indices = Image::from_source(3, 1, Box::from([0u8, 1, 2]))
+            .ok()
+            .unwrap();
+        let mut image = PaletteImage::new(indices, vec!['a', 'b', 'c', 'd']).unwrap();
+        image.set_transparent_indices(vec![3]).unwrap();
+        assert!(matches!(
+            image.set_transparent_indices(vec![1, 4]),
+            Err(TransparentOutsidePalette {
+                index: 4,
+                palette_len: 4
+            })
+        ));
+        assert_eq!(image.transparent_indices(), [3]);
+        image.remap(&[2, 1, 0, 3], vec!['c', 'b', 'a', 'd']);
+        assert_eq!(image.transparent_indices(), [3]);
+        // The transparent entry is unused, so it goes away along with it.
+        image.compact();
+        assert_eq!(image.palette(), ['c', 'b', 'a']);
+        assert!(image.transparent_indices().is_empty());
+    }
+}
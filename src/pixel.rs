@@ -0,0 +1,77 @@
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Luma<T> {
+    pub l: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LumaA<T> {
+    pub l: T,
+    pub a: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgba<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+    pub a: T,
+}
+
+impl<T> From<[T; 1]> for Luma<T> {
+    fn from([l]: [T; 1]) -> Self {
+        Self { l }
+    }
+}
+
+impl<T> From<Luma<T>> for [T; 1] {
+    fn from(Luma { l }: Luma<T>) -> Self {
+        [l]
+    }
+}
+
+impl<T> From<[T; 2]> for LumaA<T> {
+    fn from([l, a]: [T; 2]) -> Self {
+        Self { l, a }
+    }
+}
+
+impl<T> From<LumaA<T>> for [T; 2] {
+    fn from(LumaA { l, a }: LumaA<T>) -> Self {
+        [l, a]
+    }
+}
+
+impl<T> From<[T; 3]> for Rgb<T> {
+    fn from([r, g, b]: [T; 3]) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl<T> From<Rgb<T>> for [T; 3] {
+    fn from(Rgb { r, g, b }: Rgb<T>) -> Self {
+        [r, g, b]
+    }
+}
+
+impl<T> From<[T; 4]> for Rgba<T> {
+    fn from([r, g, b, a]: [T; 4]) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl<T> From<Rgba<T>> for [T; 4] {
+    fn from(Rgba { r, g, b, a }: Rgba<T>) -> Self {
+        [r, g, b, a]
+    }
+}
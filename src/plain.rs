@@ -0,0 +1,96 @@
+use crate::{
+    alpha::{PremultipliedLumaA, PremultipliedRgba},
+    packed::{Bgr565, Rgb10A2, Rgb565, Rgba4444, Rgba5551},
+    pixel::{Luma, LumaA, Rgb, Rgba},
+    ycbcr::YCbCr,
+    Image, ImageCursor,
+};
+use core::mem::size_of_val;
+
+/// Types that can be viewed as, and created from, raw bytes.
+///
+/// # Safety
+/// There must be no padding bytes, and every bit pattern must be a valid value.
+pub unsafe trait PlainData: Copy + 'static {}
+
+unsafe impl PlainData for u8 {}
+unsafe impl PlainData for i8 {}
+unsafe impl PlainData for u16 {}
+unsafe impl PlainData for i16 {}
+unsafe impl PlainData for u32 {}
+unsafe impl PlainData for i32 {}
+unsafe impl PlainData for u64 {}
+unsafe impl PlainData for i64 {}
+unsafe impl PlainData for f32 {}
+unsafe impl PlainData for f64 {}
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+unsafe impl<T: PlainData> PlainData for Luma<T> {}
+unsafe impl<T: PlainData> PlainData for LumaA<T> {}
+unsafe impl<T: PlainData> PlainData for Rgb<T> {}
+unsafe impl<T: PlainData> PlainData for Rgba<T> {}
+unsafe impl<T: PlainData> PlainData for YCbCr<T> {}
+unsafe impl<T: PlainData> PlainData for PremultipliedRgba<T> {}
+unsafe impl<T: PlainData> PlainData for PremultipliedLumaA<T> {}
+unsafe impl PlainData for Rgb565 {}
+unsafe impl PlainData for Bgr565 {}
+unsafe impl PlainData for Rgba4444 {}
+unsafe impl PlainData for Rgba5551 {}
+unsafe impl PlainData for Rgb10A2 {}
+
+/// One field of a pixel struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelInfo {
+    pub name: &'static str,
+    /// Byte offset within the pixel.
+    pub offset: usize,
+    pub size: usize,
+    /// The type as written in the struct.
+    pub ty: &'static str,
+}
+
+/// Pixels with named channels, usually from `#[derive(Pixel)]`.
+pub trait Pixel: PlainData {
+    const CHANNELS: &'static [ChannelInfo];
+
+    fn channel(name: &str) -> Option<&'static ChannelInfo> {
+        Self::CHANNELS.iter().find(|channel| channel.name == name)
+    }
+}
+
+pub fn as_bytes<T: PlainData>(slice: &[T]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(slice.as_ptr().cast(), size_of_val(slice)) }
+}
+
+pub fn as_bytes_mut<T: PlainData>(slice: &mut [T]) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(slice.as_mut_ptr().cast(), size_of_val(slice)) }
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]>,
+    P: PlainData,
+{
+    pub fn row_bytes(&self, y: usize) -> Option<&[u8]> {
+        self.iter_rows().nth(y).map(as_bytes)
+    }
+    /// Every byte of the source, including the gaps between rows.
+    pub fn source_bytes(&self) -> &[u8] {
+        as_bytes(unsafe { self.source() }.as_ref())
+    }
+    pub fn byte_cursor(&self) -> ImageCursor<Source, P, &Self> {
+        unsafe { self.cursor() }
+    }
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]> + AsMut<[P]>,
+    P: PlainData,
+{
+    pub fn row_bytes_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        self.iter_rows_mut().nth(y).map(as_bytes_mut)
+    }
+    pub fn byte_cursor_mut(&mut self) -> ImageCursor<Source, P, &mut Self> {
+        unsafe { self.cursor_mut() }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{error::SizeMismatch, Image};
+
+/// `N` single-channel planes of equal size, one per channel.
+pub struct PlanarImage<Source, T, const N: usize>
+where
+    Source: AsRef<[T]>,
+{
+    planes: [Image<Source, T>; N],
+}
+
+impl<Source, T, const N: usize> PlanarImage<Source, T, N>
+where
+    Source: AsRef<[T]>,
+{
+    /// Fails if any plane differs in size from the first.
+    pub fn new(planes: [Image<Source, T>; N]) -> Result<Self, SizeMismatch> {
+        if let Some(first) = planes.first() {
+            let size = [first.width(), first.height()];
+            for plane in &planes[1..] {
+                if [plane.width(), plane.height()] != size {
+                    return Err(SizeMismatch {
+                        left: size,
+                        right: [plane.width(), plane.height()],
+                    });
+                }
+            }
+        }
+        Ok(Self { planes })
+    }
+    pub fn width(&self) -> usize {
+        self.planes.first().map_or(0, Image::width)
+    }
+    pub fn height(&self) -> usize {
+        self.planes.first().map_or(0, Image::height)
+    }
+    pub fn plane(&self, channel: usize) -> &Image<Source, T> {
+        &self.planes[channel]
+    }
+    pub fn planes(&self) -> &[Image<Source, T>; N] {
+        &self.planes
+    }
+    pub fn into_planes(self) -> [Image<Source, T>; N] {
+        self.planes
+    }
+    /// Maps every sample, passing the channel it belongs to along with it.
+    pub fn map<U>(&self, f: impl Fn(usize, &T) -> U) -> PlanarImage<Box<[U]>, U, N> {
+        PlanarImage {
+            planes: core::array::from_fn(|c| self.planes[c].map(|t| f(c, t))),
+        }
+    }
+}
+
+impl<Source, T, const N: usize> PlanarImage<Source, T, N>
+where
+    Source: AsRef<[T]>,
+    T: Copy,
+{
+    pub fn merge_channels<P: From<[T; N]>>(&self) -> Image<Box<[P]>, P> {
+        let (width, height) = (self.width(), self.height());
+        let mut rows = self.planes.each_ref().map(|plane| plane.iter_rows());
+        let mut buf = Vec::with_capacity(width * height);
+        for _ in 0..height {
+            let row = rows.each_mut().map(|rows| rows.next().unwrap());
+            buf.extend((0..width).map(|x| P::from(row.map(|row| row[x]))));
+        }
+        unsafe { Image::from_source_unchecked(width, height, buf.into_boxed_slice()) }
+    }
+}
+
+impl<Source, T, const N: usize> PlanarImage<Source, T, N>
+where
+    Source: AsRef<[T]> + AsMut<[T]>,
+{
+    pub fn plane_mut(&mut self, channel: usize) -> &mut Image<Source, T> {
+        &mut self.planes[channel]
+    }
+    pub fn planes_mut(&mut self) -> &mut [Image<Source, T>; N] {
+        &mut self.planes
+    }
+    /// Maps the samples of one channel in place.
+    pub fn map_channel(&mut self, channel: usize, f: impl Fn(&T) -> T) {
+        for sample in self.planes[channel].iter_rows_mut().flatten() {
+            *sample = f(sample);
+        }
+    }
+}
+
+impl<Source, Pixel> Image<Source, Pixel>
+where
+    Source: AsRef<[Pixel]>,
+    Pixel: Copy,
+{
+    pub fn split_channels<T, const N: usize>(&self) -> PlanarImage<Box<[T]>, T, N>
+    where
+        Pixel: Into<[T; N]>,
+    {
+        let mut bufs: [Vec<T>; N] =
+            core::array::from_fn(|_| Vec::with_capacity(self.width() * self.height()));
+        for &pixel in self.iter_rows().flatten() {
+            for (buf, sample) in bufs.iter_mut().zip(pixel.into()) {
+                buf.push(sample);
+            }
+        }
+        PlanarImage {
+            planes: bufs.map(|buf| unsafe {
+                Image::from_source_unchecked(self.width(), self.height(), buf.into_boxed_slice())
+            }),
+        }
+    }
+}
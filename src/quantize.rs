@@ -0,0 +1,475 @@
+use crate::{
+    palette::PaletteImage,
+    pixel::{Rgb, Rgba},
+    Image,
+};
+use std::collections::HashMap;
+
+/// Pixels that can be reduced to a palette, viewed as RGBA.
+pub trait Quantizable: Copy {
+    fn to_rgba(self) -> [u8; 4];
+    fn from_rgba(rgba: [u8; 4]) -> Self;
+}
+
+impl Quantizable for Rgb<u8> {
+    fn to_rgba(self) -> [u8; 4] {
+        [self.r, self.g, self.b, u8::MAX]
+    }
+    fn from_rgba([r, g, b, _]: [u8; 4]) -> Self {
+        Rgb { r, g, b }
+    }
+}
+
+impl Quantizable for Rgba<u8> {
+    fn to_rgba(self) -> [u8; 4] {
+        self.into()
+    }
+    fn from_rgba(rgba: [u8; 4]) -> Self {
+        rgba.into()
+    }
+}
+
+impl Quantizable for [u8; 3] {
+    fn to_rgba(self) -> [u8; 4] {
+        [self[0], self[1], self[2], u8::MAX]
+    }
+    fn from_rgba([r, g, b, _]: [u8; 4]) -> Self {
+        [r, g, b]
+    }
+}
+
+impl Quantizable for [u8; 4] {
+    fn to_rgba(self) -> [u8; 4] {
+        self
+    }
+    fn from_rgba(rgba: [u8; 4]) -> Self {
+        rgba
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuantizeMethod {
+    MedianCut,
+    Octree,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuantizeOptions {
+    /// Clamped to `2..=256`.
+    pub colors: usize,
+    pub method: QuantizeMethod,
+    /// Rounds of k-means refinement run on the initial palette.
+    pub kmeans_iterations: usize,
+    /// Treat alpha as a fourth dimension. When unset, alpha is ignored and every entry is opaque.
+    pub alpha: bool,
+    /// Weigh the channels by their contribution to luma when comparing colors.
+    pub perceptual: bool,
+}
+
+impl QuantizeOptions {
+    pub const fn new(colors: usize) -> Self {
+        Self {
+            colors,
+            method: QuantizeMethod::MedianCut,
+            kmeans_iterations: 0,
+            alpha: false,
+            perceptual: false,
+        }
+    }
+    fn weights(&self) -> [f32; 4] {
+        let alpha = if self.alpha { 1.0 } else { 0.0 };
+        if self.perceptual {
+            [0.299 * 3.0, 0.587 * 3.0, 0.114 * 3.0, alpha]
+        } else {
+            [1.0, 1.0, 1.0, alpha]
+        }
+    }
+    fn canonical(&self, rgba: [u8; 4]) -> [u8; 4] {
+        if self.alpha {
+            rgba
+        } else {
+            [rgba[0], rgba[1], rgba[2], u8::MAX]
+        }
+    }
+}
+
+fn distance(weights: &[f32; 4], lhs: [u8; 4], rhs: [f32; 4]) -> f32 {
+    (0..4)
+        .map(|c| {
+            let d = lhs[c] as f32 - rhs[c];
+            weights[c] * d * d
+        })
+        .sum()
+}
+
+/// The index of the palette entry closest to `color` under the metric of `options`.
+pub fn nearest_index<P: Quantizable>(palette: &[P], color: P, options: &QuantizeOptions) -> usize {
+    let weights = options.weights();
+    let color = options.canonical(color.to_rgba());
+    let mut best = (0, f32::INFINITY);
+    for (i, entry) in palette.iter().enumerate() {
+        let d = distance(&weights, color, entry.to_rgba().map(f32::from));
+        if d < best.1 {
+            best = (i, d);
+        }
+    }
+    best.0
+}
+
+struct Histogram {
+    colors: Vec<([u8; 4], u64)>,
+}
+
+impl Histogram {
+    fn new<Source, P>(image: &Image<Source, P>, options: &QuantizeOptions) -> Self
+    where
+        Source: AsRef<[P]>,
+        P: Quantizable,
+    {
+        let mut counts = HashMap::new();
+        if image.width() != 0 && image.height() != 0 {
+            for (_, pixel) in image.iter() {
+                *counts
+                    .entry(options.canonical(pixel.to_rgba()))
+                    .or_insert(0) += 1;
+            }
+        }
+        let mut colors: Vec<_> = counts.into_iter().collect();
+        colors.sort_unstable();
+        Self { colors }
+    }
+}
+
+fn mean(colors: &[([u8; 4], u64)]) -> [f32; 4] {
+    let mut sum = [0.0f64; 4];
+    let mut total = 0u64;
+    for &(color, count) in colors {
+        for c in 0..4 {
+            sum[c] += color[c] as f64 * count as f64;
+        }
+        total += count;
+    }
+    sum.map(|s| (s / total.max(1) as f64) as f32)
+}
+
+fn median_cut(histogram: &Histogram, options: &QuantizeOptions, colors: usize) -> Vec<[f32; 4]> {
+    let weights = options.weights();
+    let mut boxes = vec![histogram.colors.clone()];
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (channel, range) = (0..4)
+                    .map(|c| {
+                        let min = colors.iter().map(|(color, _)| color[c]).min().unwrap();
+                        let max = colors.iter().map(|(color, _)| color[c]).max().unwrap();
+                        (c, (max - min) as f32 * weights[c].sqrt())
+                    })
+                    .fold((0, -1.0), |a, b| if b.1 > a.1 { b } else { a });
+                (i, channel, range)
+            })
+            .fold(None, |best: Option<(usize, usize, f32)>, b| match best {
+                Some(a) if a.2 >= b.2 => Some(a),
+                _ => Some(b),
+            });
+        let Some((i, channel, _)) = widest else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut running = 0;
+        let split = colors
+            .iter()
+            .position(|(_, count)| {
+                running += count;
+                running * 2 >= total
+            })
+            .unwrap()
+            .min(colors.len() - 2)
+            + 1;
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|colors| mean(colors)).collect()
+}
+
+#[derive(Default)]
+struct OctreeNode {
+    children: Vec<Option<usize>>,
+    sum: [u64; 4],
+    count: u64,
+    leaf: bool,
+}
+
+fn octree(histogram: &Histogram, options: &QuantizeOptions, colors: usize) -> Vec<[f32; 4]> {
+    const DEPTH: usize = 8;
+    let channels = if options.alpha { 4 } else { 3 };
+    let mut nodes = vec![OctreeNode {
+        children: vec![None; 1 << channels],
+        ..Default::default()
+    }];
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); DEPTH];
+    let mut leaves = 0;
+    for &(color, count) in &histogram.colors {
+        let mut node = 0;
+        for depth in 0..DEPTH {
+            let slot = (0..channels).fold(0, |slot, c| {
+                (slot << 1) | ((color[c] >> (7 - depth)) & 1) as usize
+            });
+            node = match nodes[node].children[slot] {
+                Some(child) => child,
+                None => {
+                    let child = nodes.len();
+                    let leaf = depth == DEPTH - 1;
+                    nodes.push(OctreeNode {
+                        children: if leaf {
+                            Vec::new()
+                        } else {
+                            vec![None; 1 << channels]
+                        },
+                        leaf,
+                        ..Default::default()
+                    });
+                    nodes[node].children[slot] = Some(child);
+                    if leaf {
+                        leaves += 1;
+                    } else {
+                        levels[depth + 1].push(child);
+                    }
+                    child
+                }
+            };
+        }
+        for (sum, &channel) in nodes[node].sum.iter_mut().zip(&color) {
+            *sum += channel as u64 * count;
+        }
+        nodes[node].count += count;
+    }
+    levels[0].push(0);
+    // Counts of inner nodes are only needed to pick what to merge, so fill them bottom up.
+    for depth in (0..DEPTH).rev() {
+        for &node in &levels[depth] {
+            let count = nodes[node]
+                .children
+                .iter()
+                .flatten()
+                .map(|&child| nodes[child].count)
+                .sum();
+            nodes[node].count = count;
+        }
+    }
+    for depth in (0..DEPTH).rev() {
+        let mut level = std::mem::take(&mut levels[depth]);
+        level.sort_unstable_by_key(|&node| std::cmp::Reverse(nodes[node].count));
+        while leaves > colors {
+            let Some(node) = level.pop() else {
+                break;
+            };
+            let children: Vec<usize> = nodes[node].children.iter().flatten().copied().collect();
+            let mut sum = [0; 4];
+            for &child in &children {
+                for (sum, channel) in sum.iter_mut().zip(nodes[child].sum) {
+                    *sum += channel;
+                }
+            }
+            nodes[node].sum = sum;
+            nodes[node].leaf = true;
+            nodes[node].children.clear();
+            leaves = leaves + 1 - children.len();
+        }
+        if leaves <= colors {
+            break;
+        }
+    }
+    let mut palette = Vec::new();
+    let mut stack = vec![0];
+    while let Some(node) = stack.pop() {
+        let node = &nodes[node];
+        if node.leaf {
+            if node.count != 0 {
+                palette.push(node.sum.map(|s| s as f32 / node.count as f32));
+            }
+        } else {
+            stack.extend(node.children.iter().flatten());
+        }
+    }
+    palette
+}
+
+fn kmeans(histogram: &Histogram, options: &QuantizeOptions, palette: &mut [[f32; 4]]) {
+    let weights = options.weights();
+    let mut assignment = vec![usize::MAX; histogram.colors.len()];
+    for _ in 0..options.kmeans_iterations {
+        let mut changed = false;
+        let mut sums = vec![([0.0f64; 4], 0u64); palette.len()];
+        for (&(color, count), assigned) in histogram.colors.iter().zip(&mut assignment) {
+            let nearest = (0..palette.len())
+                .min_by(|&a, &b| {
+                    distance(&weights, color, palette[a])
+                        .total_cmp(&distance(&weights, color, palette[b]))
+                })
+                .unwrap();
+            changed |= *assigned != nearest;
+            *assigned = nearest;
+            for (sum, &channel) in sums[nearest].0.iter_mut().zip(&color) {
+                *sum += channel as f64 * count as f64;
+            }
+            sums[nearest].1 += count;
+        }
+        for (entry, (sum, count)) in palette.iter_mut().zip(sums) {
+            if count != 0 {
+                *entry = sum.map(|s| (s / count as f64) as f32);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+impl<Source, P> Image<Source, P>
+where
+    Source: AsRef<[P]>,
+    P: Quantizable,
+{
+    pub fn quantize(&self, options: QuantizeOptions) -> PaletteImage<Box<[u8]>, u8, P> {
+        let colors = options.colors.clamp(2, 256);
+        let histogram = Histogram::new(self, &options);
+        let mut palette = if histogram.colors.len() <= colors {
+            histogram
+                .colors
+                .iter()
+                .map(|(color, _)| color.map(f32::from))
+                .collect()
+        } else {
+            match options.method {
+                QuantizeMethod::MedianCut => median_cut(&histogram, &options, colors),
+                QuantizeMethod::Octree => octree(&histogram, &options, colors),
+            }
+        };
+        kmeans(&histogram, &options, &mut palette);
+        let palette: Vec<P> = palette
+            .into_iter()
+            .map(|entry| P::from_rgba(options.canonical(entry.map(|c| c.round() as u8))))
+            .collect();
+
+        let mut cache = HashMap::new();
+        let mut buf = Vec::with_capacity(self.width() * self.height());
+        for pixel in self.iter_rows().flatten() {
+            let key = options.canonical(pixel.to_rgba());
+            let index = *cache
+                .entry(key)
+                .or_insert_with(|| nearest_index(&palette, *pixel, &options) as u8);
+            buf.push(index);
+        }
+        let indices = unsafe {
+            Image::from_source_unchecked(self.width(), self.height(), buf.into_boxed_slice())
+        };
+        unsafe { PaletteImage::new_unchecked(indices, palette) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [QuantizeMethod; 2] = [QuantizeMethod::MedianCut, QuantizeMethod::Octree];
+
+    fn image<P>(
+        width: usize,
+        height: usize,
+        pixel: impl Fn(usize, usize) -> P,
+    ) -> Image<Box<[P]>, P> {
+        let pixels = (0..width * height).map(|i| pixel(i % width, i / width));
+        Image::from_source(width, height, pixels.collect())
+            .ok()
+            .unwrap()
+    }
+
+    #[test]
+    fn palette_size_is_bounded() {
+        let gradient = image(32, 32, |x, y| {
+            Rgb::from([x as u8 * 8, y as u8 * 8, (x ^ y) as u8])
+        });
+        for method in METHODS {
+            for kmeans_iterations in [0, 4] {
+                for (colors, max) in [(1, 2), (2, 2), (16, 16), (100, 100), (1000, 256)] {
+                    let options = QuantizeOptions {
+                        method,
+                        kmeans_iterations,
+                        ..QuantizeOptions::new(colors)
+                    };
+                    let quantized = gradient.quantize(options);
+                    let len = quantized.palette().len();
+                    assert!((1..=max).contains(&len), "{options:?}: {len}");
+                    assert_eq!(quantized.width(), 32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn few_colors_are_exact() {
+        let colors = [
+            [0, 0, 0, 255],
+            [255, 0, 0, 255],
+            [0, 0, 255, 128],
+            [0, 0, 255, 0],
+            [7, 8, 9, 10],
+        ];
+        let rgba = image(9, 4, |x, y| Rgba::from(colors[(x * y + x) % colors.len()]));
+        let rgb = rgba.map(|p| Rgb::from([p.r, p.g, p.b]));
+        for method in METHODS {
+            for kmeans_iterations in [0, 3] {
+                let options = QuantizeOptions {
+                    method,
+                    kmeans_iterations,
+                    ..QuantizeOptions::new(colors.len())
+                };
+                assert_eq!(rgb.quantize(options).to_truecolor(), rgb);
+                let options = QuantizeOptions {
+                    alpha: true,
+                    ..options
+                };
+                let quantized = rgba.quantize(options);
+                assert_eq!(quantized.palette().len(), colors.len());
+                assert_eq!(quantized.to_truecolor(), rgba);
+            }
+        }
+    }
+
+    #[test]
+    fn nearest() {
+        let options = QuantizeOptions::new(256);
+        let palette = [[0, 0, 0], [255, 255, 255], [255, 0, 0]];
+        assert_eq!(nearest_index(&palette, [200, 30, 30], &options), 2);
+        assert_eq!(nearest_index(&palette, [100, 100, 100], &options), 0);
+        assert_eq!(nearest_index(&palette, [128, 128, 128], &options), 1);
+        // Ties go to the first entry.
+        assert_eq!(
+            nearest_index(&[[10, 0, 0], [0, 10, 0]], [0, 0, 0], &options),
+            0
+        );
+
+        // Green weighs far more than blue.
+        let palette = [[0, 0, 0], [0, 120, 255]];
+        assert_eq!(nearest_index(&palette, [0, 0, 255], &options), 1);
+        let perceptual = QuantizeOptions {
+            perceptual: true,
+            ..options
+        };
+        assert_eq!(nearest_index(&palette, [0, 0, 255], &perceptual), 0);
+
+        let palette = [[0, 0, 0, 255], [40, 40, 40, 0]];
+        assert_eq!(nearest_index(&palette, [0, 0, 0, 0], &options), 0);
+        let alpha = QuantizeOptions {
+            alpha: true,
+            ..options
+        };
+        assert_eq!(nearest_index(&palette, [0, 0, 0, 0], &alpha), 1);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::Image;
+use core::marker::PhantomData;
+
+/// Which input channel every one of the `M` output channels is taken from.
+pub trait ChannelMap<const M: usize> {
+    const MAP: [usize; M];
+}
+
+/// A single channel.
+pub struct Select<const C: usize>;
+
+impl<const C: usize> ChannelMap<1> for Select<C> {
+    const MAP: [usize; 1] = [C];
+}
+
+/// Swaps the first and third channel, turning BGRA into RGBA and back.
+pub struct SwapRb;
+
+impl ChannelMap<3> for SwapRb {
+    const MAP: [usize; 3] = [2, 1, 0];
+}
+
+impl ChannelMap<4> for SwapRb {
+    const MAP: [usize; 4] = [2, 1, 0, 3];
+}
+
+/// One channel repeated into every output channel, such as gray into RGB.
+pub struct Replicate<const C: usize>;
+
+impl<const C: usize, const M: usize> ChannelMap<M> for Replicate<C> {
+    const MAP: [usize; M] = [C; M];
+}
+
+pub(crate) const fn in_bounds<const M: usize>(map: [usize; M], n: usize) -> bool {
+    let mut i = 0;
+    while i < M {
+        if map[i] >= n {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Applies `Map` to a pixel of `N` channels.
+pub fn swizzle<T: Copy, const N: usize, const M: usize, Map: ChannelMap<M>>(
+    pixel: [T; N],
+) -> [T; M] {
+    Map::MAP.map(|c| pixel[c])
+}
+
+/// A read-only view of an image of `[T; N]` pixels as `[T; M]` pixels, reordered by `Map`.
+pub struct SwizzleView<'a, Source, T, const N: usize, const M: usize, Map>
+where
+    Source: AsRef<[[T; N]]>,
+{
+    image: &'a Image<Source, [T; N]>,
+    _map: PhantomData<Map>,
+}
+
+impl<'a, Source, T, const N: usize, const M: usize, Map> SwizzleView<'a, Source, T, N, M, Map>
+where
+    Source: AsRef<[[T; N]]>,
+    T: Copy,
+    Map: ChannelMap<M>,
+{
+    const VALID: () = assert!(in_bounds(Map::MAP, N), "channel map out of bounds");
+
+    pub const fn image(&self) -> &'a Image<Source, [T; N]> {
+        self.image
+    }
+    pub const fn width(&self) -> usize {
+        self.image.width()
+    }
+    pub const fn height(&self) -> usize {
+        self.image.height()
+    }
+    pub fn get(&self, index: impl crate::ImageIndex) -> [T; M] {
+        swizzle::<T, N, M, Map>(self.image[index])
+    }
+    pub fn iter_rows(&self) -> impl Iterator<Item = impl Iterator<Item = [T; M]> + 'a> + 'a {
+        self.image
+            .iter_rows()
+            .map(|row| row.iter().map(|&pixel| swizzle::<T, N, M, Map>(pixel)))
+    }
+    pub fn to_image(&self) -> Image<Box<[[T; M]]>, [T; M]> {
+        self.image.map(|&pixel| swizzle::<T, N, M, Map>(pixel))
+    }
+}
+
+impl<Source, T, const N: usize> Image<Source, [T; N]>
+where
+    Source: AsRef<[[T; N]]>,
+    T: Copy,
+{
+    pub fn swizzle<const M: usize, Map: ChannelMap<M>>(
+        &self,
+    ) -> SwizzleView<'_, Source, T, N, M, Map> {
+        let () = SwizzleView::<Source, T, N, M, Map>::VALID;
+        SwizzleView {
+            image: self,
+            _map: PhantomData,
+        }
+    }
+    /// A view of the single channel `C`.
+    pub fn select<const C: usize>(&self) -> SwizzleView<'_, Source, T, N, 1, Select<C>> {
+        self.swizzle()
+    }
+}
+
+impl<Source, T, const N: usize> Image<Source, [T; N]>
+where
+    Source: AsRef<[[T; N]]> + AsMut<[[T; N]]>,
+    T: Copy,
+{
+    /// Reorders the channels of every pixel in place.
+    pub fn swizzle_mut<Map: ChannelMap<N>>(&mut self) {
+        let () = SwizzleView::<Source, T, N, N, Map>::VALID;
+        for pixel in self.iter_rows_mut().flatten() {
+            *pixel = swizzle::<T, N, N, Map>(*pixel);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{pixel::Rgb, Image};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct YCbCr<T> {
+    pub y: T,
+    pub cb: T,
+    pub cr: T,
+}
+
+impl<T> From<[T; 3]> for YCbCr<T> {
+    fn from([y, cb, cr]: [T; 3]) -> Self {
+        Self { y, cb, cr }
+    }
+}
+
+impl<T> From<YCbCr<T>> for [T; 3] {
+    fn from(YCbCr { y, cb, cr }: YCbCr<T>) -> Self {
+        [y, cb, cr]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YCbCrMatrix {
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+impl YCbCrMatrix {
+    /// The luma weights `[Kr, Kg, Kb]`.
+    pub const fn luma_coefficients(self) -> [f64; 3] {
+        let (kr, kb) = match self {
+            YCbCrMatrix::Bt601 => (0.299, 0.114),
+            YCbCrMatrix::Bt709 => (0.2126, 0.0722),
+            YCbCrMatrix::Bt2020 => (0.2627, 0.0593),
+        };
+        [kr, 1.0 - kr - kb, kb]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YCbCrRange {
+    /// Every channel spans the whole code range.
+    Full,
+    /// Luma spans 16..=235 and chroma 16..=240, scaled up for deeper channels.
+    Limited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct YCbCrFormat {
+    pub matrix: YCbCrMatrix,
+    pub range: YCbCrRange,
+}
+
+impl YCbCrFormat {
+    pub const fn new(matrix: YCbCrMatrix, range: YCbCrRange) -> Self {
+        Self { matrix, range }
+    }
+
+    fn rgb_to_ycbcr<T: YCbCrChannel>(self) -> ([[f64; 3]; 3], [f64; 3]) {
+        let [kr, kg, kb] = self.matrix.luma_coefficients();
+        let half = 128.0 * T::UNIT;
+        let (luma_scale, chroma_scale, luma_offset) = match self.range {
+            YCbCrRange::Full => (1.0, 1.0, 0.0),
+            YCbCrRange::Limited => (
+                219.0 * T::UNIT / T::MAX,
+                224.0 * T::UNIT / T::MAX,
+                16.0 * T::UNIT,
+            ),
+        };
+        let cb = 2.0 * (1.0 - kb);
+        let cr = 2.0 * (1.0 - kr);
+        (
+            [
+                [kr, kg, kb].map(|k| k * luma_scale),
+                [-kr / cb, -kg / cb, (1.0 - kb) / cb].map(|k| k * chroma_scale),
+                [(1.0 - kr) / cr, -kg / cr, -kb / cr].map(|k| k * chroma_scale),
+            ],
+            [luma_offset, half, half],
+        )
+    }
+
+    fn ycbcr_to_rgb<T: YCbCrChannel>(self) -> ([[f64; 3]; 3], [f64; 3]) {
+        let (forward, offset) = self.rgb_to_ycbcr::<T>();
+        let inverse = invert(forward);
+        let offset =
+            inverse.map(|row| -(row[0] * offset[0] + row[1] * offset[1] + row[2] * offset[2]));
+        (inverse, offset)
+    }
+}
+
+fn invert(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let determinant =
+        m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    adjugate.map(|row| row.map(|v| v / determinant))
+}
+
+pub trait YCbCrChannel: Copy {
+    type Accumulator: Copy;
+    /// The largest code value.
+    const MAX: f64;
+    /// The size of one 8-bit code step, used to place the limited range.
+    const UNIT: f64;
+    fn accumulator(coefficient: f64) -> Self::Accumulator;
+    /// One row of the matrix. Rounding each coefficient on its own can make the row sum drift,
+    /// which integer channels avoid.
+    fn row(coefficients: [f64; 3]) -> [Self::Accumulator; 3] {
+        coefficients.map(Self::accumulator)
+    }
+    fn apply(
+        matrix: &[[Self::Accumulator; 3]; 3],
+        offset: &[Self::Accumulator; 3],
+        pixel: [Self; 3],
+    ) -> [Self; 3];
+}
+
+/// Fractional bits of the integer coefficients, well past the 16 bits of the deepest channel.
+const FIXED_SHIFT: u32 = 30;
+
+fn fixed(coefficient: f64) -> i64 {
+    (coefficient * (1i64 << FIXED_SHIFT) as f64).round() as i64
+}
+
+/// Rounds `row` so that its sum is rounded once, which keeps luma rows summing to their scale
+/// and chroma rows to exactly 0, so that grays map to the exact codes.
+fn fixed_row(row: [f64; 3]) -> [i64; 3] {
+    let [r, _, b] = row;
+    let (r, b) = (fixed(r), fixed(b));
+    [r, fixed(row.iter().sum()) - r - b, b]
+}
+
+fn apply_fixed(matrix: &[[i64; 3]; 3], offset: &[i64; 3], pixel: [i64; 3], max: i64) -> [i64; 3] {
+    let mut out = [0; 3];
+    for (out, (row, offset)) in out.iter_mut().zip(matrix.iter().zip(offset)) {
+        let sum = row[0] * pixel[0] + row[1] * pixel[1] + row[2] * pixel[2] + offset;
+        *out = ((sum + (1 << (FIXED_SHIFT - 1))) >> FIXED_SHIFT).clamp(0, max);
+    }
+    out
+}
+
+impl YCbCrChannel for u8 {
+    type Accumulator = i64;
+    const MAX: f64 = u8::MAX as f64;
+    const UNIT: f64 = 1.0;
+    fn accumulator(coefficient: f64) -> i64 {
+        fixed(coefficient)
+    }
+    fn row(coefficients: [f64; 3]) -> [i64; 3] {
+        fixed_row(coefficients)
+    }
+    fn apply(matrix: &[[i64; 3]; 3], offset: &[i64; 3], pixel: [u8; 3]) -> [u8; 3] {
+        apply_fixed(matrix, offset, pixel.map(i64::from), u8::MAX.into()).map(|v| v as u8)
+    }
+}
+
+impl YCbCrChannel for u16 {
+    type Accumulator = i64;
+    const MAX: f64 = u16::MAX as f64;
+    const UNIT: f64 = 256.0;
+    fn accumulator(coefficient: f64) -> i64 {
+        fixed(coefficient)
+    }
+    fn row(coefficients: [f64; 3]) -> [i64; 3] {
+        fixed_row(coefficients)
+    }
+    fn apply(matrix: &[[i64; 3]; 3], offset: &[i64; 3], pixel: [u16; 3]) -> [u16; 3] {
+        apply_fixed(matrix, offset, pixel.map(i64::from), u16::MAX.into()).map(|v| v as u16)
+    }
+}
+
+/// Floating point channels use the 8-bit code values divided by 255, and are not clamped.
+impl YCbCrChannel for f32 {
+    type Accumulator = f32;
+    const MAX: f64 = 1.0;
+    const UNIT: f64 = 1.0 / 255.0;
+    fn accumulator(coefficient: f64) -> f32 {
+        coefficient as f32
+    }
+    fn apply(matrix: &[[f32; 3]; 3], offset: &[f32; 3], [a, b, c]: [f32; 3]) -> [f32; 3] {
+        let mut out = [0.0; 3];
+        for (out, (row, offset)) in out.iter_mut().zip(matrix.iter().zip(offset)) {
+            *out = row[0] * a + row[1] * b + row[2] * c + offset;
+        }
+        out
+    }
+}
+
+pub struct YCbCrTransform<T: YCbCrChannel> {
+    matrix: [[T::Accumulator; 3]; 3],
+    offset: [T::Accumulator; 3],
+}
+
+impl<T: YCbCrChannel> YCbCrTransform<T> {
+    fn from_affine((matrix, offset): ([[f64; 3]; 3], [f64; 3])) -> Self {
+        Self {
+            matrix: matrix.map(T::row),
+            offset: offset.map(T::accumulator),
+        }
+    }
+    pub fn rgb_to_ycbcr(format: YCbCrFormat) -> Self {
+        Self::from_affine(format.rgb_to_ycbcr::<T>())
+    }
+    pub fn ycbcr_to_rgb(format: YCbCrFormat) -> Self {
+        Self::from_affine(format.ycbcr_to_rgb::<T>())
+    }
+    pub fn apply(&self, pixel: [T; 3]) -> [T; 3] {
+        T::apply(&self.matrix, &self.offset, pixel)
+    }
+}
+
+impl<T: YCbCrChannel> YCbCr<T> {
+    pub fn from_rgb(rgb: Rgb<T>, format: YCbCrFormat) -> Self {
+        YCbCrTransform::rgb_to_ycbcr(format)
+            .apply(rgb.into())
+            .into()
+    }
+    pub fn to_rgb(self, format: YCbCrFormat) -> Rgb<T> {
+        YCbCrTransform::ycbcr_to_rgb(format)
+            .apply(self.into())
+            .into()
+    }
+}
+
+fn transformed<Source, In, Out, T>(
+    image: &Image<Source, In>,
+    transform: YCbCrTransform<T>,
+) -> Image<Box<[Out]>, Out>
+where
+    Source: AsRef<[In]>,
+    In: Copy + Into<[T; 3]>,
+    Out: From<[T; 3]>,
+    T: YCbCrChannel,
+{
+    let mut buf = Box::new_uninit_slice(image.width() * image.height());
+    for (dst, pixel) in buf.iter_mut().zip(image.iter_rows().flatten()) {
+        dst.write(transform.apply((*pixel).into()).into());
+    }
+    unsafe { Image::from_source_unchecked(image.width(), image.height(), buf.assume_init()) }
+}
+
+impl<Source, T> Image<Source, Rgb<T>>
+where
+    Source: AsRef<[Rgb<T>]>,
+    T: YCbCrChannel,
+{
+    pub fn to_ycbcr(&self, format: YCbCrFormat) -> Image<Box<[YCbCr<T>]>, YCbCr<T>> {
+        transformed(self, YCbCrTransform::rgb_to_ycbcr(format))
+    }
+}
+
+impl<Source, T> Image<Source, YCbCr<T>>
+where
+    Source: AsRef<[YCbCr<T>]>,
+    T: YCbCrChannel,
+{
+    pub fn to_rgb(&self, format: YCbCrFormat) -> Image<Box<[Rgb<T>]>, Rgb<T>> {
+        transformed(self, YCbCrTransform::ycbcr_to_rgb(format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATRICES: [YCbCrMatrix; 3] =
+        [YCbCrMatrix::Bt601, YCbCrMatrix::Bt709, YCbCrMatrix::Bt2020];
+    const RANGES: [YCbCrRange; 2] = [YCbCrRange::Full, YCbCrRange::Limited];
+
+    /// The codes of `rgb`, given as fractions of white, straight from the definition.
+    fn reference(format: YCbCrFormat, rgb: [f64; 3], max: f64) -> [f64; 3] {
+        let unit = (max + 1.0) / 256.0;
+        let [kr, kg, kb] = format.matrix.luma_coefficients();
+        let y = kr * rgb[0] + kg * rgb[1] + kb * rgb[2];
+        let cb = (rgb[2] - y) / (2.0 * (1.0 - kb));
+        let cr = (rgb[0] - y) / (2.0 * (1.0 - kr));
+        let (y, cb, cr) = match format.range {
+            YCbCrRange::Full => (y * max, cb * max, cr * max),
+            YCbCrRange::Limited => (
+                (16.0 + 219.0 * y) * unit,
+                224.0 * cb * unit,
+                224.0 * cr * unit,
+            ),
+        };
+        [y, 128.0 * unit + cb, 128.0 * unit + cr].map(|v| v.round().clamp(0.0, max))
+    }
+
+    fn colors() -> [[f64; 3]; 5] {
+        [
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+
+    #[test]
+    fn grays_are_exact() {
+        for matrix in MATRICES {
+            let full = YCbCrFormat::new(matrix, YCbCrRange::Full);
+            let limited = YCbCrFormat::new(matrix, YCbCrRange::Limited);
+            for (format, black, white) in [
+                (full, [0, 128, 128], [255, 128, 128]),
+                (limited, [16, 128, 128], [235, 128, 128]),
+            ] {
+                assert_eq!(
+                    <[u8; 3]>::from(YCbCr::from_rgb(Rgb::from([0u8; 3]), format)),
+                    black
+                );
+                assert_eq!(
+                    <[u8; 3]>::from(YCbCr::from_rgb(Rgb::from([255u8; 3]), format)),
+                    white
+                );
+                assert_eq!(<[u8; 3]>::from(YCbCr::from(white).to_rgb(format)), [255; 3]);
+                assert_eq!(<[u8; 3]>::from(YCbCr::from(black).to_rgb(format)), [0; 3]);
+            }
+            for (format, black, white) in [
+                (full, [0, 32768, 32768], [65535, 32768, 32768]),
+                (limited, [4096, 32768, 32768], [60160, 32768, 32768]),
+            ] {
+                let ycbcr =
+                    |rgb: u16| <[u16; 3]>::from(YCbCr::from_rgb(Rgb::from([rgb; 3]), format));
+                assert_eq!(ycbcr(0), black, "{format:?}");
+                assert_eq!(ycbcr(u16::MAX), white, "{format:?}");
+                assert_eq!(
+                    <[u16; 3]>::from(YCbCr::from(white).to_rgb(format)),
+                    [65535; 3]
+                );
+                assert_eq!(<[u16; 3]>::from(YCbCr::from(black).to_rgb(format)), [0; 3]);
+            }
+        }
+    }
+
+    #[test]
+    fn primaries_match_the_definition() {
+        for matrix in MATRICES {
+            for range in RANGES {
+                let format = YCbCrFormat::new(matrix, range);
+                for color in colors() {
+                    let rgb = color.map(|c| (c * 255.0) as u8);
+                    let found = <[u8; 3]>::from(YCbCr::from_rgb(Rgb::from(rgb), format));
+                    let expected = reference(format, color, 255.0).map(|v| v as u8);
+                    assert_eq!(found, expected, "{format:?} {rgb:?}");
+                    let rgb = color.map(|c| (c * 65535.0) as u16);
+                    let found = <[u16; 3]>::from(YCbCr::from_rgb(Rgb::from(rgb), format));
+                    let expected = reference(format, color, 65535.0).map(|v| v as u16);
+                    assert_eq!(found, expected, "{format:?} {rgb:?}");
+                }
+            }
+        }
+        // Published values for red.
+        let red = |matrix, range| {
+            <[u8; 3]>::from(YCbCr::from_rgb(
+                Rgb {
+                    r: 255u8,
+                    g: 0,
+                    b: 0,
+                },
+                YCbCrFormat::new(matrix, range),
+            ))
+        };
+        assert_eq!(red(YCbCrMatrix::Bt601, YCbCrRange::Full), [76, 85, 255]);
+        assert_eq!(red(YCbCrMatrix::Bt709, YCbCrRange::Limited), [63, 102, 240]);
+    }
+
+    #[test]
+    fn floats_round_trip() {
+        for matrix in MATRICES {
+            for range in RANGES {
+                let format = YCbCrFormat::new(matrix, range);
+                for color in colors() {
+                    let rgb = color.map(|c| c as f32);
+                    let back: [f32; 3] = YCbCr::from_rgb(Rgb::from(rgb), format)
+                        .to_rgb(format)
+                        .into();
+                    for (a, b) in back.iter().zip(rgb) {
+                        assert!((a - b).abs() < 1e-5, "{format:?} {rgb:?} {back:?}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use crate::{
+    error::{InvalidPlane, InvalidPlaneReason, SizeMismatch},
+    pixel::Rgb,
+    ycbcr::{YCbCrFormat, YCbCrTransform},
+    Image,
+};
+
+/// Where chroma samples sit relative to the luma samples they cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChromaSiting {
+    /// Centered between luma samples on both axes (JPEG, MPEG-1).
+    Center,
+    /// Co-sited with the left luma sample, centered vertically (MPEG-2, H.264).
+    Left,
+    /// Co-sited with the top left luma sample (BT.2020 4:2:0).
+    TopLeft,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChromaFilter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct YuvOptions {
+    pub format: YCbCrFormat,
+    pub siting: ChromaSiting,
+    pub filter: ChromaFilter,
+}
+
+impl YuvOptions {
+    pub const fn new(format: YCbCrFormat) -> Self {
+        Self {
+            format,
+            siting: ChromaSiting::Center,
+            filter: ChromaFilter::Bilinear,
+        }
+    }
+    fn cosited(&self) -> [bool; 2] {
+        match self.siting {
+            ChromaSiting::Center => [false, false],
+            ChromaSiting::Left => [true, false],
+            ChromaSiting::TopLeft => [true, true],
+        }
+    }
+}
+
+/// The byte order of the chroma plane of a semi-planar frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChromaOrder {
+    /// NV12
+    Uv,
+    /// NV21
+    Vu,
+}
+
+/// The byte order of a packed 4:2:2 macropixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PackedYuvOrder {
+    /// YUY2
+    Yuyv,
+    /// UYVY
+    Uyvy,
+}
+
+/// Three 8-bit planes, with chroma subsampled by `SUB_X` and `SUB_Y`.
+pub struct PlanarYuv<Source, const SUB_X: usize, const SUB_Y: usize>
+where
+    Source: AsRef<[u8]>,
+{
+    y: Image<Source, u8>,
+    u: Image<Source, u8>,
+    v: Image<Source, u8>,
+}
+
+pub type I420<Source> = PlanarYuv<Source, 2, 2>;
+pub type I422<Source> = PlanarYuv<Source, 2, 1>;
+pub type I444<Source> = PlanarYuv<Source, 1, 1>;
+
+/// A luma plane followed by one plane of interleaved 4:2:0 chroma pairs.
+pub struct SemiPlanarYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    y: Image<Source, u8>,
+    uv: Image<Source, u8>,
+    order: ChromaOrder,
+}
+
+/// 4:2:2 frames with two luma and one chroma pair packed into every four bytes.
+pub struct PackedYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    width: usize,
+    data: Image<Source, u8>,
+    order: PackedYuvOrder,
+}
+
+const fn chroma_size(width: usize, height: usize, sub: [usize; 2]) -> [usize; 2] {
+    [width.div_ceil(sub[0]), height.div_ceil(sub[1])]
+}
+
+fn plane(
+    bytes: &[u8],
+    plane: usize,
+    offset: usize,
+    stride: usize,
+    width: usize,
+    height: usize,
+) -> Result<Image<&[u8], u8>, InvalidPlane> {
+    if stride < width {
+        return Err(InvalidPlane {
+            plane,
+            reason: InvalidPlaneReason::StrideTooSmall,
+        });
+    }
+    let end = if height == 0 {
+        offset
+    } else {
+        offset + stride * (height - 1) + width
+    };
+    match bytes.get(offset..end) {
+        Some(source) => {
+            Ok(unsafe { Image::from_source_with_stride_unchecked(width, height, stride, source) })
+        }
+        None => Err(InvalidPlane {
+            plane,
+            reason: InvalidPlaneReason::PastEnd,
+        }),
+    }
+}
+
+impl<'a, const SUB_X: usize, const SUB_Y: usize> PlanarYuv<&'a [u8], SUB_X, SUB_Y> {
+    /// Planes are given in Y, U, V order.
+    pub fn from_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+        offsets: [usize; 3],
+        strides: [usize; 3],
+    ) -> Result<Self, InvalidPlane> {
+        let [cw, ch] = chroma_size(width, height, [SUB_X, SUB_Y]);
+        Ok(Self {
+            y: plane(bytes, 0, offsets[0], strides[0], width, height)?,
+            u: plane(bytes, 1, offsets[1], strides[1], cw, ch)?,
+            v: plane(bytes, 2, offsets[2], strides[2], cw, ch)?,
+        })
+    }
+    /// Tightly packed Y, U and V planes, as in I420.
+    pub fn from_contiguous_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+    ) -> Result<Self, InvalidPlane> {
+        let [cw, ch] = chroma_size(width, height, [SUB_X, SUB_Y]);
+        let u = width * height;
+        let v = u + cw * ch;
+        Self::from_bytes(bytes, width, height, [0, u, v], [width, cw, cw])
+    }
+    /// Tightly packed Y, V and U planes, as in YV12.
+    pub fn from_contiguous_bytes_yvu(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+    ) -> Result<Self, InvalidPlane> {
+        let [cw, ch] = chroma_size(width, height, [SUB_X, SUB_Y]);
+        let v = width * height;
+        let u = v + cw * ch;
+        Self::from_bytes(bytes, width, height, [0, u, v], [width, cw, cw])
+    }
+}
+
+impl<Source, const SUB_X: usize, const SUB_Y: usize> PlanarYuv<Source, SUB_X, SUB_Y>
+where
+    Source: AsRef<[u8]>,
+{
+    /// Fails if the chroma planes do not match the subsampled size of the luma plane, handing the
+    /// planes back.
+    pub fn from_planes(
+        y: Image<Source, u8>,
+        u: Image<Source, u8>,
+        v: Image<Source, u8>,
+    ) -> Result<Self, (InvalidPlane, [Image<Source, u8>; 3])> {
+        let chroma = chroma_size(y.width(), y.height(), [SUB_X, SUB_Y]);
+        let mismatch = [&u, &v].iter().enumerate().find_map(|(index, plane)| {
+            let size = [plane.width(), plane.height()];
+            (size != chroma).then_some(InvalidPlane {
+                plane: index + 1,
+                reason: InvalidPlaneReason::SizeMismatch(SizeMismatch {
+                    left: chroma,
+                    right: size,
+                }),
+            })
+        });
+        if let Some(error) = mismatch {
+            return Err((error, [y, u, v]));
+        }
+        Ok(Self { y, u, v })
+    }
+    pub fn width(&self) -> usize {
+        self.y.width()
+    }
+    pub fn height(&self) -> usize {
+        self.y.height()
+    }
+    pub const fn y(&self) -> &Image<Source, u8> {
+        &self.y
+    }
+    pub const fn u(&self) -> &Image<Source, u8> {
+        &self.u
+    }
+    pub const fn v(&self) -> &Image<Source, u8> {
+        &self.v
+    }
+    pub fn into_planes(self) -> [Image<Source, u8>; 3] {
+        [self.y, self.u, self.v]
+    }
+    pub fn to_rgb(&self, options: YuvOptions) -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+        to_rgb(self, options)
+    }
+}
+
+impl<Source, const SUB_X: usize, const SUB_Y: usize> PlanarYuv<Source, SUB_X, SUB_Y>
+where
+    Source: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn y_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.y
+    }
+    pub fn u_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.u
+    }
+    pub fn v_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.v
+    }
+}
+
+impl<const SUB_X: usize, const SUB_Y: usize> PlanarYuv<Box<[u8]>, SUB_X, SUB_Y> {
+    pub fn from_rgb<Source>(image: &Image<Source, Rgb<u8>>, options: YuvOptions) -> Self
+    where
+        Source: AsRef<[Rgb<u8>]>,
+    {
+        let (y, chroma, [cw, ch]) = subsample(image, [SUB_X, SUB_Y], options);
+        let (u, v): (Vec<u8>, Vec<u8>) = chroma.into_iter().map(|[u, v]| (u, v)).unzip();
+        unsafe {
+            Self {
+                y: Image::from_source_unchecked(image.width(), image.height(), y),
+                u: Image::from_source_unchecked(cw, ch, u.into_boxed_slice()),
+                v: Image::from_source_unchecked(cw, ch, v.into_boxed_slice()),
+            }
+        }
+    }
+}
+
+impl<'a> SemiPlanarYuv<&'a [u8]> {
+    pub fn from_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+        order: ChromaOrder,
+        offsets: [usize; 2],
+        strides: [usize; 2],
+    ) -> Result<Self, InvalidPlane> {
+        let [cw, ch] = chroma_size(width, height, [2, 2]);
+        Ok(Self {
+            y: plane(bytes, 0, offsets[0], strides[0], width, height)?,
+            uv: plane(bytes, 1, offsets[1], strides[1], cw * 2, ch)?,
+            order,
+        })
+    }
+    pub fn from_contiguous_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+        order: ChromaOrder,
+    ) -> Result<Self, InvalidPlane> {
+        let cw = chroma_size(width, height, [2, 2])[0];
+        Self::from_bytes(
+            bytes,
+            width,
+            height,
+            order,
+            [0, width * height],
+            [width, cw * 2],
+        )
+    }
+}
+
+impl<Source> SemiPlanarYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    pub fn width(&self) -> usize {
+        self.y.width()
+    }
+    pub fn height(&self) -> usize {
+        self.y.height()
+    }
+    pub const fn order(&self) -> ChromaOrder {
+        self.order
+    }
+    pub const fn y(&self) -> &Image<Source, u8> {
+        &self.y
+    }
+    /// The interleaved chroma plane, two bytes per chroma sample.
+    pub const fn uv(&self) -> &Image<Source, u8> {
+        &self.uv
+    }
+    pub fn into_planes(self) -> [Image<Source, u8>; 2] {
+        [self.y, self.uv]
+    }
+    pub fn to_rgb(&self, options: YuvOptions) -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+        to_rgb(self, options)
+    }
+}
+
+impl<Source> SemiPlanarYuv<Source>
+where
+    Source: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn y_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.y
+    }
+    pub fn uv_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.uv
+    }
+}
+
+impl SemiPlanarYuv<Box<[u8]>> {
+    pub fn from_rgb<Source>(
+        image: &Image<Source, Rgb<u8>>,
+        order: ChromaOrder,
+        options: YuvOptions,
+    ) -> Self
+    where
+        Source: AsRef<[Rgb<u8>]>,
+    {
+        let (y, chroma, [cw, ch]) = subsample(image, [2, 2], options);
+        let uv = chroma
+            .into_iter()
+            .flat_map(|[u, v]| match order {
+                ChromaOrder::Uv => [u, v],
+                ChromaOrder::Vu => [v, u],
+            })
+            .collect();
+        unsafe {
+            Self {
+                y: Image::from_source_unchecked(image.width(), image.height(), y),
+                uv: Image::from_source_unchecked(cw * 2, ch, uv),
+                order,
+            }
+        }
+    }
+}
+
+impl<'a> PackedYuv<&'a [u8]> {
+    pub fn from_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+        order: PackedYuvOrder,
+        offset: usize,
+        stride: usize,
+    ) -> Result<Self, InvalidPlane> {
+        let row = width.div_ceil(2) * 4;
+        Ok(Self {
+            width,
+            data: plane(bytes, 0, offset, stride, row, height)?,
+            order,
+        })
+    }
+    pub fn from_contiguous_bytes(
+        bytes: &'a [u8],
+        width: usize,
+        height: usize,
+        order: PackedYuvOrder,
+    ) -> Result<Self, InvalidPlane> {
+        Self::from_bytes(bytes, width, height, order, 0, width.div_ceil(2) * 4)
+    }
+}
+
+impl<Source> PackedYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.data.height()
+    }
+    pub const fn order(&self) -> PackedYuvOrder {
+        self.order
+    }
+    /// The packed bytes, four per pair of pixels.
+    pub const fn data(&self) -> &Image<Source, u8> {
+        &self.data
+    }
+    pub fn into_data(self) -> Image<Source, u8> {
+        self.data
+    }
+    pub fn to_rgb(&self, options: YuvOptions) -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+        to_rgb(self, options)
+    }
+}
+
+impl<Source> PackedYuv<Source>
+where
+    Source: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn data_mut(&mut self) -> &mut Image<Source, u8> {
+        &mut self.data
+    }
+}
+
+impl PackedYuv<Box<[u8]>> {
+    pub fn from_rgb<Source>(
+        image: &Image<Source, Rgb<u8>>,
+        order: PackedYuvOrder,
+        options: YuvOptions,
+    ) -> Self
+    where
+        Source: AsRef<[Rgb<u8>]>,
+    {
+        let (y, chroma, [cw, ch]) = subsample(image, [2, 1], options);
+        let width = image.width();
+        let mut data = Vec::with_capacity(cw * 4 * ch);
+        for (row, chroma) in y.chunks(width.max(1)).zip(chroma.chunks(cw.max(1))) {
+            for (i, &[u, v]) in chroma.iter().enumerate() {
+                let y0 = row[i * 2];
+                let y1 = *row.get(i * 2 + 1).unwrap_or(&y0);
+                data.extend_from_slice(&match order {
+                    PackedYuvOrder::Yuyv => [y0, u, y1, v],
+                    PackedYuvOrder::Uyvy => [u, y0, v, y1],
+                });
+            }
+        }
+        Self {
+            width,
+            data: unsafe { Image::from_source_unchecked(cw * 4, ch, data.into_boxed_slice()) },
+            order,
+        }
+    }
+}
+
+trait YuvSamples {
+    const SUBSAMPLING: [usize; 2];
+    fn size(&self) -> [usize; 2];
+    fn luma(&self, x: usize, y: usize) -> u8;
+    /// The `[u, v]` pair at chroma sample coordinates.
+    fn chroma(&self, x: usize, y: usize) -> [u8; 2];
+}
+
+impl<Source, const SUB_X: usize, const SUB_Y: usize> YuvSamples for PlanarYuv<Source, SUB_X, SUB_Y>
+where
+    Source: AsRef<[u8]>,
+{
+    const SUBSAMPLING: [usize; 2] = [SUB_X, SUB_Y];
+    fn size(&self) -> [usize; 2] {
+        [self.y.width(), self.y.height()]
+    }
+    fn luma(&self, x: usize, y: usize) -> u8 {
+        self.y[[x, y]]
+    }
+    fn chroma(&self, x: usize, y: usize) -> [u8; 2] {
+        [self.u[[x, y]], self.v[[x, y]]]
+    }
+}
+
+impl<Source> YuvSamples for SemiPlanarYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    const SUBSAMPLING: [usize; 2] = [2, 2];
+    fn size(&self) -> [usize; 2] {
+        [self.y.width(), self.y.height()]
+    }
+    fn luma(&self, x: usize, y: usize) -> u8 {
+        self.y[[x, y]]
+    }
+    fn chroma(&self, x: usize, y: usize) -> [u8; 2] {
+        let pair = [self.uv[[x * 2, y]], self.uv[[x * 2 + 1, y]]];
+        match self.order {
+            ChromaOrder::Uv => pair,
+            ChromaOrder::Vu => [pair[1], pair[0]],
+        }
+    }
+}
+
+impl<Source> YuvSamples for PackedYuv<Source>
+where
+    Source: AsRef<[u8]>,
+{
+    const SUBSAMPLING: [usize; 2] = [2, 1];
+    fn size(&self) -> [usize; 2] {
+        [self.width, self.data.height()]
+    }
+    fn luma(&self, x: usize, y: usize) -> u8 {
+        let macropixel = (x / 2) * 4;
+        match self.order {
+            PackedYuvOrder::Yuyv => self.data[[macropixel + (x % 2) * 2, y]],
+            PackedYuvOrder::Uyvy => self.data[[macropixel + (x % 2) * 2 + 1, y]],
+        }
+    }
+    fn chroma(&self, x: usize, y: usize) -> [u8; 2] {
+        let macropixel = x * 4;
+        match self.order {
+            PackedYuvOrder::Yuyv => [
+                self.data[[macropixel + 1, y]],
+                self.data[[macropixel + 3, y]],
+            ],
+            PackedYuvOrder::Uyvy => [self.data[[macropixel, y]], self.data[[macropixel + 2, y]]],
+        }
+    }
+}
+
+/// The position of a luma sample in chroma sample coordinates.
+fn chroma_position(luma: usize, sub: usize, cosited: bool) -> f32 {
+    let offset = if cosited {
+        0.0
+    } else {
+        (sub as f32 - 1.0) / 2.0
+    };
+    (luma as f32 - offset) / sub as f32
+}
+
+fn to_rgb<F: YuvSamples>(frame: &F, options: YuvOptions) -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+    let [width, height] = frame.size();
+    let sub = F::SUBSAMPLING;
+    let [cw, ch] = chroma_size(width, height, sub);
+    let cosited = options.cosited();
+    let transform = YCbCrTransform::<u8>::ycbcr_to_rgb(options.format);
+    let mut buf = Box::new_uninit_slice(width * height);
+    if width == 0 || height == 0 {
+        return unsafe { Image::from_source_unchecked(width, height, buf.assume_init()) };
+    }
+    for y in 0..height {
+        let cy = chroma_position(y, sub[1], cosited[1]).clamp(0.0, (ch - 1) as f32);
+        for x in 0..width {
+            let cx = chroma_position(x, sub[0], cosited[0]).clamp(0.0, (cw - 1) as f32);
+            let [u, v] = match options.filter {
+                ChromaFilter::Nearest => frame.chroma(cx.round() as usize, cy.round() as usize),
+                ChromaFilter::Bilinear => {
+                    let (x0, y0) = (cx.floor() as usize, cy.floor() as usize);
+                    let (x1, y1) = ((x0 + 1).min(cw - 1), (y0 + 1).min(ch - 1));
+                    let (fx, fy) = (cx - x0 as f32, cy - y0 as f32);
+                    let [a, b, c, d] =
+                        [[x0, y0], [x1, y0], [x0, y1], [x1, y1]].map(|[x, y]| frame.chroma(x, y));
+                    let lerp = |i: usize| {
+                        let top = a[i] as f32 + (b[i] as f32 - a[i] as f32) * fx;
+                        let bottom = c[i] as f32 + (d[i] as f32 - c[i] as f32) * fx;
+                        (top + (bottom - top) * fy).round() as u8
+                    };
+                    [lerp(0), lerp(1)]
+                }
+            };
+            buf[x + y * width].write(transform.apply([frame.luma(x, y), u, v]).into());
+        }
+    }
+    unsafe { Image::from_source_unchecked(width, height, buf.assume_init()) }
+}
+
+/// Filter taps, relative to the first luma sample covered by a chroma sample.
+fn taps(sub: usize, cosited: bool) -> Vec<(isize, f32)> {
+    if cosited && sub > 1 {
+        vec![(-1, 0.25), (0, 0.5), (1, 0.25)]
+    } else {
+        (0..sub as isize).map(|i| (i, 1.0 / sub as f32)).collect()
+    }
+}
+
+/// Converts to full resolution YCbCr, then filters chroma down to the subsampled size.
+fn subsample<Source>(
+    image: &Image<Source, Rgb<u8>>,
+    sub: [usize; 2],
+    options: YuvOptions,
+) -> (Box<[u8]>, Vec<[u8; 2]>, [usize; 2])
+where
+    Source: AsRef<[Rgb<u8>]>,
+{
+    let (width, height) = (image.width(), image.height());
+    let [cw, ch] = chroma_size(width, height, sub);
+    let transform = YCbCrTransform::<u8>::rgb_to_ycbcr(options.format);
+    let mut luma = Vec::with_capacity(width * height);
+    let mut full_chroma = Vec::with_capacity(width * height);
+    for pixel in image.iter_rows().flatten() {
+        let [y, u, v] = transform.apply((*pixel).into());
+        luma.push(y);
+        full_chroma.push([u, v]);
+    }
+
+    let cosited = options.cosited();
+    let (taps_x, taps_y) = (taps(sub[0], cosited[0]), taps(sub[1], cosited[1]));
+    let clamp = |i: isize, len: usize| i.clamp(0, len as isize - 1) as usize;
+    let mut chroma = Vec::with_capacity(cw * ch);
+    for cy in 0..ch {
+        for cx in 0..cw {
+            let mut sum = [0.0f32; 2];
+            for &(ty, wy) in &taps_y {
+                let y = clamp((cy * sub[1]) as isize + ty, height);
+                for &(tx, wx) in &taps_x {
+                    let x = clamp((cx * sub[0]) as isize + tx, width);
+                    let [u, v] = full_chroma[x + y * width];
+                    sum[0] += u as f32 * wx * wy;
+                    sum[1] += v as f32 * wx * wy;
+                }
+            }
+            chroma.push(sum.map(|s| s.round() as u8));
+        }
+    }
+    (luma.into_boxed_slice(), chroma, [cw, ch])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ycbcr::{YCbCrMatrix, YCbCrRange};
+
+    fn options() -> YuvOptions {
+        YuvOptions::new(YCbCrFormat::new(YCbCrMatrix::Bt601, YCbCrRange::Full))
+    }
+    const SIZES: [[usize; 2]; 3] = [[6, 4], [5, 3], [1, 1]];
+
+    fn image(width: usize, height: usize, gray: bool) -> Image<Box<[Rgb<u8>]>, Rgb<u8>> {
+        let pixels = (0..width * height).map(|i| {
+            let v = (i % width * 40 + i / width * 25) as u8;
+            if gray {
+                Rgb { r: v, g: v, b: v }
+            } else {
+                Rgb {
+                    r: 200,
+                    g: 60,
+                    b: 30,
+                }
+            }
+        });
+        Image::from_source(width, height, pixels.collect())
+            .ok()
+            .unwrap()
+    }
+
+    fn bytes(plane: &Image<Box<[u8]>, u8>) -> Vec<u8> {
+        plane.iter_rows().flatten().copied().collect()
+    }
+
+    /// Gray has neutral chroma and survives subsampling exactly, a solid color nearly so.
+    fn assert_round_trips(
+        original: &Image<Box<[Rgb<u8>]>, Rgb<u8>>,
+        decoded: Image<Box<[Rgb<u8>]>, Rgb<u8>>,
+        gray: bool,
+    ) {
+        assert_eq!(
+            [decoded.width(), decoded.height()],
+            [original.width(), original.height()]
+        );
+        let tolerance = if gray { 0 } else { 2 };
+        for ((_, a), (_, b)) in original.iter().zip(decoded.iter()) {
+            let [a, b]: [[u8; 3]; 2] = [(*a).into(), (*b).into()];
+            assert!(
+                a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= tolerance),
+                "{a:?} {b:?}"
+            );
+        }
+    }
+
+    fn planar<const SUB_X: usize, const SUB_Y: usize>() {
+        for [width, height] in SIZES {
+            for gray in [true, false] {
+                let rgb = image(width, height, gray);
+                let frame = PlanarYuv::<_, SUB_X, SUB_Y>::from_rgb(&rgb, options());
+                let chroma = [width.div_ceil(SUB_X), height.div_ceil(SUB_Y)];
+                assert_eq!([frame.u().width(), frame.u().height()], chroma);
+                assert_eq!([frame.v().width(), frame.v().height()], chroma);
+                assert_round_trips(&rgb, frame.to_rgb(options()), gray);
+
+                let [y, u, v] = [frame.y(), frame.u(), frame.v()].map(bytes);
+                let contiguous = [&y[..], &u, &v].concat();
+                let parsed =
+                    PlanarYuv::<_, SUB_X, SUB_Y>::from_contiguous_bytes(&contiguous, width, height)
+                        .unwrap();
+                assert_eq!(parsed.to_rgb(options()), frame.to_rgb(options()));
+                let yvu = [&y[..], &v, &u].concat();
+                let parsed =
+                    PlanarYuv::<_, SUB_X, SUB_Y>::from_contiguous_bytes_yvu(&yvu, width, height)
+                        .unwrap();
+                assert_eq!(parsed.to_rgb(options()), frame.to_rgb(options()));
+                let short = &contiguous[..contiguous.len() - 1];
+                let error =
+                    PlanarYuv::<_, SUB_X, SUB_Y>::from_contiguous_bytes(short, width, height)
+                        .err()
+                        .unwrap();
+                assert!(matches!(error.reason, InvalidPlaneReason::PastEnd));
+            }
+        }
+    }
+
+    #[test]
+    fn planar_round_trips() {
+        planar::<2, 2>();
+        planar::<2, 1>();
+        planar::<1, 1>();
+    }
+
+    #[test]
+    fn semi_planar_round_trips() {
+        for [width, height] in SIZES {
+            for gray in [true, false] {
+                let rgb = image(width, height, gray);
+                let nv12 = SemiPlanarYuv::from_rgb(&rgb, ChromaOrder::Uv, options());
+                let nv21 = SemiPlanarYuv::from_rgb(&rgb, ChromaOrder::Vu, options());
+                let chroma = [width.div_ceil(2) * 2, height.div_ceil(2)];
+                assert_eq!([nv12.uv().width(), nv12.uv().height()], chroma);
+                let swapped: Vec<u8> = bytes(nv21.uv())
+                    .chunks(2)
+                    .flat_map(|p| [p[1], p[0]])
+                    .collect();
+                assert_eq!(swapped, bytes(nv12.uv()));
+                for frame in [nv12, nv21] {
+                    assert_round_trips(&rgb, frame.to_rgb(options()), gray);
+                    let contiguous = [bytes(frame.y()), bytes(frame.uv())].concat();
+                    let parsed = SemiPlanarYuv::from_contiguous_bytes(
+                        &contiguous,
+                        width,
+                        height,
+                        frame.order(),
+                    )
+                    .unwrap();
+                    assert_eq!(parsed.to_rgb(options()), frame.to_rgb(options()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_round_trips() {
+        for [width, height] in SIZES {
+            for gray in [true, false] {
+                let rgb = image(width, height, gray);
+                let yuyv = PackedYuv::from_rgb(&rgb, PackedYuvOrder::Yuyv, options());
+                let uyvy = PackedYuv::from_rgb(&rgb, PackedYuvOrder::Uyvy, options());
+                assert_eq!(
+                    [yuyv.data().width(), yuyv.data().height()],
+                    [width.div_ceil(2) * 4, height]
+                );
+                let swapped: Vec<u8> = bytes(uyvy.data())
+                    .chunks(2)
+                    .flat_map(|p| [p[1], p[0]])
+                    .collect();
+                assert_eq!(swapped, bytes(yuyv.data()));
+                for frame in [yuyv, uyvy] {
+                    assert_round_trips(&rgb, frame.to_rgb(options()), gray);
+                    let data = bytes(frame.data());
+                    let parsed =
+                        PackedYuv::from_contiguous_bytes(&data, width, height, frame.order())
+                            .unwrap();
+                    assert_eq!(parsed.to_rgb(options()), frame.to_rgb(options()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_planes() {
+        let plane = |width, height| {
+            Image::from_source(width, height, vec![0u8; width * height].into_boxed_slice())
+                .ok()
+                .unwrap()
+        };
+        let (error, [y, u, v]) = I420::from_planes(plane(5, 3), plane(3, 2), plane(3, 1))
+            .err()
+            .unwrap();
+        assert_eq!(error.plane, 2);
+        let InvalidPlaneReason::SizeMismatch(mismatch) = error.reason else {
+            panic!("{error:?}");
+        };
+        assert_eq!([mismatch.left, mismatch.right], [[3, 2], [3, 1]]);
+        assert_eq!([y.width(), u.width(), v.height()], [5, 3, 1]);
+        assert!(I420::from_planes(plane(5, 3), plane(3, 2), plane(3, 2)).is_ok());
+
+        let error = I444::from_bytes(&[0; 64], 4, 4, [0, 16, 32], [4, 3, 4])
+            .err()
+            .unwrap();
+        assert_eq!(error.plane, 1);
+        assert!(matches!(error.reason, InvalidPlaneReason::StrideTooSmall));
+    }
+}